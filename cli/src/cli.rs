@@ -1,9 +1,16 @@
 use std::path::PathBuf;
+use std::time::Duration;
 
 use clap::{Parser, Subcommand};
 use log::error;
 
+use crate::config_schema;
+use crate::dashboard;
+use crate::endurance;
+use crate::preflight;
 use crate::status;
+use crate::status::ReportFormat;
+use crate::status_config;
 use crate::sys;
 
 #[derive(Parser)]
@@ -35,7 +42,72 @@ struct Cli {
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Generate a system status report
-    Status {},
+    Status {
+        /// Output format of the report
+        #[clap(short, long, value_enum, default_value = "table")]
+        format: ReportFormat,
+
+        /// Periodically publish the report on the bus instead of printing it
+        #[clap(short, long)]
+        publish: bool,
+
+        /// Seconds between published reports
+        #[clap(long, value_name = "SECONDS", default_value = "10", value_parser = seconds)]
+        period: Duration,
+
+        /// Seconds to listen to the bus for flight software activity
+        #[clap(short, long, value_name = "SECONDS", default_value = "2", value_parser = seconds)]
+        listen: Duration,
+
+        /// Status report thresholds and settings
+        #[clap(
+            short,
+            long,
+            value_name = "TOML",
+            default_value = "../support_apps/config/status_config.toml"
+        )]
+        config: PathBuf,
+    },
+
+    /// Check and compare app config files
+    Config {
+        #[clap(subcommand)]
+        action: ConfigAction,
+    },
+
+    /// Watch the flight live and send ground commands
+    Dashboard {
+        /// UDP address of tlm_rx, where ground commands are sent
+        #[clap(long, value_name = "ADDR", default_value = "127.0.0.1:6666")]
+        uplink: String,
+    },
+
+    /// Print how long the ballast and lift gas are forecast to last
+    Endurance {
+        /// Output format of the forecast
+        #[clap(short, long, value_enum, default_value = "table")]
+        format: ReportFormat,
+
+        /// Seconds to wait for the control app's next forecast
+        #[clap(short, long, value_name = "SECONDS", default_value = "70", value_parser = seconds)]
+        listen: Duration,
+    },
+
+    /// Run the pre-flight go/no-go checklist
+    Preflight {
+        /// Checklist to run
+        #[clap(
+            short,
+            long,
+            value_name = "TOML",
+            default_value = "../support_apps/config/preflight_config.toml"
+        )]
+        config: PathBuf,
+
+        /// Name of the operator signing off the checklist
+        #[clap(short, long)]
+        operator: String,
+    },
 
     /// Start necessary systems for flight
     FlightReady {
@@ -62,16 +134,76 @@ enum Commands {
     },
 }
 
-pub fn parse_inputs() {
-    // parse CLI input args and options
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// Validate config files against each app's schema
+    Validate {
+        /// Files to validate; defaults to every known config in --config-dir
+        files: Vec<PathBuf>,
+
+        /// Directory holding the app config files
+        #[clap(long, value_name = "DIR", default_value = "../support_apps/config")]
+        config_dir: PathBuf,
+    },
+
+    /// Compare two configs key by key
+    Diff {
+        /// Config to compare from
+        old: PathBuf,
+        /// Config to compare to
+        new: PathBuf,
+    },
+}
+
+/// Parse a number of seconds, refusing negative, NaN or endless times
+fn seconds(arg: &str) -> Result<Duration, String> {
+    let seconds: f32 = arg
+        .parse()
+        .map_err(|_| format!("'{}' is not a number of seconds", arg))?;
+    Duration::try_from_secs_f32(seconds).map_err(|_| format!("{} s is not a valid time", seconds))
+}
+
+pub fn parse_inputs() -> i32 {
+    // parse CLI input args and options, return the process exit code
     let cli = Cli::parse();
     match &cli.command {
-        Commands::Status {} => status::full_report(),
+        Commands::Status {
+            format,
+            publish,
+            period,
+            listen,
+            config,
+        } => {
+            let config = match status_config::load(config) {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("{}", e);
+                    return 2;
+                }
+            };
+            if *publish {
+                status::publish_report(*period, *listen, &config);
+                0
+            } else {
+                status::full_report(*format, *listen, &config)
+            }
+        }
+        Commands::Config { action } => match action {
+            ConfigAction::Validate { files, config_dir } => {
+                config_schema::validate(files, config_dir)
+            }
+            ConfigAction::Diff { old, new } => config_schema::diff(old, new),
+        },
+        Commands::Dashboard { uplink } => dashboard::run(uplink),
+        Commands::Endurance { format, listen } => endurance::print_forecast(*format, *listen),
+        Commands::Preflight { config, operator } => preflight::run_checklist(config, operator),
         Commands::AltCtrl { config } => {
             sys::init_altctrl(config);
+            0
         }
         _ => {
-            error!("Command not implemented yet!")
+            error!("Command not implemented yet!");
+            1
         }
     }
 }
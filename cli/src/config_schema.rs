@@ -0,0 +1,1509 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use log::error;
+use toml::Value;
+
+use control_apps::controller::{Calibration, GainSchedule};
+use control_apps::wind::WindProfile;
+use mfc::common::mfc_msgs::Severity;
+
+use crate::status::exit_code;
+
+/// Type a config value must have
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ValueKind {
+    /// Must be written as a float, e.g. `24000.0` rather than `24000`
+    Float,
+    /// Float or integer
+    Number,
+    /// Must be written as an integer, e.g. a count of samples
+    Integer,
+    Str,
+    Bool,
+    /// Inline table of `{ warn, error }` floats, with an optional
+    /// `direction` of "above" or "below"
+    Threshold,
+    /// Array of floats, e.g. a column of a calibration table
+    FloatArray,
+    /// A float, or an array of floats, e.g. one gain or a gain per breakpoint
+    FloatOrArray,
+    /// Array of integers, e.g. a count per layer
+    IntegerArray,
+    /// Table whose keys depend on its `kind`, checked on its own
+    Table,
+    /// Array of tables whose keys depend on their `kind`, checked on their own
+    TableArray,
+}
+
+/// Expected key in a config file
+pub struct KeySpec {
+    pub key: &'static str,
+    pub kind: ValueKind,
+    pub unit: &'static str,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Whether `min` itself is out of range, for values that must be positive
+    pub min_exclusive: bool,
+}
+
+/// Expected contents of one app's config file
+pub struct Schema {
+    pub app: &'static str,
+    pub file_name: &'static str,
+    pub keys: &'static [KeySpec],
+    /// Whether the app fails without every key, or falls back to defaults
+    pub all_required: bool,
+}
+
+const fn float(
+    key: &'static str,
+    unit: &'static str,
+    min: Option<f64>,
+    max: Option<f64>,
+) -> KeySpec {
+    KeySpec {
+        key,
+        kind: ValueKind::Float,
+        unit,
+        min,
+        max,
+        min_exclusive: false,
+    }
+}
+
+const fn number(
+    key: &'static str,
+    unit: &'static str,
+    min: Option<f64>,
+    max: Option<f64>,
+) -> KeySpec {
+    KeySpec {
+        key,
+        kind: ValueKind::Number,
+        unit,
+        min,
+        max,
+        min_exclusive: false,
+    }
+}
+
+const fn positive(key: &'static str, unit: &'static str) -> KeySpec {
+    KeySpec {
+        key,
+        kind: ValueKind::Float,
+        unit,
+        min: Some(0.0),
+        max: None,
+        min_exclusive: true,
+    }
+}
+
+const fn integer(
+    key: &'static str,
+    unit: &'static str,
+    min: Option<f64>,
+    max: Option<f64>,
+) -> KeySpec {
+    KeySpec {
+        key,
+        kind: ValueKind::Integer,
+        unit,
+        min,
+        max,
+        min_exclusive: false,
+    }
+}
+
+const fn string(key: &'static str) -> KeySpec {
+    KeySpec {
+        key,
+        kind: ValueKind::Str,
+        unit: "",
+        min: None,
+        max: None,
+        min_exclusive: false,
+    }
+}
+
+const fn boolean(key: &'static str) -> KeySpec {
+    KeySpec {
+        key,
+        kind: ValueKind::Bool,
+        unit: "",
+        min: None,
+        max: None,
+        min_exclusive: false,
+    }
+}
+
+const fn table(key: &'static str, kind: ValueKind) -> KeySpec {
+    KeySpec {
+        key,
+        kind,
+        unit: "",
+        min: None,
+        max: None,
+        min_exclusive: false,
+    }
+}
+
+const fn float_array(
+    key: &'static str,
+    unit: &'static str,
+    min: Option<f64>,
+    max: Option<f64>,
+) -> KeySpec {
+    KeySpec {
+        key,
+        kind: ValueKind::FloatArray,
+        unit,
+        min,
+        max,
+        min_exclusive: false,
+    }
+}
+
+const fn integer_array(
+    key: &'static str,
+    unit: &'static str,
+    min: Option<f64>,
+    max: Option<f64>,
+) -> KeySpec {
+    KeySpec {
+        key,
+        kind: ValueKind::IntegerArray,
+        unit,
+        min,
+        max,
+        min_exclusive: false,
+    }
+}
+
+const fn float_or_array(
+    key: &'static str,
+    unit: &'static str,
+    min: Option<f64>,
+    max: Option<f64>,
+) -> KeySpec {
+    KeySpec {
+        key,
+        kind: ValueKind::FloatOrArray,
+        unit,
+        min,
+        max,
+        min_exclusive: false,
+    }
+}
+
+const fn threshold(key: &'static str, unit: &'static str) -> KeySpec {
+    KeySpec {
+        key,
+        kind: ValueKind::Threshold,
+        unit,
+        min: Some(0.0),
+        max: None,
+        min_exclusive: false,
+    }
+}
+
+/// Keys read by `ControlMngr::new` and the rest of `control_app`
+const CONTROL_KEYS: &[KeySpec] = &[
+    float("target_altitude_m", "m", Some(0.0), Some(50_000.0)),
+    string("vent_gain_schedule_by"),
+    float_array("vent_gain_schedule_at", "", Some(0.0), None),
+    float_or_array("vent_kp", "", Some(0.0), None),
+    float_or_array("vent_ki", "", Some(0.0), None),
+    float_or_array("vent_kd", "", Some(0.0), None),
+    string("dump_gain_schedule_by"),
+    float_array("dump_gain_schedule_at", "", Some(0.0), None),
+    float_or_array("dump_kp", "", Some(0.0), None),
+    float_or_array("dump_ki", "", Some(0.0), None),
+    float_or_array("dump_kd", "", Some(0.0), None),
+    float("rate_kp", "", Some(0.0), None),
+    float("rate_ki", "", Some(0.0), None),
+    float("rate_kd", "", Some(0.0), None),
+    float("profile_ascent_rate_m_s", "m/s", Some(0.0), None),
+    float("profile_descent_rate_m_s", "m/s", None, Some(0.0)),
+    float("profile_reentry_error_m", "m", Some(0.0), None),
+    float("altitude_floor_m", "m", Some(0.0), Some(50_000.0)),
+    float("descent_rate_m_s", "m/s", None, Some(0.0)),
+    float("landing_rate_m_s", "m/s", None, Some(0.0)),
+    float("ground_altitude_m", "m", Some(-500.0), Some(9_000.0)),
+    float("flare_height_m", "m", Some(0.0), None),
+    float("touchdown_height_m", "m", Some(0.0), None),
+    float("landing_ballast_kg", "kg", Some(0.0), None),
+    float("landed_rate_m_s", "m/s", Some(0.0), None),
+    float("landed_time_s", "s", Some(0.0), None),
+    float("economy_band_low_m", "m", Some(0.0), Some(50_000.0)),
+    float("economy_band_high_m", "m", Some(0.0), Some(50_000.0)),
+    float("economy_horizon_s", "s", Some(0.0), None),
+    float("economy_ballast_kg_per_day", "kg/day", Some(0.0), None),
+    float("economy_gas_kg_per_day", "kg/day", Some(0.0), None),
+    float("endurance_period_s", "s", Some(0.0), None),
+    float("endurance_min_history_s", "s", Some(0.0), None),
+    float("endurance_step_s", "s", Some(1.0), None),
+    float("endurance_horizon_days", "days", Some(0.0), Some(365.0)),
+    float("landing_step_m", "m", Some(1.0), None),
+    float("landing_wind_sigma_m_s", "m/s", Some(0.0), None),
+    float("landing_rate_sigma", "", Some(0.0), Some(1.0)),
+    float("parachute_mass_kg", "kg", Some(0.0), None),
+    positive("parachute_drag_area_m2", "m^2"),
+    string("wind_profile_file"),
+    float("wind_layer_m", "m", Some(1.0), None),
+    integer("wind_min_samples", "", Some(1.0), None),
+    float("wind_min_baseline_s", "s", Some(0.1), None),
+    float("wind_max_gap_s", "s", Some(0.1), None),
+    float("wind_max_speed_m_s", "m/s", Some(0.0), None),
+    float("wind_survey_period_s", "s", Some(0.0), None),
+    string("measured_wind_file"),
+    float("error_deadzone_m", "m", Some(0.0), None),
+    float("error_ready_threshold_m", "m", Some(0.0), None),
+    float("speed_deadzone_m_s", "m/s", Some(0.0), None),
+    float("tlm_max_age_s", "s", Some(0.0), None),
+    float("min_ballast_kg", "kg", Some(0.0), None),
+    float("ctrl_rate_hz", "Hz", Some(0.0), Some(100.0)),
+    float("qnh_hpa", "hPa", Some(850.0), Some(1100.0)),
+    float("baro_crosscheck_m", "m", Some(0.0), None),
+    float("baro_noise_m", "m", Some(0.0), None),
+    float("baro_latency_s", "s", Some(0.0), Some(10.0)),
+    float("gps_noise_m", "m", Some(0.0), None),
+    float("gps_latency_s", "s", Some(0.0), Some(10.0)),
+    float("fusion_accel_noise_m_s2", "m/s^2", Some(0.0), None),
+    float("fusion_gate_sigma", "", Some(1.0), None),
+    float("sensor_frozen_s", "s", Some(0.0), None),
+    float("phase_ascent_rate_m_s", "m/s", Some(0.0), None),
+    float("phase_float_rate_m_s", "m/s", Some(0.0), None),
+    float("phase_descent_rate_m_s", "m/s", None, Some(0.0)),
+    float("phase_landed_altitude_m", "m", Some(-500.0), Some(50_000.0)),
+    float("phase_dwell_s", "s", Some(0.0), None),
+    float("burst_rate_m_s", "m/s", None, Some(0.0)),
+    float("burst_deceleration_m_s2", "m/s^2", Some(0.0), None),
+    float("uncommanded_descent_rate_m_s", "m/s", None, Some(0.0)),
+    float("burst_confirm_s", "s", Some(0.0), None),
+    float("leak_confirm_s", "s", Some(0.0), None),
+    float("anomaly_reminder_s", "s", Some(0.0), None),
+    float("solar_lead_s", "s", Some(0.0), None),
+    float("sunset_ballast_kg", "kg", Some(0.0), None),
+    float("sunrise_vent_s", "s", Some(0.0), None),
+    float("altitude_min_m", "m", Some(-1_000.0), Some(50_000.0)),
+    float("altitude_max_m", "m", Some(0.0), Some(60_000.0)),
+    float("altitude_max_rate_m_s", "m/s", Some(0.0), None),
+    float("ballast_min_kg", "kg", None, None),
+    float("ballast_max_kg", "kg", Some(0.0), None),
+    float("ballast_max_rate_kg_s", "kg/s", Some(0.0), None),
+    integer("median_window", "", Some(1.0), Some(100.0)),
+    float("bad_tlm_timeout_s", "s", Some(0.0), None),
+    float("dump_flow_uncertainty", "", Some(0.0), Some(1.0)),
+    float("load_cell_noise_kg", "kg", Some(0.0), None),
+    float("ballast_fault_kg", "kg", Some(0.0), None),
+    float_array("vent_cal_pwm", "", Some(0.0), Some(1.0)),
+    float_array("vent_cal_flow_kg_s", "kg/s", Some(0.0), None),
+    float_array("dump_cal_pwm", "", Some(0.0), Some(1.0)),
+    float_array("dump_cal_flow_kg_s", "kg/s", Some(0.0), None),
+    float(
+        "lift_gas_molar_mass_kg_mol",
+        "kg/mol",
+        Some(0.0),
+        Some(0.0289644),
+    ),
+    float("vent_area_m2", "m^2", Some(0.0), None),
+    float("vent_head_m", "m", Some(0.0), None),
+    float("balloon_volume_m3", "m^3", Some(0.0), None),
+    float("system_mass_kg", "kg", Some(0.0), None),
+    float("initial_lift_gas_kg", "kg", Some(0.0), None),
+    float("valve_min_on_s", "s", Some(0.0), None),
+    float("valve_min_off_s", "s", Some(0.0), None),
+    float("valve_max_open_s", "s", Some(0.0), None),
+    float("valve_cooldown_s", "s", Some(0.0), None),
+    float("vent_budget_s_per_hr", "s", Some(0.0), Some(3600.0)),
+    float("ballast_budget_kg_per_hr", "kg", Some(0.0), None),
+    float("pwm_slew_rate_per_s", "1/s", Some(0.0), None),
+];
+
+/// Keys read by `status_config::load`
+const STATUS_KEYS: &[KeySpec] = &[
+    string("recorder_dir"),
+    string("eth_interface"),
+    threshold("cpu_load_pct", "%"),
+    number("temperature_warn_c", "C", None, Some(150.0)),
+    number("temperature_critical_c", "C", None, Some(150.0)),
+    threshold("clock_error_ms", "ms"),
+    threshold("heartbeat_age_s", "s"),
+    threshold("topic_age_s", "s"),
+    threshold("recorder_disk_used_pct", "%"),
+    threshold("recorder_disk_free_mb", "MB"),
+    threshold("process_cpu_pct", "%"),
+    threshold("process_memory_mb", "MB"),
+    threshold("process_open_fds", ""),
+];
+
+/// Keys of a wind profile, read by `WindProfile::load`. A forecast may leave
+/// out `samples`.
+const WIND_KEYS: &[KeySpec] = &[
+    float_array("base_m", "m", Some(-500.0), Some(50_000.0)),
+    float_array("top_m", "m", Some(-500.0), Some(50_000.0)),
+    float_array("speed_m_s", "m/s", Some(0.0), Some(150.0)),
+    float_array("direction_deg", "deg", Some(0.0), Some(360.0)),
+    integer_array("samples", "", Some(0.0), None),
+];
+
+/// Top level keys of a pre-flight checklist
+const PREFLIGHT_KEYS: &[KeySpec] = &[
+    string("report_dir"),
+    string("status_config"),
+    string("uplink"),
+    table("cutdown", ValueKind::Table),
+    table("checks", ValueKind::TableArray),
+];
+
+/// Keys of each kind of cutdown backend
+const CUTDOWN_KINDS: &[(&str, &[KeySpec])] = &[
+    ("simulated", &[boolean("continuous")]),
+    ("sysfs", &[string("sense_path")]),
+];
+
+/// Keys every check has, besides `kind`; `required` defaults to true
+const CHECK_KEYS: &[KeySpec] = &[string("name")];
+const CHECK_OPTIONAL_KEYS: &[KeySpec] = &[boolean("required")];
+
+/// Keys of each kind of check
+const CHECK_KINDS: &[(&str, &[KeySpec])] = &[
+    ("config_valid", &[string("path")]),
+    ("app_health", &[]),
+    (
+        "topic_fresh",
+        &[string("topic"), number("max_age_s", "s", Some(0.1), None)],
+    ),
+    (
+        "valve_test",
+        &[
+            string("valve"),
+            number("pulse_s", "s", Some(0.1), Some(10.0)),
+            number("timeout_s", "s", Some(0.1), None),
+        ],
+    ),
+    ("cutdown_continuity", &[]),
+    (
+        "disk_budget",
+        &[number("min_free_mb", "MB", Some(0.0), None)],
+    ),
+    (
+        "clock_sync",
+        &[number("max_error_ms", "ms", Some(0.0), None)],
+    ),
+];
+
+/// Config files each app reads. The sim, manager and tlm_rx apps don't read
+/// a config file yet, so there is nothing to check for them.
+pub const SCHEMAS: &[Schema] = &[
+    Schema {
+        app: "control_app",
+        file_name: "control_config.toml",
+        keys: CONTROL_KEYS,
+        all_required: true,
+    },
+    Schema {
+        app: "mfc_cli status",
+        file_name: "status_config.toml",
+        keys: STATUS_KEYS,
+        all_required: false,
+    },
+    Schema {
+        app: "control_app",
+        file_name: "wind_profile.toml",
+        keys: WIND_KEYS,
+        all_required: false,
+    },
+    Schema {
+        app: "mfc_cli preflight",
+        file_name: "preflight_config.toml",
+        keys: PREFLIGHT_KEYS,
+        all_required: true,
+    },
+];
+
+/// Something wrong with a config file
+pub struct Issue {
+    pub severity: Severity,
+    pub file: PathBuf,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+/// The schema for a config file, chosen by its file name
+pub fn schema_for(path: &Path) -> Option<&'static Schema> {
+    let file_name = path.file_name()?.to_str()?;
+    SCHEMAS.iter().find(|schema| schema.file_name == file_name)
+}
+
+/// Check a config file against its schema. Files without a schema are only
+/// checked for being valid TOML.
+pub fn validate_file(path: &Path) -> Vec<Issue> {
+    let issue = |severity: Severity, line: Option<usize>, message: String| Issue {
+        severity,
+        file: path.to_path_buf(),
+        line,
+        message,
+    };
+
+    let contents = match std::fs::read_to_string(path) {
+        Ok(v) => v,
+        Err(e) => return vec![issue(Severity::Error, None, e.to_string())],
+    };
+    let config = match contents.parse::<Value>() {
+        Ok(v) => v,
+        Err(e) => {
+            let line = e.line_col().map(|(line, _)| line + 1);
+            return vec![issue(Severity::Error, line, e.to_string())];
+        }
+    };
+    let schema = match schema_for(path) {
+        Some(v) => v,
+        None => return vec![],
+    };
+    let table = match config.as_table() {
+        Some(v) => v,
+        None => return vec![issue(Severity::Error, None, String::from("not a table"))],
+    };
+
+    let mut issues: Vec<Issue> = vec![];
+    for (key, _) in table.iter() {
+        if !schema.keys.iter().any(|spec| spec.key == key) {
+            issues.push(issue(
+                Severity::Warn,
+                line_of(&contents, key),
+                format!("unknown key '{}' is ignored by {}", key, schema.app),
+            ));
+        }
+    }
+    for spec in schema.keys {
+        let line = line_of(&contents, spec.key);
+        match table.get(spec.key) {
+            None if schema.all_required => issues.push(issue(
+                Severity::Error,
+                None,
+                format!("missing key '{}'", spec.key),
+            )),
+            None => (),
+            Some(value) => {
+                if let Err(message) = check_value(spec, value) {
+                    issues.push(issue(Severity::Error, line, message));
+                }
+            }
+        }
+    }
+
+    // relationships between keys
+    if schema.file_name == "control_config.toml" {
+        let floor = table.get("altitude_floor_m").and_then(as_number);
+        let target = table.get("target_altitude_m").and_then(as_number);
+        if let (Some(floor), Some(target)) = (floor, target) {
+            if target <= floor {
+                issues.push(issue(
+                    Severity::Error,
+                    line_of(&contents, "target_altitude_m"),
+                    format!(
+                        "target_altitude_m {} m must be above altitude_floor_m {} m",
+                        target, floor
+                    ),
+                ));
+            }
+        }
+        // touching down comes after slowing down for it
+        let flare = table.get("flare_height_m").and_then(as_number);
+        let touchdown = table.get("touchdown_height_m").and_then(as_number);
+        if let (Some(flare), Some(touchdown)) = (flare, touchdown) {
+            if touchdown > flare {
+                issues.push(issue(
+                    Severity::Error,
+                    line_of(&contents, "touchdown_height_m"),
+                    format!(
+                        "touchdown_height_m {} m must not be above flare_height_m {} m",
+                        touchdown, flare
+                    ),
+                ));
+            }
+        }
+        // the economy band has to sit above the floor, bottom below top
+        let low = table.get("economy_band_low_m").and_then(as_number);
+        let high = table.get("economy_band_high_m").and_then(as_number);
+        if let (Some(low), Some(high)) = (low, high) {
+            if low >= high {
+                issues.push(issue(
+                    Severity::Error,
+                    line_of(&contents, "economy_band_low_m"),
+                    format!(
+                        "economy_band_low_m {} m must be below economy_band_high_m {} m",
+                        low, high
+                    ),
+                ));
+            }
+        }
+        // hysteresis between following the profile and holding altitude
+        let ready = table.get("error_ready_threshold_m").and_then(as_number);
+        let reentry = table.get("profile_reentry_error_m").and_then(as_number);
+        if let (Some(ready), Some(reentry)) = (ready, reentry) {
+            if reentry <= ready {
+                issues.push(issue(
+                    Severity::Error,
+                    line_of(&contents, "profile_reentry_error_m"),
+                    format!(
+                        "profile_reentry_error_m {} m must be above error_ready_threshold_m {} m",
+                        reentry, ready
+                    ),
+                ));
+            }
+        }
+        // a leak leans on the lift gas model, so it waits at least as long
+        let burst = table.get("burst_confirm_s").and_then(as_number);
+        let leak = table.get("leak_confirm_s").and_then(as_number);
+        if let (Some(burst), Some(leak)) = (burst, leak) {
+            if leak < burst {
+                issues.push(issue(
+                    Severity::Error,
+                    line_of(&contents, "leak_confirm_s"),
+                    format!(
+                        "leak_confirm_s {} s must not be below burst_confirm_s {} s",
+                        leak, burst
+                    ),
+                ));
+            }
+        }
+        // landing is only recognized between the ground and the floor
+        let ground = table.get("ground_altitude_m").and_then(as_number);
+        let landed = table.get("phase_landed_altitude_m").and_then(as_number);
+        if let (Some(ground), Some(landed), Some(floor)) = (ground, landed, floor) {
+            if landed <= ground || landed >= floor {
+                issues.push(issue(
+                    Severity::Error,
+                    line_of(&contents, "phase_landed_altitude_m"),
+                    format!(
+                        "phase_landed_altitude_m {} m must be between ground_altitude_m {} m and altitude_floor_m {} m",
+                        landed, ground, floor
+                    ),
+                ));
+            }
+        }
+        // plausibility limits need room between them
+        for (min_key, max_key) in [
+            ("altitude_min_m", "altitude_max_m"),
+            ("ballast_min_kg", "ballast_max_kg"),
+        ] {
+            let min = table.get(min_key).and_then(as_number);
+            let max = table.get(max_key).and_then(as_number);
+            if let (Some(min), Some(max)) = (min, max) {
+                if min >= max {
+                    issues.push(issue(
+                        Severity::Error,
+                        line_of(&contents, min_key),
+                        format!("{} {} must be below {} {}", min_key, min, max_key, max),
+                    ));
+                }
+            }
+        }
+        if let (Some(floor), Some(low)) = (floor, low) {
+            if low <= floor {
+                issues.push(issue(
+                    Severity::Error,
+                    line_of(&contents, "economy_band_low_m"),
+                    format!(
+                        "economy_band_low_m {} m must be above altitude_floor_m {} m",
+                        low, floor
+                    ),
+                ));
+            }
+        }
+        // drift is only measured between fixes close enough together
+        let baseline = table.get("wind_min_baseline_s").and_then(as_number);
+        let gap = table.get("wind_max_gap_s").and_then(as_number);
+        if let (Some(baseline), Some(gap)) = (baseline, gap) {
+            if baseline >= gap {
+                issues.push(issue(
+                    Severity::Error,
+                    line_of(&contents, "wind_min_baseline_s"),
+                    format!(
+                        "wind_min_baseline_s {} s must be below wind_max_gap_s {} s",
+                        baseline, gap
+                    ),
+                ));
+            }
+        }
+        let column = |key: &str| -> Option<Vec<f32>> {
+            table
+                .get(key)?
+                .as_array()?
+                .iter()
+                .map(|v| v.as_float().map(|f| f as f32))
+                .collect()
+        };
+        // calibration tables must be invertible
+        for valve in ["vent", "dump"] {
+            let pwm_key = format!("{}_cal_pwm", valve);
+            let pwm = column(&pwm_key);
+            let flow = column(&format!("{}_cal_flow_kg_s", valve));
+            if let (Some(pwm), Some(flow)) = (pwm, flow) {
+                if let Err(e) = Calibration::new(pwm, flow) {
+                    issues.push(issue(
+                        Severity::Error,
+                        line_of(&contents, &pwm_key),
+                        format!("{} valve calibration: {}", valve, e),
+                    ));
+                }
+            }
+        }
+        // gain schedules need one set of gains per breakpoint, unless every
+        // gain is a single one
+        for valve in ["vent", "dump"] {
+            let by_key = format!("{}_gain_schedule_by", valve);
+            let single = ["kp", "ki", "kd"].map(|gain| {
+                table
+                    .get(&format!("{}_{}", valve, gain))
+                    .is_some_and(Value::is_float)
+            });
+            if single == [true; 3] {
+                continue;
+            }
+            if single.contains(&true) {
+                issues.push(issue(
+                    Severity::Error,
+                    line_of(&contents, &format!("{}_kp", valve)),
+                    format!(
+                        "{} gains: kp/ki/kd must all be single gains or all be arrays",
+                        valve
+                    ),
+                ));
+                continue;
+            }
+            let schedule = |by: &str| -> Option<Result<GainSchedule, String>> {
+                let by = match by.parse() {
+                    Ok(v) => v,
+                    Err(e) => return Some(Err(e)),
+                };
+                Some(GainSchedule::new(
+                    by,
+                    column(&format!("{}_gain_schedule_at", valve))?,
+                    column(&format!("{}_kp", valve))?,
+                    column(&format!("{}_ki", valve))?,
+                    column(&format!("{}_kd", valve))?,
+                ))
+            };
+            let checked = table
+                .get(&by_key)
+                .and_then(|v| v.as_str())
+                .and_then(schedule);
+            if let Some(Err(e)) = checked {
+                issues.push(issue(
+                    Severity::Error,
+                    line_of(&contents, &by_key),
+                    format!("{} gain schedule: {}", valve, e),
+                ));
+            }
+        }
+    }
+    if schema.file_name == "wind_profile.toml" {
+        // layers in order, one entry in every column for each
+        if let Err(e) = WindProfile::from_toml(&config) {
+            issues.push(issue(Severity::Error, line_of(&contents, "base_m"), e));
+        }
+    }
+    if schema.file_name == "preflight_config.toml" {
+        if let Some(Value::Table(cutdown)) = table.get("cutdown") {
+            for message in check_entry("cutdown", cutdown, CUTDOWN_KINDS, &[], &[]) {
+                issues.push(issue(
+                    Severity::Error,
+                    line_of(&contents, "cutdown"),
+                    message,
+                ));
+            }
+        }
+        if let Some(Value::Array(checks)) = table.get("checks") {
+            for (i, check) in checks.iter().enumerate() {
+                let name = format!("checks[{}]", i);
+                let line = line_of_entry(&contents, "checks", i);
+                let messages = match check.as_table() {
+                    Some(check) => {
+                        check_entry(&name, check, CHECK_KINDS, CHECK_KEYS, CHECK_OPTIONAL_KEYS)
+                    }
+                    None => vec![format!("'{}' must be a table", name)],
+                };
+                for message in messages {
+                    issues.push(issue(Severity::Error, line, message));
+                }
+                // the pulse has to be over before giving up on it
+                let seconds = |key: &str| check.get(key).and_then(as_number);
+                if let (Some(pulse), Some(timeout)) = (seconds("pulse_s"), seconds("timeout_s")) {
+                    if timeout <= pulse {
+                        issues.push(issue(
+                            Severity::Error,
+                            line,
+                            format!(
+                                "{}: timeout_s {} s must be above pulse_s {} s",
+                                name, timeout, pulse
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    issues.sort_by_key(|issue| issue.line);
+    issues
+}
+
+/// Check the keys of a table nested in a config, e.g. one check in a
+/// checklist, against the keys its `kind` has
+fn check_entry(
+    name: &str,
+    entry: &toml::value::Table,
+    kinds: &[(&str, &[KeySpec])],
+    required: &[KeySpec],
+    optional: &[KeySpec],
+) -> Vec<String> {
+    let kind = match entry.get("kind").map(|v| v.as_str()) {
+        Some(Some(v)) => v,
+        Some(None) => return vec![format!("'{}.kind' must be a string", name)],
+        None => return vec![format!("missing key '{}.kind'", name)],
+    };
+    let specific = match kinds.iter().find(|(k, _)| *k == kind) {
+        Some((_, keys)) => *keys,
+        None => {
+            let known: Vec<&str> = kinds.iter().map(|(k, _)| *k).collect();
+            return vec![format!(
+                "'{}.kind' \"{}\" must be one of {}",
+                name,
+                kind,
+                known.join(", ")
+            )];
+        }
+    };
+
+    let mut messages: Vec<String> = vec![];
+    for key in entry.keys() {
+        let known = key == "kind"
+            || required
+                .iter()
+                .chain(specific)
+                .chain(optional)
+                .any(|spec| spec.key == key);
+        if !known {
+            messages.push(format!(
+                "unknown key '{}.{}' for kind \"{}\"",
+                name, key, kind
+            ));
+        }
+    }
+    for spec in required.iter().chain(specific) {
+        if !entry.contains_key(spec.key) {
+            messages.push(format!("missing key '{}.{}'", name, spec.key));
+        }
+    }
+    for spec in required.iter().chain(specific).chain(optional) {
+        if let Some(value) = entry.get(spec.key) {
+            if let Err(message) = check_value(spec, value) {
+                messages.push(format!("{}: {}", name, message));
+            }
+        }
+    }
+    messages
+}
+
+fn check_value(spec: &KeySpec, value: &Value) -> Result<(), String> {
+    match spec.kind {
+        ValueKind::Str => match value {
+            Value::String(_) => Ok(()),
+            other => Err(format!(
+                "'{}' must be a string, not {}",
+                spec.key,
+                other.type_str()
+            )),
+        },
+        ValueKind::Bool => match value {
+            Value::Boolean(_) => Ok(()),
+            other => Err(format!(
+                "'{}' must be true or false, not {}",
+                spec.key,
+                other.type_str()
+            )),
+        },
+        ValueKind::Table => match value {
+            Value::Table(_) => Ok(()),
+            other => Err(format!(
+                "'{}' must be a table, not {}",
+                spec.key,
+                other.type_str()
+            )),
+        },
+        ValueKind::TableArray => match value {
+            Value::Array(array) if array.iter().all(Value::is_table) => Ok(()),
+            _ => Err(format!("'{}' must be an array of tables", spec.key)),
+        },
+        ValueKind::Float => match value {
+            Value::Float(number) => check_range(spec, spec.key, *number),
+            Value::Integer(number) => Err(format!(
+                "'{}' must be a float, write {}.0",
+                spec.key, number
+            )),
+            other => Err(format!(
+                "'{}' must be a float, not {}",
+                spec.key,
+                other.type_str()
+            )),
+        },
+        ValueKind::Number => match as_number(value) {
+            Some(number) => check_range(spec, spec.key, number),
+            None => Err(format!(
+                "'{}' must be a number, not {}",
+                spec.key,
+                value.type_str()
+            )),
+        },
+        ValueKind::Integer => match value {
+            Value::Integer(number) => check_range(spec, spec.key, *number as f64),
+            other => Err(format!(
+                "'{}' must be an integer, not {}",
+                spec.key,
+                other.type_str()
+            )),
+        },
+        ValueKind::Threshold => {
+            let table = value
+                .as_table()
+                .ok_or_else(|| format!("'{}' must be a {{ warn, error }} table", spec.key))?;
+            let mut limits = [0.0; 2];
+            for (limit, field) in limits.iter_mut().zip(["warn", "error"]) {
+                *limit = table
+                    .get(field)
+                    .and_then(as_number)
+                    .ok_or_else(|| format!("'{}.{}' must be a number", spec.key, field))?;
+                check_range(spec, &format!("{}.{}", spec.key, field), *limit)?;
+            }
+            if let Some(extra) = table
+                .keys()
+                .find(|k| !["warn", "error", "direction"].contains(&k.as_str()))
+            {
+                return Err(format!("unknown key '{}.{}'", spec.key, extra));
+            }
+            // the warning has to come before the error on the way to trouble
+            let [warn, error] = limits;
+            match table.get("direction").map(|v| v.as_str()) {
+                None | Some(Some("above")) if warn > error => Err(format!(
+                    "'{}.warn' {} must not be above '{}.error' {}",
+                    spec.key, warn, spec.key, error
+                )),
+                Some(Some("below")) if warn < error => Err(format!(
+                    "'{}.warn' {} must not be below '{}.error' {} with direction \"below\"",
+                    spec.key, warn, spec.key, error
+                )),
+                None | Some(Some("above")) | Some(Some("below")) => Ok(()),
+                _ => Err(format!(
+                    "'{}.direction' must be \"above\" or \"below\"",
+                    spec.key
+                )),
+            }
+        }
+        ValueKind::FloatOrArray => match value {
+            Value::Float(number) => check_range(spec, spec.key, *number),
+            Value::Array(array) => check_floats(spec, array),
+            other => Err(format!(
+                "'{}' must be a float or an array of floats, not {}",
+                spec.key,
+                other.type_str()
+            )),
+        },
+        ValueKind::FloatArray => {
+            let array = value
+                .as_array()
+                .ok_or_else(|| format!("'{}' must be an array of floats", spec.key))?;
+            check_floats(spec, array)
+        }
+        ValueKind::IntegerArray => {
+            let array = value
+                .as_array()
+                .ok_or_else(|| format!("'{}' must be an array of integers", spec.key))?;
+            for (i, element) in array.iter().enumerate() {
+                match element {
+                    Value::Integer(number) => {
+                        check_range(spec, &format!("{}[{}]", spec.key, i), *number as f64)?
+                    }
+                    other => {
+                        return Err(format!(
+                            "'{}[{}]' must be an integer, not {}",
+                            spec.key,
+                            i,
+                            other.type_str()
+                        ))
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn check_floats(spec: &KeySpec, array: &[Value]) -> Result<(), String> {
+    for (i, element) in array.iter().enumerate() {
+        match element {
+            Value::Float(number) => check_range(spec, &format!("{}[{}]", spec.key, i), *number)?,
+            other => {
+                return Err(format!(
+                    "'{}[{}]' must be a float, not {}",
+                    spec.key,
+                    i,
+                    other.type_str()
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_range(spec: &KeySpec, key: &str, number: f64) -> Result<(), String> {
+    if !number.is_finite() {
+        return Err(format!("'{}' must be finite", key));
+    }
+    if let Some(min) = spec.min {
+        if spec.min_exclusive && number <= min {
+            return Err(format!(
+                "'{}' = {} {} must be above {} {}",
+                key, number, spec.unit, min, spec.unit
+            ));
+        }
+        if number < min {
+            return Err(format!(
+                "'{}' = {} {} is below the minimum {} {}",
+                key, number, spec.unit, min, spec.unit
+            ));
+        }
+    }
+    if let Some(max) = spec.max {
+        if number > max {
+            return Err(format!(
+                "'{}' = {} {} is above the maximum {} {}",
+                key, number, spec.unit, max, spec.unit
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Float(f) => Some(*f),
+        Value::Integer(i) => Some(*i as f64),
+        _ => None,
+    }
+}
+
+/// Line (1-based) where a top level key is assigned, either as `key = ...`
+/// before the first table header or as a `[key]` table. Keys inside other
+/// tables don't count.
+fn line_of(contents: &str, key: &str) -> Option<usize> {
+    let mut top_level = true;
+    contents
+        .lines()
+        .position(|line| {
+            let line = line.trim_start();
+            if line.starts_with('[') {
+                top_level = false;
+                let header = line.trim_start_matches('[').split(']').next().unwrap_or("");
+                return header.trim() == key;
+            }
+            top_level
+                && line
+                    .strip_prefix(key)
+                    .map(|rest| rest.trim_start().starts_with('='))
+                    .unwrap_or(false)
+        })
+        .map(|i| i + 1)
+}
+
+/// Line (1-based) of the header of the `index`th `[[key]]` table, or of the
+/// key itself if the array is written inline
+fn line_of_entry(contents: &str, key: &str, index: usize) -> Option<usize> {
+    let header = format!("[[{}]]", key);
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.trim() == header)
+        .nth(index)
+        .map(|(i, _)| i + 1)
+        .or_else(|| line_of(contents, key))
+}
+
+/// One key that differs between two configs
+#[derive(Debug, PartialEq)]
+pub enum Difference {
+    Added {
+        key: String,
+        value: String,
+    },
+    Removed {
+        key: String,
+        value: String,
+    },
+    Changed {
+        key: String,
+        old: String,
+        new: String,
+    },
+}
+
+/// Compare two configs key by key. Numbers are compared by value, so
+/// `24_000.0` and `24000` are the same, and shown with the schema's units.
+pub fn diff_files(a: &Path, b: &Path) -> Result<Vec<Difference>, String> {
+    let load = |path: &Path| -> Result<BTreeMap<String, Value>, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        let value = contents
+            .parse::<Value>()
+            .map_err(|e| format!("Invalid TOML in {}: {}", path.display(), e))?;
+        let mut flat = BTreeMap::new();
+        flatten("", &value, &mut flat);
+        Ok(flat)
+    };
+    let old = load(a)?;
+    let new = load(b)?;
+    let schema = schema_for(b).or_else(|| schema_for(a));
+    let show = |key: &str, value: &Value| with_unit(schema, key, value);
+
+    let mut differences: Vec<Difference> = vec![];
+    for (key, old_value) in &old {
+        match new.get(key) {
+            None => differences.push(Difference::Removed {
+                key: key.clone(),
+                value: show(key, old_value),
+            }),
+            Some(new_value) if !same_value(old_value, new_value) => {
+                differences.push(Difference::Changed {
+                    key: key.clone(),
+                    old: show(key, old_value),
+                    new: show(key, new_value),
+                })
+            }
+            Some(_) => (),
+        }
+    }
+    for (key, new_value) in &new {
+        if !old.contains_key(key) {
+            differences.push(Difference::Added {
+                key: key.clone(),
+                value: show(key, new_value),
+            });
+        }
+    }
+    Ok(differences)
+}
+
+/// Flatten nested tables into dotted keys
+fn flatten(prefix: &str, value: &Value, flat: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Table(table) => {
+            for (key, value) in table {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&key, value, flat);
+            }
+        }
+        other => {
+            flat.insert(String::from(prefix), other.clone());
+        }
+    }
+}
+
+fn same_value(a: &Value, b: &Value) -> bool {
+    match (as_number(a), as_number(b)) {
+        (Some(x), Some(y)) => x == y || (x - y).abs() <= 1e-12 * x.abs().max(y.abs()),
+        _ => a == b,
+    }
+}
+
+fn with_unit(schema: Option<&Schema>, key: &str, value: &Value) -> String {
+    // thresholds are flattened to "key.warn" and "key.error"
+    let base_key = key.split('.').next().unwrap_or(key);
+    let unit = schema
+        .and_then(|schema| schema.keys.iter().find(|spec| spec.key == base_key))
+        .map(|spec| spec.unit)
+        .unwrap_or("");
+    match as_number(value) {
+        Some(number) if !unit.is_empty() => format!("{} {}", number, unit),
+        Some(number) => format!("{}", number),
+        None => value.to_string(),
+    }
+}
+
+/// Validate config files, printing every issue found, and return the exit
+/// code for the worst one. With no files, every known config in `config_dir`
+/// is validated.
+pub fn validate(files: &[PathBuf], config_dir: &Path) -> i32 {
+    let files: Vec<PathBuf> = if files.is_empty() {
+        SCHEMAS
+            .iter()
+            .map(|schema| config_dir.join(schema.file_name))
+            .collect()
+    } else {
+        files.to_vec()
+    };
+
+    let mut worst = Severity::Info;
+    for file in &files {
+        let issues = validate_file(file);
+        if issues.is_empty() {
+            println!("{}: OK", file.display());
+        }
+        for issue in &issues {
+            let location = match issue.line {
+                Some(line) => format!("{}:{}", issue.file.display(), line),
+                None => format!("{}", issue.file.display()),
+            };
+            println!(
+                "{}: {}: {}",
+                location,
+                format!("{:?}", issue.severity).to_lowercase(),
+                issue.message
+            );
+            worst = worst.max(issue.severity);
+        }
+    }
+    exit_code(worst)
+}
+
+/// Print the differences between two configs. Returns 0 if they are the
+/// same, 1 if they differ.
+pub fn diff(a: &Path, b: &Path) -> i32 {
+    let differences = match diff_files(a, b) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{}", e);
+            return 2;
+        }
+    };
+    println!("--- {}\n+++ {}", a.display(), b.display());
+    for difference in &differences {
+        match difference {
+            Difference::Added { key, value } => println!("+ {} = {}", key, value),
+            Difference::Removed { key, value } => println!("- {} = {}", key, value),
+            Difference::Changed { key, old, new } => println!("~ {}: {} -> {}", key, old, new),
+        }
+    }
+    if differences.is_empty() {
+        println!("No differences");
+        0
+    } else {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../support_apps/config");
+
+    fn shipped(file_name: &str) -> String {
+        std::fs::read_to_string(Path::new(CONFIG_DIR).join(file_name)).unwrap()
+    }
+
+    /// Write a config where its schema will find it, in a directory of its own
+    fn write_config(test: &str, file_name: &str, contents: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("mfc_config_schema_{}_{}", std::process::id(), test));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(file_name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Replace the line assigning a top level key
+    fn set(contents: &str, key: &str, value: &str) -> String {
+        let line = line_of(contents, key).unwrap();
+        contents
+            .lines()
+            .enumerate()
+            .map(|(i, text)| {
+                if i + 1 == line {
+                    format!("{} = {}", key, value)
+                } else {
+                    String::from(text)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn errors(path: &Path) -> Vec<(Option<usize>, String)> {
+        validate_file(path)
+            .into_iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .map(|issue| (issue.line, issue.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_shipped_configs_are_valid() {
+        for schema in SCHEMAS {
+            let path = Path::new(CONFIG_DIR).join(schema.file_name);
+            let issues: Vec<String> = validate_file(&path)
+                .into_iter()
+                .map(|issue| issue.message)
+                .collect();
+            assert!(issues.is_empty(), "{}: {:?}", schema.file_name, issues);
+        }
+    }
+
+    #[test]
+    fn test_type_and_range_errors() {
+        let config = shipped("control_config.toml");
+        let line = line_of(&config, "target_altitude_m");
+
+        let integer = set(&config, "target_altitude_m", "24000");
+        let path = write_config("integer", "control_config.toml", &integer);
+        assert_eq!(
+            errors(&path),
+            vec![(
+                line,
+                String::from("'target_altitude_m' must be a float, write 24000.0")
+            )]
+        );
+
+        let text = set(&config, "vent_gain_schedule_by", "1.0");
+        let path = write_config("text", "control_config.toml", &text);
+        assert!(errors(&path)[0]
+            .1
+            .contains("'vent_gain_schedule_by' must be a string"));
+
+        let fraction = set(&config, "median_window", "5.0");
+        let path = write_config("fraction", "control_config.toml", &fraction);
+        assert!(errors(&path)[0]
+            .1
+            .contains("'median_window' must be an integer, not float"));
+
+        let high = set(&config, "target_altitude_m", "60000.0");
+        let path = write_config("range", "control_config.toml", &high);
+        let found = errors(&path);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, line);
+        assert!(
+            found[0].1.contains("is above the maximum 50000 m"),
+            "{}",
+            found[0].1
+        );
+
+        let no_canopy = set(&config, "parachute_drag_area_m2", "0.0");
+        let path = write_config("exclusive", "control_config.toml", &no_canopy);
+        assert_eq!(
+            errors(&path),
+            vec![(
+                line_of(&config, "parachute_drag_area_m2"),
+                String::from("'parachute_drag_area_m2' = 0 m^2 must be above 0 m^2")
+            )]
+        );
+    }
+
+    #[test]
+    fn test_missing_and_unknown_keys() {
+        let config = shipped("control_config.toml");
+        let renamed = config.replace("target_altitude_m", "target_altitude");
+        let path = write_config("renamed", "control_config.toml", &renamed);
+        let issues = validate_file(&path);
+        assert!(issues.iter().any(|issue| issue.severity == Severity::Error
+            && issue.message == "missing key 'target_altitude_m'"));
+        assert!(issues.iter().any(|issue| issue.severity == Severity::Warn
+            && issue.message.starts_with("unknown key 'target_altitude'")));
+    }
+
+    #[test]
+    fn test_cross_key_errors() {
+        let config = shipped("control_config.toml");
+        let inverted = set(&config, "economy_band_low_m", "27000.0");
+        let path = write_config("band", "control_config.toml", &inverted);
+        assert_eq!(
+            errors(&path),
+            vec![(
+                line_of(&config, "economy_band_low_m"),
+                String::from(
+                    "economy_band_low_m 27000 m must be below economy_band_high_m 26000 m"
+                )
+            )]
+        );
+
+        let landed = set(&config, "phase_landed_altitude_m", "16000.0");
+        let path = write_config("landed", "control_config.toml", &landed);
+        assert_eq!(
+            errors(&path),
+            vec![(
+                line_of(&config, "phase_landed_altitude_m"),
+                String::from(
+                    "phase_landed_altitude_m 16000 m must be between ground_altitude_m 0 m and altitude_floor_m 15000 m"
+                )
+            )]
+        );
+
+        let status = shipped("status_config.toml");
+        let upward = set(
+            &status,
+            "recorder_disk_free_mb",
+            "{ warn = 2048.0, error = 512.0 }",
+        );
+        let path = write_config("direction", "status_config.toml", &upward);
+        assert!(errors(&path)[0]
+            .1
+            .contains("'recorder_disk_free_mb.warn' 2048 must not be above"));
+
+        // single gains need no breakpoints, but can't be mixed with arrays
+        let single = set(
+            &set(&set(&config, "vent_kp", "1.0E-5"), "vent_ki", "1.0E-8"),
+            "vent_kd",
+            "0.1",
+        );
+        let path = write_config("single", "control_config.toml", &single);
+        assert!(errors(&path).is_empty(), "{:?}", errors(&path));
+        let mixed = set(&config, "vent_kp", "1.0E-5");
+        let path = write_config("mixed", "control_config.toml", &mixed);
+        assert_eq!(
+            errors(&path),
+            vec![(
+                line_of(&config, "vent_kp"),
+                String::from("vent gains: kp/ki/kd must all be single gains or all be arrays")
+            )]
+        );
+    }
+
+    #[test]
+    fn test_preflight_checks() {
+        let config = shipped("preflight_config.toml");
+        let line = line_of_entry(&config, "checks", 3);
+        assert!(config
+            .lines()
+            .nth(line.unwrap())
+            .unwrap()
+            .contains("Vent valve"));
+
+        let typo = config.replacen("pulse_s = 1.0", "pulse = 1.0", 1);
+        let path = write_config("typo", "preflight_config.toml", &typo);
+        assert_eq!(
+            errors(&path),
+            vec![
+                (
+                    line,
+                    String::from("unknown key 'checks[3].pulse' for kind \"valve_test\"")
+                ),
+                (line, String::from("missing key 'checks[3].pulse_s'")),
+            ]
+        );
+
+        let slow = config.replacen("timeout_s = 5.0", "timeout_s = 0.5", 1);
+        let path = write_config("slow", "preflight_config.toml", &slow);
+        assert_eq!(
+            errors(&path),
+            vec![(
+                line,
+                String::from("checks[3]: timeout_s 0.5 s must be above pulse_s 1 s")
+            )]
+        );
+
+        let unknown = config.replacen("kind = \"valve_test\"", "kind = \"valve\"", 1);
+        let path = write_config("kind", "preflight_config.toml", &unknown);
+        assert!(errors(&path)[0]
+            .1
+            .starts_with("'checks[3].kind' \"valve\" must be one of"));
+
+        let negative = config.replace("max_error_ms = 100.0", "max_error_ms = -1.0");
+        let path = write_config("negative", "preflight_config.toml", &negative);
+        assert!(errors(&path)[0]
+            .1
+            .contains("'max_error_ms' = -1 ms is below the minimum 0 ms"));
+    }
+
+    #[test]
+    fn test_wind_profile() {
+        let config = shipped("wind_profile.toml");
+        let backwards = set(
+            &config,
+            "direction_deg",
+            "[240.0, 250.0, 260.0, 270.0, 270.0, 270.0, 90.0, 450.0]",
+        );
+        let path = write_config("direction", "wind_profile.toml", &backwards);
+        assert_eq!(
+            errors(&path),
+            vec![(
+                line_of(&config, "direction_deg"),
+                String::from("'direction_deg[7]' = 450 deg is above the maximum 360 deg")
+            )]
+        );
+
+        let short = set(&config, "speed_m_s", "[5.0, 10.0]");
+        let path = write_config("short", "wind_profile.toml", &short);
+        assert_eq!(
+            errors(&path),
+            vec![(
+                line_of(&config, "base_m"),
+                String::from("every column needs one entry per layer")
+            )]
+        );
+    }
+
+    #[test]
+    fn test_line_of() {
+        let contents = "a = 1.0\nab = 2.0\n[b]\nc = 3.0\n[[d]]\na = 4.0\n";
+        assert_eq!(line_of(contents, "a"), Some(1));
+        assert_eq!(line_of(contents, "ab"), Some(2));
+        assert_eq!(line_of(contents, "b"), Some(3));
+        assert_eq!(line_of(contents, "d"), Some(5));
+        // only inside a table
+        assert_eq!(line_of(contents, "c"), None);
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = write_config(
+            "diff_old",
+            "status_config.toml",
+            concat!(
+                "recorder_dir = \"/var/lib/mfc\"\n",
+                "temperature_warn_c = 75\n",
+                "temperature_critical_c = 90.0\n",
+                "cpu_load_pct = { warn = 50.0, error = 80.0 }\n",
+            ),
+        );
+        let new = write_config(
+            "diff_new",
+            "cpu_status_config.toml",
+            concat!(
+                "eth_interface = \"eth1\"\n",
+                "temperature_warn_c = 75.0\n",
+                "temperature_critical_c = 85.0\n",
+                "cpu_load_pct = { warn = 60.0, error = 80.0 }\n",
+            ),
+        );
+        // units come from the old file's schema when the new one has none
+        assert_eq!(
+            diff_files(&old, &new).unwrap(),
+            vec![
+                Difference::Changed {
+                    key: String::from("cpu_load_pct.warn"),
+                    old: String::from("50 %"),
+                    new: String::from("60 %"),
+                },
+                Difference::Removed {
+                    key: String::from("recorder_dir"),
+                    value: String::from("\"/var/lib/mfc\""),
+                },
+                Difference::Changed {
+                    key: String::from("temperature_critical_c"),
+                    old: String::from("90 C"),
+                    new: String::from("85 C"),
+                },
+                Difference::Added {
+                    key: String::from("eth_interface"),
+                    value: String::from("\"eth1\""),
+                },
+            ]
+        );
+        assert_eq!(diff(&old, &old), 0);
+        assert_eq!(diff(&old, &new), 1);
+    }
+}
@@ -0,0 +1,462 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::stdout;
+use std::net::UdpSocket;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use crossterm::ExecutableCommand;
+use nng::options::protocol::pubsub::Subscribe;
+use nng::options::{Options, RecvTimeout};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Alignment, Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, Gauge, Paragraph, Sparkline};
+use ratatui::{Frame, Terminal};
+use rmp_serde::Serializer;
+use serde::Serialize;
+
+use control_apps::control_mngr::ControlStatus;
+use mfc::common::ipc;
+use mfc::common::mfc_msgs::{self, AltCtrlStatus, AltitudeBoardTlm, GroundCmd, ManagerStatus};
+
+/// Number of samples kept for each sparkline
+const HISTORY_LEN: usize = 300;
+/// How often the screen is redrawn
+const REFRESH_PERIOD: Duration = Duration::from_millis(250);
+/// Extension id `tlm_rx` relays to the ground command topic
+const GROUND_CMD_EXT: u8 = 3;
+
+/// Latest picture of the flight, filled in by the bus listener
+#[derive(Default)]
+struct FlightState {
+    altitude: VecDeque<(Instant, f32)>,
+    ascent_rate: VecDeque<f32>,
+    ballast_mass: Option<f32>,
+    pwms: Option<(f32, f32)>,
+    ctrl_status: Option<AltCtrlStatus>,
+    manager_status: Option<ManagerStatus>,
+    topics: HashMap<String, Instant>,
+}
+
+impl FlightState {
+    fn push_altitude(&mut self, altitude: f32) {
+        let now = Instant::now();
+        // ascent rate isn't in the telemetry, so difference the altitude
+        if let Some((last_time, last_altitude)) = self.altitude.back() {
+            let dt = now.duration_since(*last_time).as_secs_f32();
+            if dt > 0.0 {
+                push_bounded(&mut self.ascent_rate, (altitude - last_altitude) / dt);
+            }
+        }
+        push_bounded(&mut self.altitude, (now, altitude));
+    }
+}
+
+fn push_bounded<T>(history: &mut VecDeque<T>, value: T) {
+    if history.len() == HISTORY_LEN {
+        history.pop_front();
+    }
+    history.push_back(value);
+}
+
+/// A ground command waiting for the operator to confirm it
+struct PendingCommand {
+    description: &'static str,
+    cmd: GroundCmd,
+}
+
+/// Run the dashboard until the operator quits
+pub fn run(uplink_addr: &str) -> i32 {
+    let state = Arc::new(Mutex::new(FlightState::default()));
+    let listener_state = state.clone();
+    std::thread::spawn(move || listen(listener_state));
+
+    let uplink = match UdpSocket::bind("0.0.0.0:0") {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Could not open uplink socket: {:?}", e);
+            return 2;
+        }
+    };
+
+    if let Err(e) =
+        enable_raw_mode().and_then(|_| stdout().execute(EnterAlternateScreen).map(|_| ()))
+    {
+        eprintln!("Could not set up the terminal: {:?}", e);
+        return 2;
+    }
+    let result = ui_loop(&state, &uplink, uplink_addr);
+    // always hand the terminal back, even if drawing failed
+    let _ = disable_raw_mode();
+    let _ = stdout().execute(LeaveAlternateScreen);
+
+    match result {
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("Dashboard error: {:?}", e);
+            2
+        }
+    }
+}
+
+fn ui_loop(
+    state: &Arc<Mutex<FlightState>>,
+    uplink: &UdpSocket,
+    uplink_addr: &str,
+) -> std::io::Result<()> {
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+    let mut pending: Option<PendingCommand> = None;
+    let mut last_sent = String::from("none");
+    // what the ground last commanded, each key changes one part of it
+    let mut commanded = GroundCmd::default();
+
+    loop {
+        {
+            let state = state.lock().unwrap();
+            terminal.draw(|f| draw(f, &state, pending.as_ref(), &last_sent))?;
+        }
+
+        if !event::poll(REFRESH_PERIOD)? {
+            continue;
+        }
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+
+        if let Some(command) = pending.take() {
+            // the confirmation dialog is open, only y confirms
+            if let KeyCode::Char('y') | KeyCode::Char('Y') = key.code {
+                last_sent = match send_ground_cmd(uplink, uplink_addr, &command.cmd) {
+                    Ok(_) => {
+                        commanded = command.cmd;
+                        String::from(command.description)
+                    }
+                    Err(e) => format!("FAILED {}: {:?}", command.description, e),
+                };
+            }
+            continue;
+        }
+
+        pending = match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Char('a') => Some(PendingCommand {
+                description: "Arm altitude control",
+                cmd: GroundCmd {
+                    arm_alt_ctrl: true,
+                    ..commanded
+                },
+            }),
+            KeyCode::Char('d') => Some(PendingCommand {
+                description: "Disarm altitude control",
+                cmd: GroundCmd {
+                    arm_alt_ctrl: false,
+                    ..commanded
+                },
+            }),
+            KeyCode::Char('c') => Some(PendingCommand {
+                description: "Arm cutdown",
+                cmd: GroundCmd {
+                    arm_cutdown: true,
+                    ..commanded
+                },
+            }),
+            KeyCode::Char('x') => Some(PendingCommand {
+                description: "CUT DOWN",
+                cmd: GroundCmd {
+                    cutdown: true,
+                    ..commanded
+                },
+            }),
+            _ => None,
+        };
+    }
+}
+
+/// Send a ground command the same way the radio does, through `tlm_rx`
+fn send_ground_cmd(uplink: &UdpSocket, uplink_addr: &str, cmd: &GroundCmd) -> std::io::Result<()> {
+    let mut buffer = vec![GROUND_CMD_EXT];
+    cmd.serialize(&mut Serializer::new(&mut buffer))
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    uplink.send_to(&buffer, uplink_addr).map(|_| ())
+}
+
+/// Collect everything published by the flight software
+fn listen(state: Arc<Mutex<FlightState>>) {
+    let s = nng::Socket::new(nng::Protocol::Sub0).unwrap();
+    s.set_opt::<Subscribe>(vec![]).unwrap(); // all topics
+    s.set_opt::<RecvTimeout>(Some(Duration::from_secs(1)))
+        .unwrap();
+    let addrs = [ipc::NNG_TX_ADDR, ipc::NNG_PWM_ADDR, ipc::NNG_MANAGER_ADDR];
+    let mut dialed = [false; 3];
+
+    loop {
+        // keep trying apps that weren't up yet
+        for (addr, dialed) in addrs.iter().zip(dialed.iter_mut()) {
+            if !*dialed {
+                *dialed = s.dial(addr).is_ok();
+            }
+        }
+
+        let msg = match s.recv() {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        let (topic, body) = match ipc::split_nng_msg(msg.as_slice()) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        let mut state = state.lock().unwrap();
+        state.topics.insert(String::from(topic), Instant::now());
+        match topic {
+            mfc_msgs::ALT_CTRL_TOPIC => {
+                if let Some(tlm) = ipc::decode::<AltitudeBoardTlm>(body) {
+                    state.push_altitude(tlm.altitude);
+                    state.ballast_mass = Some(tlm.ballast_mass);
+                }
+            }
+            mfc_msgs::PWM_TOPIC => {
+                if let Some(pwms) = ipc::decode::<(f32, f32)>(body) {
+                    state.pwms = Some(pwms);
+                }
+            }
+            mfc_msgs::ALT_CTRL_STATUS_TOPIC => {
+                if let Some(status) = ipc::decode::<AltCtrlStatus>(body) {
+                    state.ctrl_status = Some(status);
+                }
+            }
+            mfc_msgs::MANAGER_STATUS_TOPIC => {
+                if let Some(status) = ipc::decode::<ManagerStatus>(body) {
+                    state.manager_status = Some(status);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn draw(f: &mut Frame, state: &FlightState, pending: Option<&PendingCommand>, last_sent: &str) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Min(10),
+            Constraint::Length(3),
+        ])
+        .split(f.size());
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .split(rows[1]);
+
+    // mode and status flags across the top
+    let (mode, flags) = match &state.ctrl_status {
+        Some(status) => (
+            status.mode.clone(),
+            format!("{:?}", ControlStatus::from_bits_truncate(status.status)),
+        ),
+        None => (String::from("unknown"), String::from("-")),
+    };
+    let mode_color = match mode.as_str() {
+        "Abort" => Color::Red,
+        "Safe" | "unknown" => Color::Yellow,
+        _ => Color::Green,
+    };
+    let header = Paragraph::new(Line::from(vec![
+        Span::raw("Mode: "),
+        Span::styled(
+            mode,
+            Style::default().fg(mode_color).add_modifier(Modifier::BOLD),
+        ),
+        Span::raw("   Status: "),
+        Span::raw(flags),
+    ]))
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("MFC Dashboard"),
+    );
+    f.render_widget(header, rows[0]);
+
+    draw_history(f, state, columns[0]);
+    draw_actuators(f, state, columns[1]);
+
+    let help = Paragraph::new(format!(
+        "[a] arm alt ctrl  [d] disarm alt ctrl  [c] arm cutdown  [x] cut down  [q] quit    last command: {}",
+        last_sent
+    ))
+    .block(Block::default().borders(Borders::ALL));
+    f.render_widget(help, rows[2]);
+
+    if let Some(command) = pending {
+        let area = centered(f.size(), 50, 5);
+        let dialog = Paragraph::new(vec![
+            Line::from(Span::styled(
+                format!("{}?", command.description),
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Line::from("[y] send   any other key cancels"),
+        ])
+        .alignment(Alignment::Center)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Confirm ground command")
+                .border_style(Style::default().fg(Color::Red)),
+        );
+        f.render_widget(Clear, area);
+        f.render_widget(dialog, area);
+    }
+}
+
+/// Altitude and ascent rate sparklines
+fn draw_history(f: &mut Frame, state: &FlightState, area: Rect) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(area);
+
+    let altitude: Vec<f32> = state
+        .altitude
+        .iter()
+        .map(|(_, altitude)| *altitude)
+        .collect();
+    let title = match altitude.last() {
+        Some(altitude) => format!("Altitude {:.0} m", altitude),
+        None => String::from("Altitude (no data)"),
+    };
+    let data = to_sparkline(&altitude, rows[0].width);
+    f.render_widget(
+        Sparkline::default()
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .data(&data)
+            .style(Style::default().fg(Color::Cyan)),
+        rows[0],
+    );
+
+    let rates: Vec<f32> = state.ascent_rate.iter().copied().collect();
+    let title = match rates.last() {
+        Some(rate) => format!("Ascent rate {:+.2} m/s", rate),
+        None => String::from("Ascent rate (no data)"),
+    };
+    let data = to_sparkline(&rates, rows[1].width);
+    f.render_widget(
+        Sparkline::default()
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .data(&data)
+            .style(Style::default().fg(Color::Magenta)),
+        rows[1],
+    );
+}
+
+/// Valves, ballast, cutdown and topic freshness
+fn draw_actuators(f: &mut Frame, state: &FlightState, area: Rect) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Length(4),
+            Constraint::Min(3),
+        ])
+        .split(area);
+
+    let (vent, dump) = state.pwms.unwrap_or((0.0, 0.0));
+    f.render_widget(pwm_gauge("Vent PWM", vent, Color::Yellow), rows[0]);
+    f.render_widget(pwm_gauge("Dump PWM", dump, Color::Blue), rows[1]);
+
+    let cutdown = match &state.manager_status {
+        Some(status) if status.cutdown_commanded => {
+            Span::styled("COMMANDED", Style::default().fg(Color::Red))
+        }
+        Some(status) if status.cutdown_armed => {
+            Span::styled("ARMED", Style::default().fg(Color::Yellow))
+        }
+        Some(_) => Span::styled("disarmed", Style::default().fg(Color::Green)),
+        None => Span::raw("unknown"),
+    };
+    let ballast = match state.ballast_mass {
+        Some(mass) => format!("{:.3} kg", mass),
+        None => String::from("unknown"),
+    };
+    let vehicle = Paragraph::new(vec![
+        Line::from(format!("Ballast remaining: {}", ballast)),
+        Line::from(vec![Span::raw("Cutdown: "), cutdown]),
+    ])
+    .block(Block::default().borders(Borders::ALL).title("Vehicle"));
+    f.render_widget(vehicle, rows[2]);
+
+    let topics = [
+        mfc_msgs::ALT_CTRL_TOPIC,
+        mfc_msgs::PWM_TOPIC,
+        mfc_msgs::ALT_CTRL_STATUS_TOPIC,
+        mfc_msgs::MANAGER_STATUS_TOPIC,
+        mfc_msgs::HEARTBEAT_TOPIC,
+    ];
+    let lines: Vec<Line> = topics
+        .iter()
+        .map(|topic| match state.topics.get(*topic) {
+            Some(received) => {
+                let age = received.elapsed().as_secs_f32();
+                let color = match age {
+                    a if a >= 5.0 => Color::Red,
+                    a if a >= 2.0 => Color::Yellow,
+                    _ => Color::Green,
+                };
+                Line::from(Span::styled(
+                    format!("{:<16} {:>7.1} s", topic, age),
+                    Style::default().fg(color),
+                ))
+            }
+            None => Line::from(Span::styled(
+                format!("{:<16} {:>9}", topic, "never"),
+                Style::default().fg(Color::DarkGray),
+            )),
+        })
+        .collect();
+    f.render_widget(
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Topic age")),
+        rows[3],
+    );
+}
+
+fn pwm_gauge(title: &str, pwm: f32, color: Color) -> Gauge<'_> {
+    Gauge::default()
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .gauge_style(Style::default().fg(color))
+        .ratio(pwm.clamp(0.0, 1.0) as f64)
+        .label(format!("{:.0}%", 100.0 * pwm))
+}
+
+/// Shift and scale the newest samples that fit in the width into the
+/// unsigned range a sparkline draws
+fn to_sparkline(values: &[f32], width: u16) -> Vec<u64> {
+    // leave room for the borders
+    let fits = width.saturating_sub(2) as usize;
+    let values = &values[values.len().saturating_sub(fits)..];
+    let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let span = (max - min).max(f32::EPSILON);
+    values
+        .iter()
+        .map(|value| (1.0 + 99.0 * (value - min) / span) as u64)
+        .collect()
+}
+
+/// A rectangle of the given size in the middle of an area
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
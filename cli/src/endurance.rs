@@ -0,0 +1,117 @@
+use std::time::{Duration, Instant};
+
+use log::{error, info};
+use nng::options::protocol::pubsub::Subscribe;
+use nng::options::{Options, RecvTimeout};
+
+use mfc::common::ipc;
+use mfc::common::mfc_msgs::{self, EnduranceStatus};
+
+use crate::status::ReportFormat;
+
+/// Wait for the control app's next endurance forecast and print it.
+/// Returns 0 when one arrives, 2 if nothing does within the listen window.
+pub fn print_forecast(format: ReportFormat, listen_window: Duration) -> i32 {
+    let forecast = match listen_for_forecast(listen_window) {
+        Some(v) => v,
+        None => {
+            error!(
+                "No endurance forecast on the bus within {:?}, is control_app running?",
+                listen_window
+            );
+            return 2;
+        }
+    };
+    match format {
+        ReportFormat::Table => print_table(&forecast),
+        ReportFormat::Json => match serde_json::to_string_pretty(&forecast) {
+            Ok(json) => println!("{}", json),
+            Err(e) => error!("Failed to serialize endurance forecast: {:?}", e),
+        },
+        ReportFormat::Log => {
+            for (name, value, unit) in rows(&forecast) {
+                info!("{:<26} {} {}", name, value, unit);
+            }
+        }
+    }
+    0
+}
+
+fn listen_for_forecast(window: Duration) -> Option<EnduranceStatus> {
+    let s = nng::Socket::new(nng::Protocol::Sub0).unwrap();
+    s.set_opt::<Subscribe>(format!("{}:", mfc_msgs::ENDURANCE_TOPIC).into_bytes())
+        .unwrap();
+    s.set_opt::<RecvTimeout>(Some(Duration::from_millis(100)))
+        .unwrap();
+    if s.dial(ipc::NNG_PWM_ADDR).is_err() {
+        return None; // control_app isn't up
+    }
+
+    let start = Instant::now();
+    while start.elapsed() < window {
+        let msg = match s.recv() {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        if let Some((_, body)) = ipc::split_nng_msg(msg.as_slice()) {
+            if let Some(forecast) = ipc::decode::<EnduranceStatus>(body) {
+                return Some(forecast);
+            }
+        }
+    }
+    None
+}
+
+/// Name, value and unit of each line of the forecast
+fn rows(forecast: &EnduranceStatus) -> Vec<(&'static str, String, &'static str)> {
+    let remaining = match forecast.remaining_s {
+        Some(s) => (format!("{:.1}", s / 3600.0), "h"),
+        None => (String::from("beyond forecast"), ""),
+    };
+    let limited_by = if forecast.limited_by.is_empty() {
+        String::from("-")
+    } else {
+        forecast.limited_by.clone()
+    };
+    vec![
+        ("time_to_min_ballast", remaining.0, remaining.1),
+        ("limited_by", limited_by, ""),
+        (
+            "usable_ballast",
+            format!("{:.3}", forecast.usable_ballast_kg),
+            "kg",
+        ),
+        (
+            "spare_lift_gas",
+            format!("{:.4}", forecast.spare_gas_kg),
+            "kg",
+        ),
+        (
+            "day_ballast_rate",
+            format!("{:.4}", forecast.day_ballast_kg_hr),
+            "kg/h",
+        ),
+        (
+            "night_ballast_rate",
+            format!("{:.4}", forecast.night_ballast_kg_hr),
+            "kg/h",
+        ),
+        (
+            "day_gas_rate",
+            format!("{:.5}", forecast.day_gas_kg_hr),
+            "kg/h",
+        ),
+        (
+            "night_gas_rate",
+            format!("{:.5}", forecast.night_gas_kg_hr),
+            "kg/h",
+        ),
+    ]
+}
+
+fn print_table(forecast: &EnduranceStatus) {
+    println!("{:<26} {:>16} {:<6}", "FORECAST", "VALUE", "UNIT");
+    for (name, value, unit) in rows(forecast) {
+        println!("{:<26} {:>16} {:<6}", name, value, unit);
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use log::debug;
+use nng::options::protocol::pubsub::Subscribe;
+use nng::options::{Options, RecvTimeout};
+use sysinfo::{Disk, DiskExt, Pid, Process, ProcessExt, System, SystemExt};
+
+use mfc::common::ipc;
+use mfc::common::mfc_msgs::{
+    self, unix_time_ms, AltCtrlStatus, HealthMetric, Heartbeat, ManagerStatus, Severity,
+};
+
+use crate::status::{number_metric, state_metric};
+use crate::status_config::StatusConfig;
+
+/// Flight software apps and the bus address each one publishes on
+fn mfc_apps() -> [(&'static str, &'static str); 3] {
+    [
+        ("tlm_rx", ipc::NNG_TX_ADDR),
+        ("control_app", ipc::NNG_PWM_ADDR),
+        ("manager", ipc::NNG_MANAGER_ADDR),
+    ]
+}
+
+/// Topics we expect to see on the bus while the flight software is running
+fn mfc_topics() -> [&'static str; 7] {
+    [
+        mfc_msgs::ALT_CTRL_TOPIC,
+        mfc_msgs::PWM_TOPIC,
+        mfc_msgs::ALT_CTRL_STATUS_TOPIC,
+        mfc_msgs::LIFT_GAS_TOPIC,
+        mfc_msgs::VALVE_DUTY_TOPIC,
+        mfc_msgs::FLIGHT_PHASE_TOPIC,
+        mfc_msgs::MANAGER_STATUS_TOPIC,
+    ]
+}
+
+/// What was heard on the bus during the listening window
+#[derive(Default)]
+struct BusSnapshot {
+    reachable: HashMap<&'static str, bool>,
+    heartbeats: HashMap<String, u64>,
+    topics: HashMap<String, Instant>,
+    ctrl_status: Option<AltCtrlStatus>,
+    manager_status: Option<ManagerStatus>,
+}
+
+pub fn fsw_report(listen_window: Duration, config: &StatusConfig) -> Vec<HealthMetric> {
+    let mut msgs: Vec<HealthMetric> = vec![];
+
+    // process CPU usage is measured between two snapshots, so take the
+    // first before listening and the second after
+    let mut sys = System::new_all();
+    let bus = listen_to_bus(listen_window);
+    sys.refresh_processes();
+
+    // which apps are running, and as which processes
+    for (app, _) in mfc_apps() {
+        let processes: Vec<&Process> = sys
+            .processes()
+            .values()
+            .filter(|process| process.name() == app)
+            .collect();
+        let name = format!("{}.pid", app);
+        if processes.is_empty() {
+            msgs.push(state_metric(&name, "not running", Severity::Error));
+            continue;
+        }
+        let pids: Vec<String> = processes
+            .iter()
+            .map(|process| process.pid().to_string())
+            .collect();
+        msgs.push(state_metric(&name, &pids.join(","), Severity::Info));
+
+        // how much of the computer each instance is using
+        for process in processes {
+            msgs.extend(process_usage(app, process, config));
+        }
+    }
+
+    // can we reach each app's socket?
+    for (app, addr) in mfc_apps() {
+        let name = format!("{}.socket", app);
+        msgs.push(match bus.reachable.get(app) {
+            Some(true) => state_metric(&name, &format!("{} reachable", addr), Severity::Info),
+            _ => state_metric(&name, &format!("{} unreachable", addr), Severity::Error),
+        });
+    }
+
+    // how long since each app last said it was alive
+    let now_ms = unix_time_ms();
+    for (app, _) in mfc_apps() {
+        let name = format!("{}.heartbeat_age", app);
+        msgs.push(match bus.heartbeats.get(app) {
+            Some(timestamp_ms) => number_metric(
+                &name,
+                now_ms.saturating_sub(*timestamp_ms) as f64 / 1000.0,
+                "s",
+                Some(config.heartbeat_age_s),
+            ),
+            None => state_metric(&name, "none", Severity::Error),
+        });
+    }
+
+    // how long since each topic was last published
+    for topic in mfc_topics() {
+        let name = format!("topic.{}.age", topic);
+        msgs.push(match bus.topics.get(topic) {
+            Some(received) => number_metric(
+                &name,
+                received.elapsed().as_secs_f64(),
+                "s",
+                Some(config.topic_age_s),
+            ),
+            None => state_metric(
+                &name,
+                &format!("none in {:.1} s", listen_window.as_secs_f32()),
+                Severity::Warn,
+            ),
+        });
+    }
+
+    // what the control app is doing
+    msgs.push(
+        match bus.ctrl_status.as_ref().map(|status| status.mode.as_str()) {
+            Some(mode @ "Abort") => state_metric("control_app.mode", mode, Severity::Error),
+            Some(mode @ "Safe") => state_metric("control_app.mode", mode, Severity::Warn),
+            Some(mode) => state_metric("control_app.mode", mode, Severity::Info),
+            None => state_metric("control_app.mode", "unknown", Severity::Warn),
+        },
+    );
+
+    // whether the manager is ready to cut us down
+    msgs.push(match &bus.manager_status {
+        Some(status) if status.cutdown_commanded => {
+            state_metric("manager.cutdown", "commanded", Severity::Warn)
+        }
+        Some(status) if status.cutdown_armed => {
+            state_metric("manager.cutdown", "armed", Severity::Info)
+        }
+        Some(_) => state_metric("manager.cutdown", "disarmed", Severity::Info),
+        None => state_metric("manager.cutdown", "unknown", Severity::Warn),
+    });
+
+    // room left on the recorder volume
+    msgs.extend(recorder_disk_usage(&sys, config));
+    msgs
+}
+
+/// CPU, memory and open file descriptors of one app process
+fn process_usage(app: &str, process: &Process, config: &StatusConfig) -> Vec<HealthMetric> {
+    let mut msgs: Vec<HealthMetric> = vec![];
+    let pid = process.pid();
+    msgs.push(number_metric(
+        &format!("{}.{}.cpu", app, pid),
+        process.cpu_usage() as f64,
+        "%",
+        Some(config.process_cpu_pct),
+    ));
+    msgs.push(number_metric(
+        &format!("{}.{}.memory", app, pid),
+        process.memory() as f64 / 1024.0, // kB to MB
+        "MB",
+        Some(config.process_memory_mb),
+    ));
+    let name = format!("{}.{}.open_fds", app, pid);
+    msgs.push(match open_fds(pid) {
+        Some(count) => number_metric(&name, count as f64, "", Some(config.process_open_fds)),
+        None => state_metric(&name, "unknown", Severity::Warn),
+    });
+    msgs
+}
+
+/// Number of file descriptors a process has open, from procfs
+fn open_fds(pid: Pid) -> Option<usize> {
+    std::fs::read_dir(format!("/proc/{}/fd", pid))
+        .ok()
+        .map(|entries| entries.count())
+}
+
+/// Time until a message is published on the topic, or None if nothing
+/// arrives before the timeout
+pub fn wait_for_topic(topic: &str, timeout: Duration) -> Option<Duration> {
+    let s = nng::Socket::new(nng::Protocol::Sub0).unwrap();
+    s.set_opt::<Subscribe>(format!("{}:", topic).into_bytes())
+        .unwrap();
+    s.set_opt::<RecvTimeout>(Some(Duration::from_millis(100)))
+        .unwrap();
+    for (_, addr) in mfc_apps() {
+        // unreachable apps just won't deliver anything
+        let _ = s.dial(addr);
+    }
+
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        if s.recv().is_ok() {
+            return Some(start.elapsed());
+        }
+    }
+    None
+}
+
+/// Dial every app's socket and collect what is published for a while
+fn listen_to_bus(window: Duration) -> BusSnapshot {
+    let mut snapshot = BusSnapshot::default();
+
+    let s = nng::Socket::new(nng::Protocol::Sub0).unwrap();
+    s.set_opt::<Subscribe>(vec![]).unwrap(); // all topics
+    s.set_opt::<RecvTimeout>(Some(Duration::from_millis(100)))
+        .unwrap();
+    for (app, addr) in mfc_apps() {
+        let reachable = s.dial(addr).is_ok();
+        debug!("Dialed {} at {}: reachable={}", app, addr, reachable);
+        snapshot.reachable.insert(app, reachable);
+    }
+
+    let start = Instant::now();
+    while start.elapsed() < window {
+        let msg = match s.recv() {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        let (topic, body) = match ipc::split_nng_msg(msg.as_slice()) {
+            Some(v) => v,
+            None => continue,
+        };
+        snapshot.topics.insert(String::from(topic), Instant::now());
+        match topic {
+            mfc_msgs::HEARTBEAT_TOPIC => {
+                if let Some(heartbeat) = ipc::decode::<Heartbeat>(body) {
+                    snapshot
+                        .heartbeats
+                        .insert(heartbeat.app, heartbeat.timestamp_ms);
+                }
+            }
+            mfc_msgs::ALT_CTRL_STATUS_TOPIC => {
+                if let Some(status) = ipc::decode::<AltCtrlStatus>(body) {
+                    snapshot.ctrl_status = Some(status);
+                }
+            }
+            mfc_msgs::MANAGER_STATUS_TOPIC => {
+                if let Some(status) = ipc::decode::<ManagerStatus>(body) {
+                    snapshot.manager_status = Some(status);
+                }
+            }
+            _ => (),
+        }
+    }
+    snapshot
+}
+
+/// Space used and free on the disk holding the recorder directory
+fn recorder_disk_usage(sys: &System, config: &StatusConfig) -> Vec<HealthMetric> {
+    match disk_for(sys, &config.recorder_dir) {
+        Some(disk) if disk.total_space() > 0 => {
+            let used = disk.total_space() - disk.available_space();
+            vec![
+                number_metric(
+                    "recorder.disk_used",
+                    100.0 * used as f64 / disk.total_space() as f64,
+                    "%",
+                    Some(config.recorder_disk_used_pct),
+                ),
+                number_metric(
+                    "recorder.disk_free",
+                    disk.available_space() as f64 / (1024.0 * 1024.0),
+                    "MB",
+                    Some(config.recorder_disk_free_mb),
+                ),
+            ]
+        }
+        _ => vec![state_metric(
+            "recorder.disk_used",
+            &format!("no disk for {}", config.recorder_dir.display()),
+            Severity::Warn,
+        )],
+    }
+}
+
+/// Free space (MB) on the disk holding a directory
+pub fn disk_free_mb(sys: &System, dir: &Path) -> Option<f64> {
+    disk_for(sys, dir).map(|disk| disk.available_space() as f64 / (1024.0 * 1024.0))
+}
+
+/// The disk a directory lives on: the one with the longest mount point containing it
+fn disk_for<'a>(sys: &'a System, dir: &Path) -> Option<&'a Disk> {
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    sys.disks()
+        .iter()
+        .filter(|disk| dir.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+}
@@ -1,2 +1,8 @@
+mod config_schema;
+mod dashboard;
+mod endurance;
+mod fsw_status;
+mod preflight;
 mod status;
+mod status_config;
 mod sys;
\ No newline at end of file
@@ -1,11 +1,18 @@
 extern crate pretty_env_logger;
 mod cli;
+mod config_schema;
+mod dashboard;
+mod endurance;
+mod fsw_status;
+mod preflight;
 mod status;
+mod status_config;
 mod sys;
 
 fn main() {
     // initialize pretty print logger
     pretty_env_logger::init();
     // parse the commands, arguments, and options
-    cli::parse_inputs();
+    let exit_code = cli::parse_inputs();
+    std::process::exit(exit_code);
 }
\ No newline at end of file
@@ -0,0 +1,389 @@
+use std::fmt::Write as _;
+use std::net::UdpSocket;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use log::{error, info, warn};
+use nng::options::protocol::pubsub::Subscribe;
+use nng::options::{Options, RecvTimeout};
+use rmp_serde::Serializer;
+use serde::{Deserialize, Serialize};
+use sysinfo::{System, SystemExt};
+
+use mfc::common::ipc;
+use mfc::common::mfc_msgs::{self, Severity, Threshold, ValveActuation, ValveDuty, ValveTest};
+use mfc::manager::cutdown::{CutdownChannel, SimulatedCutdown, SysfsCutdown};
+
+use crate::config_schema::validate_file;
+use crate::fsw_status::{disk_free_mb, fsw_report, wait_for_topic};
+use crate::status::{clock_sync, format_value};
+use crate::status_config;
+
+/// Ordered go/no-go checklist, loaded from TOML
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PreflightConfig {
+    /// Where signed-off reports are written
+    report_dir: PathBuf,
+    /// Status config used for app health thresholds
+    status_config: PathBuf,
+    /// Where `tlm_rx` takes uplinked commands, for valve tests
+    uplink: String,
+    cutdown: CutdownBackend,
+    checks: Vec<Check>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum CutdownBackend {
+    Simulated { continuous: bool },
+    Sysfs { sense_path: PathBuf },
+}
+
+#[derive(Debug, Deserialize)]
+struct Check {
+    name: String,
+    /// A failed required check makes the result NO-GO
+    #[serde(default = "default_required")]
+    required: bool,
+    #[serde(flatten)]
+    kind: CheckKind,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+/// Extension id `tlm_rx` relays to the valve test topic
+const VALVE_TEST_EXT: u8 = 7;
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum CheckKind {
+    ConfigValid {
+        path: PathBuf,
+    },
+    AppHealth,
+    TopicFresh {
+        topic: String,
+        max_age_s: f32,
+    },
+    ValveTest {
+        valve: String,
+        pulse_s: f32,
+        timeout_s: f32,
+    },
+    CutdownContinuity,
+    DiskBudget {
+        min_free_mb: f64,
+    },
+    ClockSync {
+        max_error_ms: f64,
+    },
+}
+
+struct CheckResult {
+    name: String,
+    required: bool,
+    passed: bool,
+    detail: String,
+}
+
+/// Run the checklist, write the signed-off report and return the exit code:
+/// 0 for GO, 2 for NO-GO
+pub fn run_checklist(config_path: &Path, operator: &str) -> i32 {
+    let config = match load(config_path) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{}", e);
+            return 2;
+        }
+    };
+    // paths in the checklist are relative to the checklist itself
+    let base_dir = config_path.parent().unwrap_or_else(|| Path::new("."));
+
+    let mut results: Vec<CheckResult> = vec![];
+    for (i, check) in config.checks.iter().enumerate() {
+        info!("[{}/{}] {}", i + 1, config.checks.len(), check.name);
+        let (passed, detail) = run_check(&check.kind, &config, base_dir);
+        if passed {
+            info!("  PASS: {}", detail);
+        } else if check.required {
+            error!("  FAIL: {}", detail);
+        } else {
+            warn!("  FAIL (not required): {}", detail);
+        }
+        results.push(CheckResult {
+            name: check.name.clone(),
+            required: check.required,
+            passed,
+            detail,
+        });
+    }
+
+    let go = results
+        .iter()
+        .all(|result| result.passed || !result.required);
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|t| t.as_secs())
+        .unwrap_or(0);
+    let report = format_report(&results, go, operator, timestamp);
+    println!("{}", report);
+
+    let report_dir = base_dir.join(&config.report_dir);
+    match write_report(&report_dir, &report, timestamp) {
+        Ok(path) => info!("Pre-flight report written to {}", path.display()),
+        Err(e) => error!(
+            "Failed to write pre-flight report to {}: {}",
+            report_dir.display(),
+            e
+        ),
+    }
+
+    if go {
+        0
+    } else {
+        2
+    }
+}
+
+fn load(path: &Path) -> Result<PreflightConfig, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    toml::from_str(&contents)
+        .map_err(|e| format!("Invalid pre-flight checklist {}: {}", path.display(), e))
+}
+
+/// Run a single check, returning whether it passed and what was seen
+fn run_check(kind: &CheckKind, config: &PreflightConfig, base_dir: &Path) -> (bool, String) {
+    match kind {
+        CheckKind::ConfigValid { path } => {
+            let path = base_dir.join(path);
+            let errors: Vec<String> = validate_file(&path)
+                .into_iter()
+                .filter(|issue| issue.severity >= Severity::Error)
+                .map(|issue| issue.message)
+                .collect();
+            if errors.is_empty() {
+                (true, format!("{} is valid", path.display()))
+            } else {
+                (false, format!("{}: {}", path.display(), errors.join("; ")))
+            }
+        }
+        CheckKind::AppHealth => {
+            let status_config = match status_config::load(&base_dir.join(&config.status_config)) {
+                Ok(v) => v,
+                Err(e) => return (false, e),
+            };
+            let failures: Vec<String> = fsw_report(Duration::from_secs(2), &status_config)
+                .into_iter()
+                .filter(|metric| metric.severity >= Severity::Error)
+                .map(|metric| metric.name)
+                .collect();
+            if failures.is_empty() {
+                (true, String::from("no errors in flight software status"))
+            } else {
+                (false, format!("errors in {}", failures.join(", ")))
+            }
+        }
+        CheckKind::TopicFresh { topic, max_age_s } => {
+            let max_age = match Duration::try_from_secs_f32(*max_age_s) {
+                Ok(v) => v,
+                Err(_) => {
+                    return (
+                        false,
+                        format!("max_age_s {} is not a valid time", max_age_s),
+                    )
+                }
+            };
+            match wait_for_topic(topic, max_age) {
+                Some(age) => (
+                    true,
+                    format!("'{}' received after {:.2} s", topic, age.as_secs_f32()),
+                ),
+                None => (
+                    false,
+                    format!("nothing on '{}' for {:.1} s", topic, max_age_s),
+                ),
+            }
+        }
+        CheckKind::ValveTest {
+            valve,
+            pulse_s,
+            timeout_s,
+        } => valve_test(&config.uplink, valve, *pulse_s, *timeout_s),
+        CheckKind::CutdownContinuity => {
+            let channel: Box<dyn CutdownChannel> = match &config.cutdown {
+                CutdownBackend::Simulated { continuous } => Box::new(SimulatedCutdown {
+                    continuous: *continuous,
+                }),
+                CutdownBackend::Sysfs { sense_path } => {
+                    Box::new(SysfsCutdown::new(sense_path.clone()))
+                }
+            };
+            match channel.continuity() {
+                Ok(true) => (true, format!("{} channel is continuous", channel.name())),
+                Ok(false) => (false, format!("{} channel is open", channel.name())),
+                Err(e) => (false, format!("{} channel: {:?}", channel.name(), e)),
+            }
+        }
+        CheckKind::DiskBudget { min_free_mb } => {
+            let status_config = match status_config::load(&base_dir.join(&config.status_config)) {
+                Ok(v) => v,
+                Err(e) => return (false, e),
+            };
+            let mut sys = System::new();
+            sys.refresh_disks_list();
+            match disk_free_mb(&sys, &status_config.recorder_dir) {
+                Some(free) => (
+                    free >= *min_free_mb,
+                    format!("{:.0} MB free, need {:.0} MB", free, min_free_mb),
+                ),
+                None => (
+                    false,
+                    format!("no disk for {}", status_config.recorder_dir.display()),
+                ),
+            }
+        }
+        CheckKind::ClockSync { max_error_ms } => {
+            let metric = clock_sync(Threshold::above(*max_error_ms, *max_error_ms));
+            (
+                metric.severity <= Severity::Info,
+                format!("{} {}", metric.name, format_value(&metric.value)),
+            )
+        }
+    }
+}
+
+/// Have the control app pulse a valve in a dry run and check that the pulse
+/// comes back on the bus: the valve opening, closing and the duty delivered
+fn valve_test(uplink_addr: &str, valve: &str, pulse_s: f32, timeout_s: f32) -> (bool, String) {
+    if valve != "vent" && valve != "dump" {
+        return (false, format!("unknown valve '{}'", valve));
+    }
+    let timeout = match Duration::try_from_secs_f32(timeout_s) {
+        Ok(v) => v,
+        Err(_) => {
+            return (
+                false,
+                format!("timeout_s {} is not a valid time", timeout_s),
+            )
+        }
+    };
+
+    let s = nng::Socket::new(nng::Protocol::Sub0).unwrap();
+    for topic in [mfc_msgs::VALVE_ACTUATION_TOPIC, mfc_msgs::VALVE_DUTY_TOPIC] {
+        s.set_opt::<Subscribe>(format!("{}:", topic).into_bytes())
+            .unwrap();
+    }
+    s.set_opt::<RecvTimeout>(Some(Duration::from_millis(100)))
+        .unwrap();
+    if s.dial(ipc::NNG_PWM_ADDR).is_err() {
+        return (
+            false,
+            format!("control_app unreachable at {}", ipc::NNG_PWM_ADDR),
+        );
+    }
+    // let the subscription settle so the start of the pulse isn't missed
+    std::thread::sleep(Duration::from_millis(200));
+
+    // ask for the pulse the same way the radio would, through tlm_rx
+    let test = ValveTest {
+        valve: String::from(valve),
+        pulse_s,
+    };
+    let mut buffer = vec![VALVE_TEST_EXT];
+    test.serialize(&mut Serializer::new(&mut buffer)).unwrap();
+    if let Err(e) = UdpSocket::bind("0.0.0.0:0").and_then(|u| u.send_to(&buffer, uplink_addr)) {
+        return (false, format!("could not uplink to {}: {}", uplink_addr, e));
+    }
+
+    let (mut opened, mut closed) = (false, false);
+    let mut delivered: f32 = 0.0;
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        let msg = match s.recv() {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        let (topic, body) = match ipc::split_nng_msg(msg.as_slice()) {
+            Some(v) => v,
+            None => continue,
+        };
+        match topic {
+            mfc_msgs::VALVE_ACTUATION_TOPIC => {
+                // only the test pulse counts, not the controller's
+                if let Some(actuation) = ipc::decode::<ValveActuation>(body) {
+                    if actuation.valve == valve && actuation.dry_run {
+                        opened |= actuation.open;
+                        closed |= opened && !actuation.open;
+                    }
+                }
+            }
+            mfc_msgs::VALVE_DUTY_TOPIC if opened => {
+                if let Some(duty) = ipc::decode::<ValveDuty>(body) {
+                    delivered += match valve {
+                        "vent" => duty.vent_delivered,
+                        _ => duty.dump_delivered,
+                    };
+                }
+            }
+            _ => (),
+        }
+        if closed && delivered > 0.0 {
+            return (
+                true,
+                format!(
+                    "{} opened and closed after {:.1} s (dry run)",
+                    valve,
+                    start.elapsed().as_secs_f32()
+                ),
+            );
+        }
+    }
+    let missing = if !opened {
+        "never opened"
+    } else if !closed {
+        "never closed"
+    } else {
+        "no delivered duty on valve_duty"
+    };
+    (
+        false,
+        format!("{} {} within {:.1} s (dry run)", valve, missing, timeout_s),
+    )
+}
+
+fn format_report(results: &[CheckResult], go: bool, operator: &str, timestamp: u64) -> String {
+    let mut report = String::new();
+    let _ = writeln!(report, "MFC PRE-FLIGHT CHECKLIST");
+    let _ = writeln!(report, "Unix time: {}", timestamp);
+    let _ = writeln!(report);
+    for (i, result) in results.iter().enumerate() {
+        let _ = writeln!(
+            report,
+            "{:>2}. [{}] {}{}",
+            i + 1,
+            if result.passed { "PASS" } else { "FAIL" },
+            result.name,
+            if result.required {
+                ""
+            } else {
+                " (not required)"
+            },
+        );
+        let _ = writeln!(report, "      {}", result.detail);
+    }
+    let _ = writeln!(report);
+    let _ = writeln!(report, "RESULT: {}", if go { "GO" } else { "NO-GO" });
+    let _ = writeln!(report, "Signed off by: {}", operator);
+    report
+}
+
+fn write_report(report_dir: &Path, report: &str, timestamp: u64) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(report_dir)?;
+    let path = report_dir.join(format!("preflight_{}.txt", timestamp));
+    std::fs::write(&path, report)?;
+    Ok(path)
+}
@@ -1,43 +1,203 @@
+use std::thread::sleep;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clap::ValueEnum;
 use log::{debug, info, warn, error};
 use sysinfo::{ComponentExt, System, SystemExt};
 
-enum LogLevel {
-    Debug,
-    Info,
-    Warn,
-    Error,
+use crate::fsw_status::fsw_report;
+use crate::status_config::StatusConfig;
+use mfc::common::ipc;
+use mfc::common::mfc_msgs::{
+    Direction, HealthMetric, HealthReport, MetricValue, Severity, Threshold, HEALTH_TOPIC,
+};
+
+/// How the status report is written to the terminal
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub enum ReportFormat {
+    /// Human readable table on stdout
+    Table,
+    /// Machine readable JSON on stdout
+    Json,
+    /// Through the logger, filtered by RUST_LOG
+    Log,
+}
+
+pub fn full_report(format: ReportFormat, listen_window: Duration, config: &StatusConfig) -> i32 {
+    let report = build_report(listen_window, config);
+    match format {
+        ReportFormat::Table => print_table(&report),
+        ReportFormat::Json => print_json(&report),
+        ReportFormat::Log => print_report(&report.metrics),
+    }
+    exit_code(report.worst_severity())
+}
+
+/// Publish a fresh report on the bus every `period` until interrupted
+pub fn publish_report(period: Duration, listen_window: Duration, config: &StatusConfig) {
+    let s = nng::Socket::new(nng::Protocol::Pub0).unwrap();
+    s.listen(ipc::NNG_HEALTH_ADDR).unwrap();
+    info!("Publishing health reports on {}", ipc::NNG_HEALTH_ADDR);
+
+    loop {
+        let report = build_report(listen_window, config);
+        match ipc::publish(&s, HEALTH_TOPIC, &report) {
+            Ok(_) => debug!(
+                "Published health report with {} metrics ({:?})",
+                report.metrics.len(),
+                report.worst_severity()
+            ),
+            Err(e) => error!("Failed to send health report: {:?}", e),
+        }
+
+        sleep(period);
+    }
 }
 
-struct ReportMessage {
-    level: LogLevel,
-    content: String,
+/// Exit code for the process given the worst severity in a report:
+/// 0 when nominal, 1 for warnings, 2 for errors
+pub fn exit_code(severity: Severity) -> i32 {
+    match severity {
+        Severity::Error => 2,
+        Severity::Warn => 1,
+        _ => 0,
+    }
 }
 
-pub fn full_report() {
-    print_report(sys_report());
+fn build_report(listen_window: Duration, config: &StatusConfig) -> HealthReport {
+    let mut metrics = sys_report(config);
+    metrics.extend(fsw_report(listen_window, config));
+    let host = metrics
+        .iter()
+        .find(|metric| metric.name == "host_name")
+        .map(|metric| format_value(&metric.value))
+        .unwrap_or_else(|| String::from("unknown"));
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|t| t.as_secs())
+        .unwrap_or(0);
+    HealthReport {
+        host,
+        timestamp,
+        metrics,
+    }
 }
 
-fn print_report(messages: Vec<ReportMessage>) {
-    for msg in &messages {
-        match &msg.level {
-            LogLevel::Debug => {
-                debug!("{}", &msg.content)
+fn print_report(metrics: &[HealthMetric]) {
+    for metric in metrics {
+        let content = format!(
+            "{:<26} {} {}",
+            metric.name,
+            format_value(&metric.value),
+            metric.unit.as_deref().unwrap_or(""),
+        );
+        match &metric.severity {
+            Severity::Debug => {
+                debug!("{}", content)
             }
-            LogLevel::Info => {
-                info!("{}", &msg.content)
+            Severity::Info => {
+                info!("{}", content)
             },
-            LogLevel::Warn => {
-                warn!("{}", &msg.content)
+            Severity::Warn => {
+                warn!("{}", content)
             },
-            _ => {
-                error!("{}", &msg.content)
+            Severity::Error => {
+                error!("{}", content)
             }
         }
     }
 }
 
-fn sys_report() -> Vec<ReportMessage> {
-    let mut msgs: Vec<ReportMessage> = vec![];
+fn print_table(report: &HealthReport) {
+    println!(
+        "{:<8} {:<40} {:>24} {:<6} {:>17}",
+        "SEVERITY", "METRIC", "VALUE", "UNIT", "WARN / ERROR"
+    );
+    for metric in &report.metrics {
+        let threshold = match &metric.threshold {
+            Some(t) => format!("{:.1} / {:.1}", t.warn, t.error),
+            None => String::from("-"),
+        };
+        println!(
+            "{:<8} {:<40} {:>24} {:<6} {:>17}",
+            format!("{:?}", metric.severity).to_uppercase(),
+            metric.name,
+            format_value(&metric.value),
+            metric.unit.as_deref().unwrap_or(""),
+            threshold,
+        );
+    }
+    println!(
+        "Overall: {}",
+        format!("{:?}", report.worst_severity()).to_uppercase()
+    );
+}
+
+fn print_json(report: &HealthReport) {
+    match serde_json::to_string_pretty(report) {
+        Ok(json) => println!("{}", json),
+        Err(e) => error!("Failed to serialize status report: {:?}", e),
+    }
+}
+
+pub fn format_value(value: &MetricValue) -> String {
+    match value {
+        MetricValue::Number(n) if n.fract() == 0.0 => format!("{}", n),
+        MetricValue::Number(n) => format!("{:.3}", n),
+        MetricValue::Text(t) => t.clone(),
+    }
+}
+
+/// Severity of a value given the limits it must stay within
+fn classify(value: f64, threshold: &Threshold) -> Severity {
+    match threshold.direction {
+        Direction::Above => match value {
+            v if v >= threshold.error => Severity::Error,
+            v if v >= threshold.warn => Severity::Warn,
+            _ => Severity::Info,
+        },
+        Direction::Below => match value {
+            v if v <= threshold.error => Severity::Error,
+            v if v <= threshold.warn => Severity::Warn,
+            _ => Severity::Info,
+        },
+    }
+}
+
+pub fn text_metric(name: &str, value: Option<String>) -> HealthMetric {
+    HealthMetric {
+        name: String::from(name),
+        value: MetricValue::Text(value.unwrap_or_else(|| String::from("unknown"))),
+        unit: None,
+        severity: Severity::Info,
+        threshold: None,
+    }
+}
+
+/// A text metric whose severity is decided by the caller
+pub fn state_metric(name: &str, value: &str, severity: Severity) -> HealthMetric {
+    HealthMetric {
+        severity,
+        ..text_metric(name, Some(String::from(value)))
+    }
+}
+
+pub fn number_metric(name: &str, value: f64, unit: &str, threshold: Option<Threshold>) -> HealthMetric {
+    let severity = match &threshold {
+        Some(t) => classify(value, t),
+        None => Severity::Info,
+    };
+    HealthMetric {
+        name: String::from(name),
+        value: MetricValue::Number(value),
+        unit: Some(String::from(unit)),
+        severity,
+        threshold,
+    }
+}
+
+fn sys_report(config: &StatusConfig) -> Vec<HealthMetric> {
+    let mut msgs: Vec<HealthMetric> = vec![];
 
     // Please note that we use "new_all" to ensure that all list of
     // components, network interfaces, disks and users are already
@@ -48,91 +208,83 @@ fn sys_report() -> Vec<ReportMessage> {
     sys.refresh_all();
 
     // Display system information:
-    let system_info = [
-        ("System host name:        ", sys.host_name()),
-        ("System name:             ", sys.name()),
-        ("System kernel version:   ", sys.kernel_version()),
-        ("System OS version:       ", sys.os_version()),
-    ];
-    for info in system_info {
-        let infoval = match info.1 {
-            None => String::from("unknown"),
-            Some(ref val) => String::from(val),
-        };
-        msgs.push(ReportMessage{
-            level: LogLevel::Info,
-            content: format!("{} {}", info.0, infoval),
-        });
-    }
-    msgs.push(ReportMessage{
-        level: LogLevel::Info,
-        content: format!("Uptime (s):               {}", sys.uptime()),
-    });
-    msgs.push(ReportMessage{
-        level: LogLevel::Info,
-        content: format!("Last boot (unix time):    {}", sys.boot_time()),
-    });
-
-    // average cpu load
+    msgs.push(text_metric("host_name", sys.host_name()));
+    msgs.push(text_metric("system_name", sys.name()));
+    msgs.push(text_metric("kernel_version", sys.kernel_version()));
+    msgs.push(text_metric("os_version", sys.os_version()));
+    msgs.push(number_metric("uptime", sys.uptime() as f64, "s", None));
+    msgs.push(number_metric("boot_time", sys.boot_time() as f64, "s", None));
+
+    // average cpu load, as a percent of all the CPUs
     let cpu_load_avg = sys.load_average();
-    let msg_level = match cpu_load_avg.five {
-        pct if pct >= 80.0 => LogLevel::Error,
-        pct if pct >= 50.0 => LogLevel::Warn,
-        _ => LogLevel::Info,
-    };
-    msgs.push(ReportMessage{
-        level: msg_level,
-        content: format!(
-            "Avg. CPU load: [1 min] {:.3}% | [5 min] {:.3}% | [15 min] {:.3}%",
-            cpu_load_avg.one,
-            cpu_load_avg.five,
-            cpu_load_avg.fifteen,
-        ),
-    });
+    let cpu_pct = 100.0 / sys.processors().len().max(1) as f64;
+    msgs.push(number_metric("cpu_load_1min", cpu_load_avg.one * cpu_pct, "%", None));
+    msgs.push(number_metric(
+        "cpu_load_5min",
+        cpu_load_avg.five * cpu_pct,
+        "%",
+        Some(config.cpu_load_pct),
+    ));
+    msgs.push(number_metric("cpu_load_15min", cpu_load_avg.fifteen * cpu_pct, "%", None));
 
-    // Then let's print the temperature of the different components:
+    // Then let's report the temperature of the different components:
     for component in sys.components() {
-        let msg_content = format!("Temperaure of {:?}", &component);
-        let critical_temp: f32 = 90.0;
-        let msg_level = match component.temperature() {
-            t if t >= critical_temp => LogLevel::Error,
-            t if t >= 0.8 * component.max() => LogLevel::Warn,
-            _ => LogLevel::Info,
-        };
-        msgs.push(ReportMessage{
-            level: msg_level,
-            content: msg_content,
-        });
-        msgs.push(match component.critical() {
-            Some(ref critical_temp) => {
-                ReportMessage{
-                    level: LogLevel::Debug,
-                    content: format!("Detected critical temp for {:}: {:}C", component.label(), critical_temp),
-                }},
-            None => {
-                ReportMessage{
-                    level: LogLevel::Debug,
-                    content: format!("Estimated critical temp for {:}: {:}C", component.label(), critical_temp),
-                }},
-            });
+        msgs.push(number_metric(
+            &format!("temperature.{}", component.label()),
+            component.temperature() as f64,
+            "C",
+            Some(Threshold::above(
+                config.temperature_warn_c,
+                config.temperature_critical_c,
+            )),
+        ));
     }
 
+    // Is the telemetry link up?
+    msgs.push(link_state(&config.eth_interface));
+
+    // Can we trust the clock?
+    msgs.push(clock_sync(config.clock_error_ms));
+
     // And finally the RAM and SWAP information:
-    msgs.push(ReportMessage{
-        level: LogLevel::Info,
-        content: format!("total memory: {} kB", sys.total_memory()),
-    });
-    msgs.push(ReportMessage{
-        level: LogLevel::Info,
-        content: format!("used memory : {} kB", sys.used_memory()),
-    });
-    msgs.push(ReportMessage{
-        level: LogLevel::Info,
-        content: format!("total swap  : {} kB", sys.total_swap()),
-    });
-    msgs.push(ReportMessage{
-        level: LogLevel::Info,
-        content: format!("used swap   : {} kB", sys.used_swap()),
-    });
-    return msgs
+    msgs.push(number_metric("memory_total", sys.total_memory() as f64, "kB", None));
+    msgs.push(number_metric("memory_used", sys.used_memory() as f64, "kB", None));
+    msgs.push(number_metric("swap_total", sys.total_swap() as f64, "kB", None));
+    msgs.push(number_metric("swap_used", sys.used_swap() as f64, "kB", None));
+    msgs
+}
+
+/// Operational state of a network interface, from sysfs
+fn link_state(interface: &str) -> HealthMetric {
+    let name = format!("network.{}.link", interface);
+    let operstate = std::fs::read_to_string(format!("/sys/class/net/{}/operstate", interface));
+    match operstate.as_ref().map(|state| state.trim()) {
+        Ok("up") => state_metric(&name, "up", Severity::Info),
+        Ok(state) => state_metric(&name, state, Severity::Error),
+        Err(_) => state_metric(&name, "no such interface", Severity::Error),
+    }
+}
+
+/// Whether the kernel considers the clock synchronized, and its estimated error
+#[cfg(target_os = "linux")]
+pub fn clock_sync(threshold: Threshold) -> HealthMetric {
+    // a zeroed timex with no mode bits set only reads the clock state
+    let mut timex: libc::timex = unsafe { std::mem::zeroed() };
+    let state = unsafe { libc::adjtimex(&mut timex) };
+    match state {
+        -1 => state_metric("clock.sync", "unknown", Severity::Warn),
+        libc::TIME_ERROR => state_metric("clock.sync", "unsynchronized", Severity::Error),
+        // esterror is in microseconds
+        _ => number_metric(
+            "clock.error",
+            timex.esterror as f64 / 1000.0,
+            "ms",
+            Some(threshold),
+        ),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn clock_sync(_threshold: Threshold) -> HealthMetric {
+    state_metric("clock.sync", "unknown", Severity::Warn)
 }
@@ -0,0 +1,66 @@
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+use serde::Deserialize;
+
+use mfc::common::mfc_msgs::Threshold;
+
+/// Settings and limits for the status report.
+/// Any key missing from the config file keeps its default.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StatusConfig {
+    /// Directory the flight recorder writes to
+    pub recorder_dir: PathBuf,
+    /// Network interface carrying telemetry to `tlm_rx`
+    pub eth_interface: String,
+    /// 5 minute load average as a percent of the CPUs
+    pub cpu_load_pct: Threshold,
+    /// Warn when any component reaches this temperature
+    pub temperature_warn_c: f64,
+    pub temperature_critical_c: f64,
+    pub clock_error_ms: Threshold,
+    pub heartbeat_age_s: Threshold,
+    pub topic_age_s: Threshold,
+    pub recorder_disk_used_pct: Threshold,
+    pub recorder_disk_free_mb: Threshold,
+    pub process_cpu_pct: Threshold,
+    pub process_memory_mb: Threshold,
+    pub process_open_fds: Threshold,
+}
+
+impl Default for StatusConfig {
+    fn default() -> Self {
+        StatusConfig {
+            recorder_dir: PathBuf::from("/var/lib/mfc"),
+            eth_interface: String::from("eth0"),
+            cpu_load_pct: Threshold::above(50.0, 80.0),
+            temperature_warn_c: 75.0,
+            temperature_critical_c: 90.0,
+            clock_error_ms: Threshold::above(100.0, 1_000.0),
+            heartbeat_age_s: Threshold::above(2.0, 5.0),
+            topic_age_s: Threshold::above(2.0, 5.0),
+            recorder_disk_used_pct: Threshold::above(80.0, 95.0),
+            recorder_disk_free_mb: Threshold::below(2_048.0, 512.0),
+            process_cpu_pct: Threshold::above(50.0, 90.0),
+            process_memory_mb: Threshold::above(256.0, 512.0),
+            process_open_fds: Threshold::above(512.0, 900.0),
+        }
+    }
+}
+
+/// Load the status config, falling back to defaults if the file is missing
+pub fn load(path: &Path) -> Result<StatusConfig, String> {
+    if !path.exists() {
+        warn!(
+            "No status config at {}, using default thresholds",
+            path.display()
+        );
+        return Ok(StatusConfig::default());
+    }
+    info!("Loading status config from {}", path.display());
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    toml::from_str(&contents)
+        .map_err(|e| format!("Invalid status config {}: {}", path.display(), e))
+}
@@ -0,0 +1,64 @@
+// ----------------------------------------------------------------------------
+// Altimeter
+// ---------
+// Barometric altitude derived from the raw pressure and temperature the
+// altitude board reports, so its own altitude can be cross-checked.
+// ----------------------------------------------------------------------------
+
+use crate::atmosphere::{
+    geopotential_altitude, Atmosphere, StandardAtmosphere, SEA_LEVEL_PRESSURE,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BaroAltitude {
+    pub pressure_altitude: f32, // standard atmosphere altitude for 1013.25 hPa in m
+    pub altitude: f32,          // geometric altitude referenced to QNH in m
+    pub geopotential_altitude: f32, // geopotential altitude referenced to QNH in m'
+    pub temperature_deviation: Option<f32>, // measured minus model temperature in K
+}
+
+pub struct BaroAltimeter {
+    // Pressure altimeter set to the launch site's QNH
+    atmosphere: Box<dyn Atmosphere>, // model used to turn pressure into altitude
+    qnh: f32,                        // sea level pressure setting in Pa
+}
+
+impl BaroAltimeter {
+    pub fn new(atmosphere: Box<dyn Atmosphere>, qnh: f32) -> Self {
+        return BaroAltimeter { atmosphere, qnh };
+    }
+
+    pub fn set_qnh(&mut self, qnh: f32) {
+        // set the sea level pressure reported for the launch site in Pa
+        self.qnh = qnh;
+    }
+
+    pub fn get_qnh(&self) -> f32 {
+        return self.qnh;
+    }
+
+    pub fn derive(&self, pressure: f32, temperature: Option<f32>) -> BaroAltitude {
+        // derive altitudes from a static pressure in Pa and, if the board
+        // measured it, the air temperature in K
+        let pressure_altitude = StandardAtmosphere.altitude(pressure);
+        // like an altimeter setting, scale the pressure so the model's sea
+        // level pressure lines up with the QNH
+        let altitude = self
+            .atmosphere
+            .altitude(pressure * self.atmosphere.pressure(0.0) / self.qnh);
+        let temperature_deviation = temperature.map(|t| t - self.atmosphere.temperature(altitude));
+        return BaroAltitude {
+            pressure_altitude,
+            altitude,
+            geopotential_altitude: geopotential_altitude(altitude),
+            temperature_deviation,
+        };
+    }
+}
+
+impl Default for BaroAltimeter {
+    fn default() -> Self {
+        // standard atmosphere at standard sea level pressure
+        return BaroAltimeter::new(Box::new(StandardAtmosphere), SEA_LEVEL_PRESSURE);
+    }
+}
@@ -0,0 +1,326 @@
+// ----------------------------------------------------------------------------
+// Atmosphere
+// ----------
+// Air pressure, temperature and density versus altitude. The US Standard
+// Atmosphere 1976 is the default, and anything implementing `Atmosphere`
+// (a measured sounding, a hot or cold day) can stand in for it.
+// ----------------------------------------------------------------------------
+
+use std::fmt;
+use std::path::Path;
+
+/// Standard gravity in m/s^2
+pub const G0: f32 = 9.80665;
+/// Effective radius of the Earth used by the 1976 standard in m
+pub const EARTH_RADIUS: f32 = 6_356_766.0;
+/// Specific gas constant of dry air in J/(kg K)
+pub const R_AIR: f32 = 287.053;
+/// Sea level pressure of the standard atmosphere in Pa
+pub const SEA_LEVEL_PRESSURE: f32 = 101_325.0;
+/// Sea level temperature of the standard atmosphere in K
+pub const SEA_LEVEL_TEMPERATURE: f32 = 288.15;
+
+pub trait Atmosphere: Send {
+    // Properties of the air at a geometric altitude in meters
+
+    /// Static pressure in Pa
+    fn pressure(&self, altitude: f32) -> f32;
+
+    /// Temperature in K
+    fn temperature(&self, altitude: f32) -> f32;
+
+    /// Density in kg/m^3
+    fn density(&self, altitude: f32) -> f32 {
+        // ideal gas law
+        return self.pressure(altitude) / (R_AIR * self.temperature(altitude));
+    }
+
+    /// Geometric altitude in meters where the pressure (Pa) is found
+    fn altitude(&self, pressure: f32) -> f32 {
+        // pressure only ever falls with altitude, so bisect for it
+        let mut low = -5_000.0;
+        let mut high = 100_000.0;
+        for _ in 0..64 {
+            let mid = 0.5 * (low + high);
+            if self.pressure(mid) > pressure {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        return 0.5 * (low + high);
+    }
+}
+
+pub fn geopotential_altitude(geometric_altitude: f32) -> f32 {
+    // convert geometric altitude (m) to geopotential altitude (m')
+    return EARTH_RADIUS * geometric_altitude / (EARTH_RADIUS + geometric_altitude);
+}
+
+pub fn geometric_altitude(geopotential_altitude: f32) -> f32 {
+    // convert geopotential altitude (m') to geometric altitude (m)
+    return EARTH_RADIUS * geopotential_altitude / (EARTH_RADIUS - geopotential_altitude);
+}
+
+// ----------------------------------------------------------------------------
+// US Standard Atmosphere 1976
+// ----------------------------------------------------------------------------
+
+struct Layer {
+    base_altitude: f32,    // geopotential altitude of the layer base in m'
+    base_temperature: f32, // temperature at the layer base in K
+    base_pressure: f32,    // pressure at the layer base in Pa
+    lapse_rate: f32,       // temperature gradient in K/m'
+}
+
+// layers of the standard atmosphere below 86 km
+const LAYERS: [Layer; 7] = [
+    Layer {
+        base_altitude: 0.0,
+        base_temperature: 288.15,
+        base_pressure: 101_325.0,
+        lapse_rate: -0.0065,
+    },
+    Layer {
+        base_altitude: 11_000.0,
+        base_temperature: 216.65,
+        base_pressure: 22_632.06,
+        lapse_rate: 0.0,
+    },
+    Layer {
+        base_altitude: 20_000.0,
+        base_temperature: 216.65,
+        base_pressure: 5_474.889,
+        lapse_rate: 0.001,
+    },
+    Layer {
+        base_altitude: 32_000.0,
+        base_temperature: 228.65,
+        base_pressure: 868.0187,
+        lapse_rate: 0.0028,
+    },
+    Layer {
+        base_altitude: 47_000.0,
+        base_temperature: 270.65,
+        base_pressure: 110.9063,
+        lapse_rate: 0.0,
+    },
+    Layer {
+        base_altitude: 51_000.0,
+        base_temperature: 270.65,
+        base_pressure: 66.93887,
+        lapse_rate: -0.0028,
+    },
+    Layer {
+        base_altitude: 71_000.0,
+        base_temperature: 214.65,
+        base_pressure: 3.95642,
+        lapse_rate: -0.002,
+    },
+];
+
+fn layer_at(geopotential_altitude: f32) -> &'static Layer {
+    // the layer containing a geopotential altitude, extended past either end
+    return LAYERS
+        .iter()
+        .rev()
+        .find(|layer| geopotential_altitude >= layer.base_altitude)
+        .unwrap_or(&LAYERS[0]);
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StandardAtmosphere;
+
+impl Atmosphere for StandardAtmosphere {
+    fn pressure(&self, altitude: f32) -> f32 {
+        let h = geopotential_altitude(altitude);
+        let layer = layer_at(h);
+        let dh = h - layer.base_altitude;
+        if layer.lapse_rate == 0.0 {
+            // isothermal layer
+            return layer.base_pressure * (-G0 * dh / (R_AIR * layer.base_temperature)).exp();
+        }
+        let temperature = layer.base_temperature + layer.lapse_rate * dh;
+        return layer.base_pressure
+            * (layer.base_temperature / temperature).powf(G0 / (R_AIR * layer.lapse_rate));
+    }
+
+    fn temperature(&self, altitude: f32) -> f32 {
+        let h = geopotential_altitude(altitude);
+        let layer = layer_at(h);
+        return layer.base_temperature + layer.lapse_rate * (h - layer.base_altitude);
+    }
+
+    fn altitude(&self, pressure: f32) -> f32 {
+        // invert the pressure equation of the layer the pressure falls in
+        let layer = LAYERS
+            .iter()
+            .rev()
+            .find(|layer| pressure <= layer.base_pressure)
+            .unwrap_or(&LAYERS[0]);
+        let ratio = pressure / layer.base_pressure;
+        let h = if layer.lapse_rate == 0.0 {
+            layer.base_altitude - R_AIR * layer.base_temperature / G0 * ratio.ln()
+        } else {
+            layer.base_altitude
+                + layer.base_temperature / layer.lapse_rate
+                    * (ratio.powf(-R_AIR * layer.lapse_rate / G0) - 1.0)
+        };
+        return geometric_altitude(h);
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Offset atmosphere
+// ----------------------------------------------------------------------------
+
+pub struct OffsetAtmosphere {
+    // Another atmosphere shifted warmer or colder, e.g. a hot or cold day.
+    // Pressure follows the base atmosphere, so only temperature and density
+    // change.
+    base: Box<dyn Atmosphere>,
+    temperature_offset: f32, // added to the base temperature in K
+}
+
+impl OffsetAtmosphere {
+    pub fn new(base: Box<dyn Atmosphere>, temperature_offset: f32) -> Self {
+        return OffsetAtmosphere {
+            base,
+            temperature_offset,
+        };
+    }
+}
+
+impl Atmosphere for OffsetAtmosphere {
+    fn pressure(&self, altitude: f32) -> f32 {
+        return self.base.pressure(altitude);
+    }
+
+    fn temperature(&self, altitude: f32) -> f32 {
+        return self.base.temperature(altitude) + self.temperature_offset;
+    }
+
+    fn altitude(&self, pressure: f32) -> f32 {
+        return self.base.altitude(pressure);
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Sounding atmosphere
+// ----------------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SoundingLevel {
+    pub altitude: f32,    // geometric altitude in m
+    pub pressure: f32,    // static pressure in Pa
+    pub temperature: f32, // temperature in K
+}
+
+#[derive(Debug)]
+pub enum AtmosphereError {
+    Io(std::io::Error),
+    BadLine(usize, String), // line number and what was wrong with it
+    TooFewLevels,
+}
+
+impl fmt::Display for AtmosphereError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AtmosphereError::Io(e) => write!(f, "{}", e),
+            AtmosphereError::BadLine(line, reason) => write!(f, "line {}: {}", line, reason),
+            AtmosphereError::TooFewLevels => write!(f, "a sounding needs at least two levels"),
+        }
+    }
+}
+
+pub struct SoundingAtmosphere {
+    // Measured profile, e.g. from a radiosonde launched near the site.
+    // Temperature is interpolated linearly and pressure exponentially
+    // between levels, and the end levels are extrapolated.
+    levels: Vec<SoundingLevel>,
+}
+
+impl SoundingAtmosphere {
+    pub fn new(mut levels: Vec<SoundingLevel>) -> Result<Self, AtmosphereError> {
+        levels.sort_by(|a, b| a.altitude.total_cmp(&b.altitude));
+        levels.dedup_by(|a, b| a.altitude == b.altitude);
+        if levels.len() < 2 {
+            return Err(AtmosphereError::TooFewLevels);
+        }
+        return Ok(SoundingAtmosphere { levels });
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, AtmosphereError> {
+        let contents = std::fs::read_to_string(path).map_err(AtmosphereError::Io)?;
+        return SoundingAtmosphere::parse(&contents);
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, AtmosphereError> {
+        // Whitespace or comma separated columns, one level per line:
+        //   altitude (m)   pressure (hPa)   temperature (C)
+        // Blank lines, lines starting with '#' and a header line are skipped.
+        let mut levels: Vec<SoundingLevel> = vec![];
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let columns: Vec<&str> = line
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|column| !column.is_empty())
+                .collect();
+            let values: Vec<f32> = match columns.iter().map(|c| c.parse::<f32>()).collect() {
+                Ok(v) => v,
+                // column names are allowed before the first level
+                Err(_) if levels.is_empty() => continue,
+                Err(e) => return Err(AtmosphereError::BadLine(i + 1, e.to_string())),
+            };
+            if values.len() != 3 {
+                return Err(AtmosphereError::BadLine(
+                    i + 1,
+                    format!("expected 3 columns, found {}", values.len()),
+                ));
+            }
+            levels.push(SoundingLevel {
+                altitude: values[0],
+                pressure: values[1] * 100.0,     // hPa to Pa
+                temperature: values[2] + 273.15, // C to K
+            });
+        }
+        return SoundingAtmosphere::new(levels);
+    }
+
+    fn levels_around(&self, altitude: f32) -> (&SoundingLevel, &SoundingLevel) {
+        // the pair of levels to interpolate (or extrapolate) between
+        let n = self.levels.len();
+        let upper = self
+            .levels
+            .iter()
+            .position(|level| level.altitude > altitude)
+            .unwrap_or(n - 1)
+            .clamp(1, n - 1);
+        return (&self.levels[upper - 1], &self.levels[upper]);
+    }
+}
+
+impl Atmosphere for SoundingAtmosphere {
+    fn pressure(&self, altitude: f32) -> f32 {
+        let (below, above) = self.levels_around(altitude);
+        let fraction = (altitude - below.altitude) / (above.altitude - below.altitude);
+        return below.pressure * (above.pressure / below.pressure).powf(fraction);
+    }
+
+    fn temperature(&self, altitude: f32) -> f32 {
+        // hold the end temperatures rather than run away with the lapse rate
+        let first = &self.levels[0];
+        let last = &self.levels[self.levels.len() - 1];
+        if altitude <= first.altitude {
+            return first.temperature;
+        } else if altitude >= last.altitude {
+            return last.temperature;
+        }
+        let (below, above) = self.levels_around(altitude);
+        let fraction = (altitude - below.altitude) / (above.altitude - below.altitude);
+        return below.temperature + fraction * (above.temperature - below.temperature);
+    }
+}
@@ -0,0 +1,119 @@
+// ----------------------------------------------------------------------------
+// Ballast
+// -------
+// Ballast mass estimate from the dump valve history, fused with the load
+// cell so a noisy reading on a swinging payload doesn't trigger an abort and
+// a disagreement between the two shows up as a fault.
+// ----------------------------------------------------------------------------
+
+use std::time::Instant;
+
+use log::{info, warn};
+
+use crate::measurement::{Measurement, Quality};
+
+// slow drift allowed in the estimate even with the valve closed, in kg/sqrt(s)
+const DRIFT_NOISE: f32 = 0.001;
+// consecutive disagreements before calling it a fault
+const FAULT_AFTER_SAMPLES: u32 = 5;
+
+pub struct BallastEstimator {
+    // Scalar Kalman filter on the remaining ballast mass
+    flow_uncertainty: f32, // 1-sigma error of the flow model as a fraction
+    load_cell_noise: f32,  // 1-sigma load cell noise in kg
+    fault_threshold: f32,  // model and load cell disagreeing by this much is a fault in kg
+    mass: Option<f32>,     // estimated ballast mass in kg
+    variance: f32,         // variance of the estimate in kg^2
+    time: Option<Instant>, // time of the estimate
+    last_reading: Option<Instant>, // time of the last load cell reading used
+    disagreements: u32,    // consecutive readings beyond the threshold
+    fault: bool,
+}
+
+impl BallastEstimator {
+    pub fn new(flow_uncertainty: f32, load_cell_noise: f32, fault_threshold: f32) -> Self {
+        return BallastEstimator {
+            flow_uncertainty,
+            load_cell_noise,
+            fault_threshold,
+            mass: None,
+            variance: 0.0,
+            time: None,
+            last_reading: None,
+            disagreements: 0,
+            fault: false,
+        };
+    }
+
+    pub fn predict(&mut self, dump_flow: f32, now: Instant) {
+        // take out what flowed through the dump valve (kg/s) since the last
+        // estimate
+        if let (Some(mass), Some(time)) = (self.mass, self.time) {
+            let dt = now.saturating_duration_since(time).as_secs_f32();
+            let dumped = dump_flow * dt;
+            self.mass = Some((mass - dumped).max(0.0));
+            self.variance += (self.flow_uncertainty * dumped).powi(2) + DRIFT_NOISE.powi(2) * dt;
+        }
+        self.time = Some(now);
+    }
+
+    pub fn update(&mut self, load_cell: &Measurement<f32>) {
+        // fuse a load cell reading, once
+        if load_cell.quality == Quality::Bad
+            || self
+                .last_reading
+                .is_some_and(|last| load_cell.timestamp <= last)
+        {
+            return;
+        }
+        self.last_reading = Some(load_cell.timestamp);
+
+        let mass = match self.mass {
+            Some(v) => v,
+            None => {
+                // start from the first reading
+                self.mass = Some(load_cell.value);
+                self.variance = self.load_cell_noise.powi(2);
+                return;
+            }
+        };
+
+        let residual = load_cell.value - mass;
+        if residual.abs() > self.fault_threshold {
+            self.disagreements += 1;
+        } else {
+            self.disagreements = 0;
+        }
+        let fault = self.disagreements >= FAULT_AFTER_SAMPLES;
+        if fault != self.fault {
+            if fault {
+                warn!(
+                    "Load cell reads {} kg but dump history says {} kg: valve or load cell fault!",
+                    load_cell.value, mass
+                );
+            } else {
+                info!("Load cell and dump history agree again");
+            }
+            self.fault = fault;
+        }
+
+        let gain = self.variance / (self.variance + self.load_cell_noise.powi(2));
+        self.mass = Some(mass + gain * residual);
+        self.variance *= 1.0 - gain;
+    }
+
+    pub fn is_faulted(&self) -> bool {
+        return self.fault;
+    }
+
+    pub fn estimate(&self) -> Option<Measurement<f32>> {
+        // estimated ballast mass, suspect while the sources disagree, stamped
+        // with the last load cell reading so it ages like the telemetry
+        let quality = if self.fault {
+            Quality::Suspect
+        } else {
+            Quality::Good
+        };
+        return Some(Measurement::new(self.mass?, self.last_reading?).with_quality(quality));
+    }
+}
@@ -0,0 +1,79 @@
+// ----------------------------------------------------------------------------
+// Budget
+// ------
+// Limits on how fast the controller may spend consumables. A budget caps how
+// much can be used over a rolling window (e.g. vent seconds per hour), and
+// the slew limit caps how fast a valve may be opened.
+// ----------------------------------------------------------------------------
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use log::warn;
+
+pub struct Budget {
+    // Consumption allowed over a rolling window
+    name: String,
+    limit: f32,                      // most that may be used per window
+    window: Duration,                // length of the rolling window
+    spent: VecDeque<(Instant, f32)>, // what was used when, oldest first
+    exhausted: bool,                 // whether the last check ran out
+}
+
+impl Budget {
+    pub fn new(name: &str, limit: f32, window: Duration) -> Self {
+        return Budget {
+            name: String::from(name),
+            limit,
+            window,
+            spent: VecDeque::new(),
+            exhausted: false,
+        };
+    }
+
+    pub fn consume(&mut self, amount: f32, now: Instant) {
+        // record consumption and forget what has left the window
+        if amount > 0.0 {
+            self.spent.push_back((now, amount));
+        }
+        while let Some((time, _)) = self.spent.front() {
+            if now.saturating_duration_since(*time) > self.window {
+                self.spent.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    pub fn used(&self) -> f32 {
+        // consumption within the window as of the last call to consume
+        return self.spent.iter().map(|(_, amount)| amount).sum();
+    }
+
+    pub fn remaining(&self) -> f32 {
+        return (self.limit - self.used()).max(0.0);
+    }
+
+    pub fn allow(&mut self) -> bool {
+        // whether there is any budget left to spend, warning once when it
+        // runs out
+        let exhausted = self.remaining() <= 0.0;
+        if exhausted && !self.exhausted {
+            warn!(
+                "{} budget of {} per {:?} is used up!",
+                self.name, self.limit, self.window
+            );
+        }
+        self.exhausted = exhausted;
+        return !exhausted;
+    }
+}
+
+pub fn slew_limit(current: f32, target: f32, max_rate: f32, dt: Duration) -> f32 {
+    // limit how fast a PWM may open up. Closing is never held back, since
+    // that only saves consumables.
+    if target <= current {
+        return target;
+    }
+    return target.min(current + max_rate * dt.as_secs_f32());
+}
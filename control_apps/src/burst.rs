@@ -0,0 +1,142 @@
+// ----------------------------------------------------------------------------
+// Burst
+// -----
+// Recognizes a burst balloon or a failing envelope from the ascent rate, so
+// the controller stops trying to fight a descent that ballast can't stop.
+// A burst is a sudden drop into a fast fall. An envelope failure is a steady
+// descent while the lift gas model says there is still free lift. The model
+// leans on the ballast estimate, so a leak has to hold for longer before it
+// is believed. Once confirmed an anomaly stays latched until the ground
+// looks at it and resets it.
+// ----------------------------------------------------------------------------
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use log::warn;
+
+use crate::measurement::Measurement;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Anomaly {
+    Burst,              // the balloon is gone, falling under the parachute
+    UncommandedDescent, // losing lift we didn't vent
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Anomaly::Burst => write!(f, "Burst"),
+            Anomaly::UncommandedDescent => write!(f, "UncommandedDescent"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct BurstLimits {
+    pub burst_rate: f32, // falling faster than this in m/s is a fast fall, negative
+    pub burst_deceleration: f32, // ascent rate dropping faster than this in m/s^2 is sudden
+    pub descent_rate: f32, // falling faster than this in m/s with free lift is a leak, negative
+    pub confirm: Duration, // how long a signature has to hold
+    pub leak_confirm: Duration, // how long a leak has to hold, longer since it trusts the lift gas model
+}
+
+pub struct BurstDetector {
+    limits: BurstLimits,
+    last_rate: Option<(f32, Instant)>, // previous ascent rate and when
+    sudden_drop: bool,                 // rate fell sharply since we were last not descending
+    suspect: Option<(Anomaly, Instant)>, // signature seen, and since when
+    detected: Option<Anomaly>,         // confirmed, latched
+}
+
+impl BurstDetector {
+    pub fn new(limits: BurstLimits) -> Self {
+        return BurstDetector {
+            limits,
+            last_rate: None,
+            sudden_drop: false,
+            suspect: None,
+            detected: None,
+        };
+    }
+
+    pub fn detected(&self) -> Option<Anomaly> {
+        return self.detected;
+    }
+
+    pub fn reset(&mut self) {
+        // forget what was seen and start watching again
+        self.last_rate = None;
+        self.sudden_drop = false;
+        self.suspect = None;
+        self.detected = None;
+    }
+
+    pub fn update(
+        &mut self,
+        ascent_rate: &Measurement<f32>,
+        free_lift: f32,
+        commanded_descent: bool,
+    ) -> Option<Anomaly> {
+        // look for a burst or leak signature, returning it once when it is
+        // first confirmed
+        if self.detected.is_some() || !ascent_rate.is_good() {
+            return None;
+        }
+        let (rate, now) = (ascent_rate.value, ascent_rate.timestamp);
+
+        // how fast the ascent rate is changing
+        let dropping = match self.last_rate {
+            Some((last_rate, last_time)) => {
+                let dt = now.saturating_duration_since(last_time).as_secs_f32();
+                dt > 0.0 && (rate - last_rate) / dt < -self.limits.burst_deceleration
+            }
+            None => false,
+        };
+        self.last_rate = Some((rate, now));
+        if dropping {
+            self.sudden_drop = true;
+        } else if rate >= self.limits.descent_rate {
+            self.sudden_drop = false; // not coming down, start over
+        }
+
+        let signature = if self.sudden_drop && rate < self.limits.burst_rate {
+            Some(Anomaly::Burst)
+        } else if !commanded_descent && free_lift > 0.0 && rate < self.limits.descent_rate {
+            Some(Anomaly::UncommandedDescent)
+        } else {
+            None
+        };
+        let anomaly = match signature {
+            Some(v) => v,
+            None => {
+                self.suspect = None;
+                return None;
+            }
+        };
+        // a burst can look like a leak for its first few seconds, so any
+        // signature counts toward confirming the worst one seen
+        let since = match self.suspect {
+            Some((_, since)) => since,
+            None => now,
+        };
+        let worst = match self.suspect {
+            Some((Anomaly::Burst, _)) => Anomaly::Burst,
+            _ => anomaly,
+        };
+        self.suspect = Some((worst, since));
+        let confirm = match worst {
+            Anomaly::Burst => self.limits.confirm,
+            Anomaly::UncommandedDescent => self.limits.leak_confirm,
+        };
+        if now.saturating_duration_since(since) < confirm {
+            return None;
+        }
+        warn!(
+            "{} detected! Ascent rate {} m/s, free lift {} kg",
+            worst, rate, free_lift
+        );
+        self.detected = Some(worst);
+        return self.detected;
+    }
+}
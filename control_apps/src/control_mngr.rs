@@ -5,15 +5,25 @@
 // ----------------------------------------------------------------------------
 
 use std::fmt;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 use toml::Value;
 
 use bitflags::bitflags;
 use log::{debug, info, warn};
 
+use crate::atmosphere::{Atmosphere, StandardAtmosphere};
+use crate::ballast::BallastEstimator;
+use crate::budget::{slew_limit, Budget};
+use crate::burst::{Anomaly, BurstDetector, BurstLimits};
 use crate::controller::Controller;
-use crate::controller::Valve;
-use crate::measurement::Measurement;
+use crate::controller::{Calibration, GainSchedule, Gains, Valve};
+use crate::endurance::{ConsumptionHistory, Endurance, EnduranceModel, Rates};
+use crate::landing::{DescentModel, LandingModel, LandingPrediction};
+use crate::lift_gas::{BalloonModel, LiftGasEstimator, LiftGasReport};
+use crate::measurement::{Measurement, Quality};
+use crate::solar::{sun_is_up, sun_position, FeedForward, SolarModel};
+use crate::wind::WindProfile;
+use crate::wind_estimate::{SurveySettings, WindEstimator};
 use pid::Pid;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -22,6 +32,9 @@ pub enum ControlMode {
     Init,      // startup, POST, FSW initialization, HW initialization
     Ready,     // not allowed to actuate valves, but waiting for go-ahead
     Stabilize, // actively actuate valves
+    HoldRate,  // actively actuate valves to hold an ascent rate
+    Descend,   // vent down to the ground, then land on the remaining ballast
+    Economy,   // only act to keep the balloon within a wide altitude band
     Safe,      // not allowed to actuate valves, sit tight
     Abort,     // panic! dump all ballast and lock balloon valve closed
 }
@@ -32,6 +45,9 @@ impl fmt::Display for ControlMode {
             ControlMode::Init => write!(f, "Init"),
             ControlMode::Ready => write!(f, "Ready"),
             ControlMode::Stabilize => write!(f, "Stabilize"),
+            ControlMode::HoldRate => write!(f, "HoldRate"),
+            ControlMode::Descend => write!(f, "Descend"),
+            ControlMode::Economy => write!(f, "Economy"),
             ControlMode::Safe => write!(f, "Safe"),
             ControlMode::Abort => write!(f, "Abort"),
         }
@@ -40,7 +56,7 @@ impl fmt::Display for ControlMode {
 
 bitflags! {
     #[derive(Default)]
-    struct ControlStatus: u32 {
+    pub struct ControlStatus: u32 {
         // registers to indicate when conditions are true
         const INACTIVE          = 0b00000000;
         const ACTIVE            = 0b00000001;
@@ -49,7 +65,11 @@ bitflags! {
         const STALE_TELEMETRY   = 0b00001000;
         const ALTITUDE_DEADZONE = 0b00010000;
         const SPEED_DEADZONE    = 0b00100000;
+        const BALLAST_FAULT     = 0b01000000;
         const PROBLEM           = 0b10000000;
+        const BUDGET_LIMITED    = 0b100000000;
+        const BURST             = 0b1000000000;
+        const SOLAR_FEED_FORWARD = 0b10000000000;
     }
 }
 
@@ -61,20 +81,90 @@ pub struct ControlCommand {
     pub dump_pwm: f32,
 }
 
+#[derive(Copy, Clone, Debug)]
+pub struct RemainingBudget {
+    // Consumables the controller may still spend within the budget window
+    pub vent_open_time: f32, // equivalent fully open vent time in s
+    pub ballast_mass: f32,   // ballast in kg
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct DescentProfile {
+    // How to come down and land without a cutdown
+    pub rate: f32,             // ascent rate to vent down at in m/s, negative
+    pub landing_rate: f32,     // ascent rate to slow to for landing in m/s, negative
+    pub ground_altitude: f32,  // altitude of the landing site in m
+    pub flare_height: f32,     // height above ground to slow down at in m
+    pub touchdown_height: f32, // standing still below this height in m is on the ground
+    pub landing_ballast: f32,  // ballast kept for landing in kg
+    pub landed_rate: f32,      // slower than this in m/s is standing still
+    pub landed_time: Duration, // standing still on the ground this long is landed
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct EconomyBand {
+    // How loosely to hold altitude to save ballast on a long float
+    pub low: f32,             // bottom of the band in m
+    pub high: f32,            // top of the band in m
+    pub horizon: Duration,    // act when the balloon would leave the band within this
+    pub ballast_per_day: f32, // ballast a day of floating takes in kg
+    pub gas_per_day: f32,     // lift gas a day of floating loses in kg
+}
+
 pub struct ControlMngr {
     // Master altitude control state machine
     mode: ControlMode,
     status: ControlStatus,
-    vent_valve: Valve,      // vent valve object
-    dump_valve: Valve,      // dump valve object
-    target_altitude: f32,   // target altitude hold in meters
-    controller: Controller, // PID controller object
-    altitude_floor: f32,    // minimum allowed altitude in meters
-    error_deadzone: f32,    // magnitude of margin to allow without actuation
-    error_ready: f32,       // basically opposite of deadzone
-    speed_deadzone: f32,    // magnitude of margin to allow without actuation
-    tlm_max_age: Duration,  // maximum age of telemetry to act on
-    min_ballast: f32,       // abort if ballast is less than this in kg
+    vent_valve: Valve,                   // vent valve object
+    dump_valve: Valve,                   // dump valve object
+    target_altitude: f32,                // target altitude hold in meters
+    target_rate: f32,                    // target ascent rate hold in m/s
+    rate_commanded: bool,                // rate hold was commanded rather than from the profile
+    profile_ascent_rate: f32,            // climb to a target above at this rate, 0 to not
+    profile_descent_rate: f32,           // descend to a target below at this rate, 0 to not
+    profile_reentry_error: f32,          // leave Stabilize for the profile beyond this error in m
+    controller: Controller,              // PID controller object
+    altitude_floor: f32,                 // minimum allowed altitude in meters
+    error_deadzone: f32,                 // magnitude of margin to allow without actuation
+    error_ready: f32,                    // basically opposite of deadzone
+    speed_deadzone: f32,                 // magnitude of margin to allow without actuation
+    tlm_max_age: Duration,               // maximum age of telemetry to act on
+    min_ballast: f32,                    // abort if ballast is less than this in kg
+    bad_tlm_timeout: Duration,           // problem if telemetry is bad for this long
+    bad_tlm_since: Option<Instant>,      // when telemetry started being bad
+    ballast: BallastEstimator,           // ballast mass from dump history and load cell
+    lift_gas: LiftGasEstimator,          // lift gas left from vent history
+    vent_budget: Budget,                 // equivalent fully open vent seconds per hour
+    ballast_budget: Budget,              // ballast kg dumped per hour
+    pwm_slew_rate: f32,                  // fastest a valve may open in PWM per second
+    last_update: Option<Instant>,        // when the valves were last set
+    atmosphere: Box<dyn Atmosphere>,     // air density for gain scheduling
+    gains: Gains,                        // gains the controller is running with
+    rate_gains: Gains,                   // gains for holding an ascent rate
+    descent: DescentProfile,             // how to come down and land
+    still_since: Option<Instant>,        // when the balloon stopped moving near the ground
+    burst: BurstDetector,                // watches for a burst or leaking envelope
+    solar: SolarModel,                   // what to spend ahead of sunset and sunrise
+    position: Option<(f32, f32)>,        // latitude and longitude in degrees, once known
+    sun_elevation: Option<f32>,          // degrees above the horizon, once known
+    feed_forward: FeedForward,           // flows to add ahead of sunset or sunrise
+    economy: EconomyBand,                // where to float when saving ballast
+    expected_duration: Option<Duration>, // flight time the consumables are good for
+    ballast_mass: f32,                   // latest ballast estimate in kg
+    altitude: f32,                       // latest altitude in m
+    ascent_rate: f32,                    // latest ascent rate in m/s
+    history: ConsumptionHistory,         // what has been spent while floating
+    endurance: EnduranceModel,           // how to forecast from the history
+    endurance_period: Duration,          // how often to forecast
+    last_forecast: Option<Instant>,      // when the last forecast was made
+    landing: LandingModel,               // how to find where we'll come down
+    parachute: DescentModel,             // coming down without the balloon
+    wind: WindProfile,                   // winds to drift with on the way down
+    forecast: WindProfile,               // forecast winds, where none were measured
+    wind_estimator: WindEstimator,       // winds measured on the way up
+    survey_period: Duration,             // how often to share the measured winds
+    last_survey: Option<Instant>,        // when the measured winds were last shared
+    surveyed_samples: u32,               // samples in the last shared profile
 }
 
 impl ControlMngr {
@@ -83,36 +173,133 @@ impl ControlMngr {
             "Setting up altitude controller with following config: \n{}",
             config
         );
-        let target_altitude = config["target_altitude_m"]
-            .as_float().unwrap() as f32; // desired flight altitude
-        let vent_kp = config["vent_kp"]
-            .as_float().unwrap() as f32; // vent valve controller proportional gain
-        let vent_ki = config["vent_ki"]
-            .as_float().unwrap() as f32; // vent valve controller integral gain
-        let vent_kd = config["vent_kd"]
-            .as_float().unwrap() as f32; // vent valve controller derivatitve gain
-        let dump_kp = config["dump_kp"]
-            .as_float().unwrap() as f32; // dump valve controller proportional gain
-        let dump_ki = config["dump_ki"]
-            .as_float().unwrap() as f32; // dump valve controller integral gain
-        let dump_kd = config["dump_kd"]
-            .as_float().unwrap() as f32; // dump valve controller derivatitve gain
-        let altitude_floor = config["altitude_floor_m"]
-            .as_float().unwrap() as f32; // minimum allowed altitude in meters
-        let error_deadzone = config["error_deadzone_m"]
-            .as_float().unwrap() as f32; // magnitude of margin to allow without actuation
-        let error_ready = config["error_ready_threshold_m"]
-            .as_float().unwrap() as f32; // basically opposite of deadzone
-        let speed_deadzone = config["speed_deadzone_m_s"]
-            .as_float().unwrap() as f32; // magnitude of margin to allow without actuation
-        let tlm_max_age = Duration::from_secs(config["tlm_max_age_s"]
-            .as_float().unwrap() as u64); // maximum age of telemetry to act on
-        let min_ballast = config["min_ballast_kg"]
-            .as_float().unwrap() as f32; // abort if ballast is less than this in kg
+        let target_altitude = config["target_altitude_m"].as_float().unwrap() as f32; // desired flight altitude
+        let altitude_floor = config["altitude_floor_m"].as_float().unwrap() as f32; // minimum allowed altitude in meters
+        let error_deadzone = config["error_deadzone_m"].as_float().unwrap() as f32; // magnitude of margin to allow without actuation
+        let error_ready = config["error_ready_threshold_m"].as_float().unwrap() as f32; // basically opposite of deadzone
+        let speed_deadzone = config["speed_deadzone_m_s"].as_float().unwrap() as f32; // magnitude of margin to allow without actuation
+        let tlm_max_age = Duration::from_secs(config["tlm_max_age_s"].as_float().unwrap() as u64); // maximum age of telemetry to act on
+        let min_ballast = config["min_ballast_kg"].as_float().unwrap() as f32; // abort if ballast is less than this in kg
+        let bad_tlm_timeout =
+            Duration::from_secs_f64(config["bad_tlm_timeout_s"].as_float().unwrap()); // problem if telemetry is bad for this long
+        let ballast = BallastEstimator::new(
+            config["dump_flow_uncertainty"].as_float().unwrap() as f32, // flow model error fraction
+            config["load_cell_noise_kg"].as_float().unwrap() as f32,    // load cell 1-sigma noise
+            config["ballast_fault_kg"].as_float().unwrap() as f32, // disagreement that is a fault
+        );
+        let balloon = BalloonModel {
+            gas_molar_mass: config["lift_gas_molar_mass_kg_mol"].as_float().unwrap() as f32, // e.g. helium or hydrogen
+            vent_area: config["vent_area_m2"].as_float().unwrap() as f32, // effective vent area
+            vent_head: config["vent_head_m"].as_float().unwrap() as f32, // gas column driving the vent flow
+            volume: config["balloon_volume_m3"].as_float().unwrap() as f32, // fully inflated balloon volume
+            system_mass: config["system_mass_kg"].as_float().unwrap() as f32, // everything but gas and ballast
+        };
+        let lift_gas = LiftGasEstimator::new(
+            balloon,
+            Box::new(StandardAtmosphere),
+            config["initial_lift_gas_kg"].as_float().unwrap() as f32, // gas at launch
+        );
+        let budget_window = Duration::from_secs(3600);
+        let vent_budget = Budget::new(
+            "Vent",
+            config["vent_budget_s_per_hr"].as_float().unwrap() as f32, // open seconds per hour
+            budget_window,
+        );
+        let ballast_budget = Budget::new(
+            "Ballast",
+            config["ballast_budget_kg_per_hr"].as_float().unwrap() as f32, // kg per hour
+            budget_window,
+        );
+        let profile_ascent_rate = config["profile_ascent_rate_m_s"].as_float().unwrap() as f32; // climb to a target above at this rate
+        let profile_descent_rate = config["profile_descent_rate_m_s"].as_float().unwrap() as f32; // descend to a target below at this rate
+        let profile_reentry_error = config["profile_reentry_error_m"].as_float().unwrap() as f32; // far enough off to follow the profile again
+        let rate_gains = Gains {
+            kp: config["rate_kp"].as_float().unwrap() as f32, // rate hold proportional gain
+            ki: config["rate_ki"].as_float().unwrap() as f32, // rate hold integral gain
+            kd: config["rate_kd"].as_float().unwrap() as f32, // rate hold derivative gain
+        };
+        let descent = DescentProfile {
+            rate: config["descent_rate_m_s"].as_float().unwrap() as f32,
+            landing_rate: config["landing_rate_m_s"].as_float().unwrap() as f32,
+            ground_altitude: config["ground_altitude_m"].as_float().unwrap() as f32,
+            flare_height: config["flare_height_m"].as_float().unwrap() as f32,
+            touchdown_height: config["touchdown_height_m"].as_float().unwrap() as f32,
+            landing_ballast: config["landing_ballast_kg"].as_float().unwrap() as f32,
+            landed_rate: config["landed_rate_m_s"].as_float().unwrap() as f32,
+            landed_time: Duration::from_secs_f64(config["landed_time_s"].as_float().unwrap()),
+        };
+        let burst = BurstDetector::new(BurstLimits {
+            burst_rate: config["burst_rate_m_s"].as_float().unwrap() as f32,
+            burst_deceleration: config["burst_deceleration_m_s2"].as_float().unwrap() as f32,
+            descent_rate: config["uncommanded_descent_rate_m_s"].as_float().unwrap() as f32,
+            confirm: Duration::from_secs_f64(config["burst_confirm_s"].as_float().unwrap()),
+            leak_confirm: Duration::from_secs_f64(config["leak_confirm_s"].as_float().unwrap()),
+        });
+        let solar = SolarModel {
+            lead: Duration::from_secs_f64(config["solar_lead_s"].as_float().unwrap()),
+            sunset_ballast: config["sunset_ballast_kg"].as_float().unwrap() as f32,
+            sunrise_vent: config["sunrise_vent_s"].as_float().unwrap() as f32,
+        };
+        let economy = EconomyBand {
+            low: config["economy_band_low_m"].as_float().unwrap() as f32,
+            high: config["economy_band_high_m"].as_float().unwrap() as f32,
+            horizon: Duration::from_secs_f64(config["economy_horizon_s"].as_float().unwrap()),
+            ballast_per_day: config["economy_ballast_kg_per_day"].as_float().unwrap() as f32,
+            gas_per_day: config["economy_gas_kg_per_day"].as_float().unwrap() as f32,
+        };
+        // until there's a history, expect what a day of economy takes
+        let day = 86_400.0;
+        let endurance = EnduranceModel {
+            default_rates: Rates {
+                ballast: economy.ballast_per_day / day,
+                gas: economy.gas_per_day / day,
+            },
+            min_history: Duration::from_secs_f64(
+                config["endurance_min_history_s"].as_float().unwrap(),
+            ),
+            step: Duration::from_secs_f64(config["endurance_step_s"].as_float().unwrap()),
+            horizon: Duration::from_secs_f64(
+                config["endurance_horizon_days"].as_float().unwrap() * day as f64,
+            ),
+        };
+        let endurance_period =
+            Duration::from_secs_f64(config["endurance_period_s"].as_float().unwrap()); // how often to forecast
+        let landing = LandingModel {
+            ground_altitude: descent.ground_altitude,
+            step: config["landing_step_m"].as_float().unwrap() as f32,
+            wind_sigma: config["landing_wind_sigma_m_s"].as_float().unwrap() as f32,
+            wind_layer: config["wind_layer_m"].as_float().unwrap() as f32,
+            rate_sigma: config["landing_rate_sigma"].as_float().unwrap() as f32,
+        };
+        let parachute = DescentModel::Parachute {
+            mass: config["parachute_mass_kg"].as_float().unwrap() as f32,
+            drag_area: config["parachute_drag_area_m2"].as_float().unwrap() as f32,
+        };
+        let survey = SurveySettings {
+            layer_depth: config["wind_layer_m"].as_float().unwrap() as f32,
+            min_samples: config["wind_min_samples"].as_integer().unwrap() as u32,
+            min_baseline: Duration::from_secs_f64(
+                config["wind_min_baseline_s"].as_float().unwrap(),
+            ),
+            max_gap: Duration::from_secs_f64(config["wind_max_gap_s"].as_float().unwrap()),
+            max_speed: config["wind_max_speed_m_s"].as_float().unwrap() as f32,
+        };
+        let survey_period =
+            Duration::from_secs_f64(config["wind_survey_period_s"].as_float().unwrap()); // how often to share the measured winds
+        let pwm_slew_rate = config["pwm_slew_rate_per_s"].as_float().unwrap() as f32; // fastest a valve may open in PWM per second
 
         // initialize valve objects
-        let vent_valve = Valve::new(-1.0, 0.0, vent_kp, vent_ki, vent_kd, String::from("VENTER"));
-        let dump_valve = Valve::new(0.0, 1.0, dump_kp, dump_ki, dump_kd, String::from("DUMPER"));
+        let mut vent_valve = Valve::new(-1.0, 0.0, 0.0, 0.0, 0.0, String::from("VENTER"));
+        let mut dump_valve = Valve::new(0.0, 1.0, 0.0, 0.0, 0.0, String::from("DUMPER"));
+        vent_valve.set_calibration(calibration(&config, "vent"));
+        dump_valve.set_calibration(calibration(&config, "dump"));
+        vent_valve.set_schedule(gain_schedule(&config, "vent"));
+        dump_valve.set_schedule(gain_schedule(&config, "dump"));
+        // start with the gains for where we mean to fly
+        let atmosphere = Box::new(StandardAtmosphere);
+        let target_density = atmosphere.density(target_altitude);
+        let gains = vent_valve.schedule_gains(target_altitude, target_density);
+        dump_valve.schedule_gains(target_altitude, target_density);
 
         // define PID error and output limits (-limit <= term <= limit)
         let p_limit = 1.0;
@@ -121,9 +308,9 @@ impl ControlMngr {
         let output_limit = 1.0;
         // initialize PID controllers beginning with vent gains
         let pid_controller = Pid::new(
-            vent_kp,
-            vent_ki,
-            vent_kd,
+            gains.kp,
+            gains.ki,
+            gains.kd,
             p_limit,
             i_limit,
             d_limit,
@@ -139,6 +326,11 @@ impl ControlMngr {
             vent_valve,
             dump_valve,
             target_altitude,
+            target_rate: 0.0,
+            rate_commanded: false,
+            profile_ascent_rate,
+            profile_descent_rate,
+            profile_reentry_error,
             controller,
             altitude_floor,
             error_deadzone,
@@ -146,6 +338,41 @@ impl ControlMngr {
             speed_deadzone,
             tlm_max_age,
             min_ballast,
+            bad_tlm_timeout,
+            bad_tlm_since: None,
+            ballast,
+            lift_gas,
+            vent_budget,
+            ballast_budget,
+            pwm_slew_rate,
+            last_update: None,
+            atmosphere,
+            gains,
+            rate_gains,
+            descent,
+            still_since: None,
+            burst,
+            solar,
+            position: None,
+            sun_elevation: None,
+            feed_forward: FeedForward::default(),
+            economy,
+            expected_duration: None,
+            ballast_mass: 0.0,
+            altitude: 0.0,
+            ascent_rate: 0.0,
+            history: ConsumptionHistory::new(),
+            endurance,
+            endurance_period,
+            last_forecast: None,
+            landing,
+            parachute,
+            wind: WindProfile::calm(),
+            forecast: WindProfile::calm(),
+            wind_estimator: WindEstimator::new(survey),
+            survey_period,
+            last_survey: None,
+            surveyed_samples: 0,
         };
     }
 
@@ -153,12 +380,190 @@ impl ControlMngr {
         return self.mode;
     }
 
+    pub fn get_status(&self) -> ControlStatus {
+        return self.status;
+    }
+
+    pub fn get_lift_gas(&self) -> LiftGasReport {
+        return self.lift_gas.report();
+    }
+
+    pub fn get_gains(&self) -> Gains {
+        return self.gains;
+    }
+
+    pub fn get_anomaly(&self) -> Option<Anomaly> {
+        // a burst or leak, once one has been detected
+        return self.burst.detected();
+    }
+
+    pub fn rearm(&mut self) {
+        // the ground has looked at a burst or leak and wants control back
+        let anomaly = match self.burst.detected() {
+            Some(v) => v,
+            None => return,
+        };
+        self.burst.reset();
+        self.status.set(ControlStatus::BURST, false);
+        if self.mode == ControlMode::Safe {
+            info!("{} cleared by the ground --> Ready!", anomaly);
+            self.mode = ControlMode::Ready;
+        }
+    }
+
+    pub fn get_sun_elevation(&self) -> Option<f32> {
+        // degrees above the horizon, once the position is known
+        return self.sun_elevation;
+    }
+
+    pub fn get_expected_duration(&self) -> Option<Duration> {
+        // how much longer the ballast and lift gas can keep us floating as
+        // of the last endurance forecast, None before the first one or if
+        // it's past the forecast horizon
+        return self.expected_duration;
+    }
+
+    pub fn forecast_endurance(&mut self) -> Option<Endurance> {
+        // a fresh forecast of how long until the ballast runs out, once
+        // every forecast period, None in between
+        let now = Instant::now();
+        if let Some(last) = self.last_forecast {
+            if now.saturating_duration_since(last) < self.endurance_period {
+                return None;
+            }
+        }
+        self.last_forecast = Some(now);
+        let forecast = self.endurance.predict(
+            &self.history,
+            self.usable_ballast(self.ballast_mass),
+            self.spare_gas(self.ballast_mass),
+            self.position,
+            self.altitude,
+            SystemTime::now(),
+        );
+        self.expected_duration = forecast.remaining;
+        return Some(forecast);
+    }
+
+    pub fn set_wind_profile(&mut self, forecast: WindProfile) {
+        // forecast winds to use for landing predictions where none have
+        // been measured
+        self.wind = forecast.overlay(&self.wind_estimator.profile());
+        self.forecast = forecast;
+    }
+
+    pub fn restore_wind(&mut self, measured: &WindProfile) {
+        // winds measured before a restart, to keep measuring on top of
+        self.wind_estimator.restore(measured);
+        self.wind = self.forecast.overlay(&self.wind_estimator.profile());
+    }
+
+    pub fn get_wind_profile(&self) -> &WindProfile {
+        return &self.wind;
+    }
+
+    pub fn observe_drift(
+        &mut self,
+        latitude: f32,
+        longitude: f32,
+        gps_altitude: &Measurement<f32>,
+    ) {
+        // a GPS fix on the way up, to measure the winds from, unless its
+        // altitude failed validation
+        if gps_altitude.quality == Quality::Bad {
+            return;
+        }
+        self.wind_estimator.record(
+            latitude,
+            longitude,
+            gps_altitude.value,
+            gps_altitude.timestamp,
+        );
+    }
+
+    pub fn survey_wind(&mut self) -> Option<WindProfile> {
+        // the winds measured so far, once every survey period when there
+        // is something new, None in between. Landing predictions use them
+        // from then on.
+        let now = Instant::now();
+        if let Some(last) = self.last_survey {
+            if now.saturating_duration_since(last) < self.survey_period {
+                return None;
+            }
+        }
+        if self.wind_estimator.samples() == self.surveyed_samples {
+            return None;
+        }
+        self.last_survey = Some(now);
+        self.surveyed_samples = self.wind_estimator.samples();
+        let measured = self.wind_estimator.profile();
+        self.wind = self.forecast.overlay(&measured);
+        return Some(measured);
+    }
+
+    pub fn landing_descent(&self) -> DescentModel {
+        // how we'd come down from here: venting down when descending, under
+        // the parachute once the balloon is lost, at the held or measured
+        // rate while it's still coming down on its own, and otherwise under
+        // the parachute after a cutdown
+        if self.mode == ControlMode::Descend {
+            return DescentModel::Venting {
+                rate: self.descent.rate,
+                landing_rate: self.descent.landing_rate,
+                flare_height: self.descent.flare_height,
+            };
+        }
+        if self.get_anomaly().is_some() {
+            return self.parachute;
+        }
+        if self.mode == ControlMode::HoldRate && self.target_rate < 0.0 {
+            return DescentModel::Steady {
+                rate: self.target_rate,
+            };
+        }
+        if self.ascent_rate < 0.0 {
+            return DescentModel::Steady {
+                rate: self.ascent_rate,
+            };
+        }
+        return self.parachute;
+    }
+
+    pub fn predict_landing(&self) -> Option<LandingPrediction> {
+        // where we'd land if we came down from here, None until the
+        // position is known
+        let (latitude, longitude) = self.position?;
+        return self.landing.predict(
+            latitude,
+            longitude,
+            self.altitude,
+            &self.landing_descent(),
+            &self.wind,
+            self.atmosphere.as_ref(),
+        );
+    }
+
+    pub fn set_position(&mut self, latitude: f32, longitude: f32) {
+        // where the balloon is, in degrees north and east, for finding the sun
+        self.position = Some((latitude, longitude));
+    }
+
+    pub fn get_remaining_budget(&self) -> RemainingBudget {
+        return RemainingBudget {
+            vent_open_time: self.vent_budget.remaining(),
+            ballast_mass: self.ballast_budget.remaining(),
+        };
+    }
+
     pub fn set_target(&mut self, target_altitude: f32) {
         // set new target altitude
         // Set a new target altitude to converge toward (in meters)
         if target_altitude > self.altitude_floor {
             // target must be above the minimum allowed altitude
             self.target_altitude = target_altitude;
+            if self.mode != ControlMode::HoldRate {
+                self.controller.set_target(target_altitude);
+            }
             info!("New target altitude: {:}m", self.target_altitude);
         } else {
             warn!(
@@ -168,6 +573,205 @@ impl ControlMngr {
         }
     }
 
+    pub fn get_target_rate(&self) -> Option<f32> {
+        // the ascent rate being held, if any
+        if matches!(self.mode, ControlMode::HoldRate | ControlMode::Descend) {
+            return Some(self.target_rate);
+        }
+        return None;
+    }
+
+    fn below_floor_descending(&self) -> bool {
+        // anything but Descend below the floor aborts and dumps all the
+        // ballast, so once down there keep descending
+        if self.mode == ControlMode::Descend && self.altitude <= self.altitude_floor {
+            warn!(
+                "Descending below the floor at {} m, not leaving Descend! Ignoring...",
+                self.altitude
+            );
+            return true;
+        }
+        return false;
+    }
+
+    pub fn hold_rate(&mut self, target_rate: f32) {
+        // hold an ascent rate rather than an altitude until told otherwise
+        if self.below_floor_descending() {
+            return;
+        }
+        match self.mode {
+            ControlMode::Ready => {
+                // start once we're above the floor
+                info!("Will hold {} m/s once above the floor", target_rate);
+                self.target_rate = target_rate;
+                self.rate_commanded = true;
+            }
+            ControlMode::Stabilize
+            | ControlMode::HoldRate
+            | ControlMode::Descend
+            | ControlMode::Economy => {
+                self.enter_hold_rate(target_rate, true);
+            }
+            _ => warn!(
+                "Not allowed to hold an ascent rate in {} mode! Ignoring...",
+                self.mode
+            ),
+        }
+    }
+
+    pub fn hold_altitude(&mut self) {
+        // go back to holding the target altitude
+        if self.below_floor_descending() {
+            return;
+        }
+        self.rate_commanded = false;
+        if matches!(
+            self.mode,
+            ControlMode::HoldRate | ControlMode::Descend | ControlMode::Economy
+        ) {
+            info!(
+                "Holding target altitude {} m --> Stabilize!",
+                self.target_altitude
+            );
+            self.enter_stabilize();
+        }
+    }
+
+    pub fn descend(&mut self) {
+        // come down and land
+        match self.mode {
+            ControlMode::Stabilize | ControlMode::HoldRate | ControlMode::Economy => {
+                info!(
+                    "Descending to land at {} m --> Descend!",
+                    self.descent.ground_altitude
+                );
+                self.mode = ControlMode::Descend;
+                self.status.set(ControlStatus::ACTIVE, true);
+                self.target_rate = self.descent.rate;
+                self.controller.set_target(self.target_rate);
+                // reset the integral to avoid accumulated error
+                self.controller.reset_integral();
+                self.still_since = None;
+            }
+            _ => warn!("Not allowed to descend in {} mode! Ignoring...", self.mode),
+        }
+    }
+
+    pub fn economize(&mut self) {
+        // let the altitude wander within the economy band to save ballast
+        if self.below_floor_descending() {
+            return;
+        }
+        match self.mode {
+            ControlMode::Stabilize | ControlMode::HoldRate | ControlMode::Descend => {
+                info!(
+                    "Floating between {} m and {} m --> Economy!",
+                    self.economy.low, self.economy.high
+                );
+                self.mode = ControlMode::Economy;
+                self.status.set(ControlStatus::ACTIVE, true);
+                self.rate_commanded = false;
+                self.target_rate = 0.0;
+                self.controller.set_target(self.target_rate);
+                // reset the integral to avoid accumulated error
+                self.controller.reset_integral();
+            }
+            _ => warn!(
+                "Not allowed to economize in {} mode! Ignoring...",
+                self.mode
+            ),
+        }
+    }
+
+    fn band_exit(&self, altitude: f32, ascent_rate: f32) -> Option<f32> {
+        // the edge of the economy band the balloon is headed out of, if it
+        // would be outside it by the end of the horizon
+        let predicted = altitude + ascent_rate * self.economy.horizon.as_secs_f32();
+        if predicted > self.economy.high {
+            return Some(self.economy.high);
+        }
+        if predicted < self.economy.low {
+            return Some(self.economy.low);
+        }
+        return None;
+    }
+
+    fn usable_ballast(&self, ballast_mass: f32) -> f32 {
+        // ballast that can go before we have to abort in kg
+        return (ballast_mass - self.min_ballast).max(0.0);
+    }
+
+    fn spare_gas(&self, ballast_mass: f32) -> f32 {
+        // lift gas that can be lost before even dumping all the usable
+        // ballast can't keep us up in kg
+        let free_lift = self.lift_gas.report().free_lift;
+        let spare_lift = (free_lift + self.usable_ballast(ballast_mass)).max(0.0);
+        return spare_lift / self.lift_gas.lift_per_kg();
+    }
+
+    fn record_consumption(&mut self, dt: Duration, altitude: f32) {
+        // only a float says anything about how long the float will last
+        if !matches!(self.mode, ControlMode::Stabilize | ControlMode::Economy) {
+            return;
+        }
+        // what the valves let out since last time
+        let dt_s = dt.as_secs_f32();
+        let ballast = self.dump_valve.get_flow() * dt_s;
+        let gas = self.vent_valve.get_flow_fraction() * self.lift_gas.vent_flow(altitude) * dt_s;
+        let sun_up = self.position.map(|(latitude, longitude)| {
+            sun_is_up(latitude, longitude, altitude, SystemTime::now())
+        });
+        self.history.record(ballast, gas, dt, sun_up);
+    }
+
+    fn landed(&mut self, height: f32, ascent_rate: &Measurement<f32>) -> bool {
+        // standing still on the ground for long enough, rather than
+        // hovering at neutral buoyancy on the way down
+        let still = height <= self.descent.touchdown_height
+            && ascent_rate.is_good()
+            && ascent_rate.value.abs() < self.descent.landed_rate;
+        if !still {
+            self.still_since = None;
+            return false;
+        }
+        let since = *self.still_since.get_or_insert_with(Instant::now);
+        return since.elapsed() >= self.descent.landed_time;
+    }
+
+    fn enter_stabilize(&mut self) {
+        self.mode = ControlMode::Stabilize;
+        self.status.set(ControlStatus::ACTIVE, true);
+        self.controller.set_target(self.target_altitude);
+        // reset the integral to avoid accumulated error
+        self.controller.reset_integral();
+    }
+
+    fn enter_hold_rate(&mut self, target_rate: f32, commanded: bool) {
+        info!("Holding ascent rate {} m/s --> HoldRate!", target_rate);
+        self.mode = ControlMode::HoldRate;
+        self.status.set(ControlStatus::ACTIVE, true);
+        self.target_rate = target_rate;
+        self.rate_commanded = commanded;
+        self.controller.set_target(target_rate);
+        // reset the integral to avoid accumulated error
+        self.controller.reset_integral();
+    }
+
+    fn follow_profile(&mut self, error: f32) -> bool {
+        // head for the target altitude at the profile's rate, if it has one
+        // for that direction
+        let rate = if error < 0.0 {
+            self.profile_ascent_rate
+        } else {
+            self.profile_descent_rate
+        };
+        if rate == 0.0 {
+            return false;
+        }
+        self.enter_hold_rate(rate, false);
+        return true;
+    }
+
     pub fn power_on_self_test(&mut self) {
         // turn on and test devices to look for errors
         info!("Starting Power-On Self Test...");
@@ -191,12 +795,210 @@ impl ControlMngr {
         } // otherwise carry on
     }
 
+    fn estimate_ballast(&mut self, load_cell: Measurement<f32>) -> Measurement<f32> {
+        // integrate what the dump valve let out since last time, then check
+        // it against the load cell
+        self.ballast
+            .predict(self.dump_valve.get_flow(), Instant::now());
+        self.ballast.update(&load_cell);
+        self.status
+            .set(ControlStatus::BALLAST_FAULT, self.ballast.is_faulted());
+        return self.ballast.estimate().unwrap_or(load_cell);
+    }
+
+    fn charge_budgets(&mut self, now: Instant) -> Duration {
+        // charge the budgets for what the valves let out since last time
+        let dt = self
+            .last_update
+            .map_or(Duration::ZERO, |time| now.saturating_duration_since(time));
+        self.last_update = Some(now);
+        self.vent_budget
+            .consume(self.vent_valve.get_flow_fraction() * dt.as_secs_f32(), now);
+        self.ballast_budget
+            .consume(self.dump_valve.get_flow() * dt.as_secs_f32(), now);
+        return dt;
+    }
+
+    fn actuate(&mut self, vent_pwm: f32, dump_pwm: f32, dt: Duration) {
+        // set the valves, holding them back if they would open faster than
+        // the slew limit or their budget is used up
+        let rate = self.pwm_slew_rate;
+        let mut vent_allowed = slew_limit(self.vent_valve.get_pwm(), vent_pwm, rate, dt);
+        let mut dump_allowed = slew_limit(self.dump_valve.get_pwm(), dump_pwm, rate, dt);
+        let mut limited = false;
+        if vent_allowed > 0.0 && !self.vent_budget.allow() {
+            vent_allowed = 0.0;
+            limited = true;
+        }
+        if dump_allowed > 0.0 && !self.ballast_budget.allow() {
+            dump_allowed = 0.0;
+            limited = true;
+        }
+        self.status.set(ControlStatus::BUDGET_LIMITED, limited);
+        self.vent_valve.set_pwm(vent_allowed);
+        self.dump_valve.set_pwm(dump_allowed);
+    }
+
+    fn check_for_problems(
+        &mut self,
+        altitude: &Measurement<f32>,
+        ascent_rate: &Measurement<f32>,
+        ballast_mass: &Measurement<f32>,
+    ) -> bool {
+        // abort if there's no ballast left, doesn't matter if tlm is stale
+        if ballast_mass.quality != Quality::Bad {
+            self.abort_if_out_of_ballast(ballast_mass.value);
+        }
+        self.flag_bad_telemetry(&[altitude, ascent_rate, ballast_mass]);
+        return self.status.intersects(ControlStatus::PROBLEM);
+    }
+
+    fn flag_unusable_telemetry(
+        &mut self,
+        altitude: &Measurement<f32>,
+        ascent_rate: &Measurement<f32>,
+    ) {
+        // don't act on telemetry that is too old or failed validation
+        if is_stale(altitude, self.tlm_max_age) | is_stale(ascent_rate, self.tlm_max_age) {
+            // altitude telemetry is stale
+            warn!(
+                "Altitude telemetry is stale! ({:#?} s old)",
+                &altitude.timestamp.elapsed()
+            );
+            self.status.set(ControlStatus::STALE_TELEMETRY, true)
+        } else if !altitude.is_good() | !ascent_rate.is_good() {
+            warn!(
+                "Altitude telemetry is {:?}, ascent rate is {:?}",
+                altitude.quality, ascent_rate.quality
+            );
+            self.status.set(ControlStatus::STALE_TELEMETRY, true)
+        } else {
+            self.status.set(ControlStatus::STALE_TELEMETRY, false)
+        }
+    }
+
+    fn plan_for_the_sun(&mut self, altitude: f32) {
+        // work out what to spend now so the lift lost at sunset or gained at
+        // sunrise doesn't catch the controller out
+        let (latitude, longitude) = match self.position {
+            Some(v) => v,
+            None => return, // don't know where we are yet
+        };
+        let now = SystemTime::now();
+        self.sun_elevation = Some(sun_position(latitude, longitude, now).elevation);
+        let feed_forward = self.solar.feed_forward(latitude, longitude, altitude, now);
+        if feed_forward != self.feed_forward {
+            info!(
+                "Solar feed-forward: vent {} open, dump {} kg/s",
+                feed_forward.vent, feed_forward.dump
+            );
+        }
+        self.feed_forward = feed_forward;
+    }
+
+    fn drive_valves(&mut self, control_effort: f32, control_ok: bool, too_high: f32, dt: Duration) {
+        // vent if `too_high` is positive, dump if negative, otherwise (or if
+        // in a dead zone or telemetry is stale) close both valves
+        self.status
+            .set(ControlStatus::VENT, control_ok & (too_high > 0.0));
+        self.status
+            .set(ControlStatus::DUMP, control_ok & (too_high < 0.0));
+        let vent_effort = if self.status.intersects(ControlStatus::VENT) {
+            control_effort.min(0.0)
+        } else {
+            0.0
+        };
+        let dump_effort = if self.status.intersects(ControlStatus::DUMP) {
+            control_effort.max(0.0)
+        } else {
+            0.0
+        };
+        // get ahead of sunset and sunrise while holding an altitude or rate,
+        // dead zones or not, as long as the telemetry is usable
+        let mut solar = if matches!(self.mode, ControlMode::Stabilize | ControlMode::HoldRate)
+            && !self.status.intersects(ControlStatus::STALE_TELEMETRY)
+        {
+            self.feed_forward
+        } else {
+            FeedForward::default()
+        };
+        // but never against the controller, dumping for sunset while it vents
+        // or venting for sunrise while it dumps
+        if self.status.intersects(ControlStatus::VENT) {
+            solar.dump = 0.0;
+        }
+        if self.status.intersects(ControlStatus::DUMP) {
+            solar.vent = 0.0;
+        }
+        self.status.set(
+            ControlStatus::SOLAR_FEED_FORWARD,
+            solar != FeedForward::default(),
+        );
+        let vent_pwm = self.vent_valve.ctrl2pwm(vent_effort - solar.vent);
+        let dump_pwm = self
+            .dump_valve
+            .ctrl2pwm(dump_effort + solar.dump / self.dump_valve.get_max_flow());
+        // actuate the valves within their budgets
+        self.actuate(vent_pwm, dump_pwm, dt);
+    }
+
+    fn watch_for_burst(&mut self, ascent_rate: &Measurement<f32>) {
+        // there's no fighting a burst or a leak with ballast, so stop trying
+        let commanded_descent = match self.mode {
+            ControlMode::Descend => true,
+            ControlMode::HoldRate | ControlMode::Economy => self.target_rate < 0.0,
+            _ => false,
+        };
+        let free_lift = self.lift_gas.report().free_lift;
+        if let Some(anomaly) = self.burst.update(ascent_rate, free_lift, commanded_descent) {
+            self.status.set(ControlStatus::BURST, true);
+            if !matches!(self.mode, ControlMode::Init | ControlMode::Safe) {
+                warn!("{} while in {} mode --> Safe!", anomaly, self.mode);
+                self.mode = ControlMode::Safe;
+            }
+        }
+    }
+
+    fn flag_bad_telemetry(&mut self, telemetry: &[&Measurement<f32>]) {
+        // telemetry that has failed validation for too long is a problem
+        if telemetry.iter().all(|tlm| tlm.quality != Quality::Bad) {
+            self.bad_tlm_since = None;
+            return;
+        }
+        let since = *self.bad_tlm_since.get_or_insert_with(Instant::now);
+        if since.elapsed() > self.bad_tlm_timeout {
+            warn!(
+                "Telemetry has been bad for {:#?} --> Problem!",
+                since.elapsed()
+            );
+            self.status.set(ControlStatus::PROBLEM, true);
+        }
+    }
+
     pub fn update(
         &mut self,
         altitude: Measurement<f32>,     // instantaneous altitude in meters
         ascent_rate: Measurement<f32>,  // instantaneous ascent rate in m/s
         ballast_mass: Measurement<f32>, // ballast mass remining in kg
     ) -> ControlCommand {
+        let now = Instant::now();
+        let dt = self.charge_budgets(now);
+        // trust the ballast estimate over the raw load cell once there is one
+        let ballast_mass = self.estimate_ballast(ballast_mass);
+        // count what the vent let out since last time
+        self.lift_gas.update(
+            self.vent_valve.get_flow_fraction(),
+            altitude.value,
+            ballast_mass.value,
+            now,
+        );
+        self.watch_for_burst(&ascent_rate);
+        self.plan_for_the_sun(altitude.value);
+        self.record_consumption(dt, altitude.value);
+        self.ballast_mass = ballast_mass.value;
+        self.altitude = altitude.value;
+        self.ascent_rate = ascent_rate.value;
+
         // calculate altitude difference from the target aka altitude error
         let error = altitude.value - self.target_altitude;
 
@@ -216,67 +1018,59 @@ impl ControlMngr {
                     error.abs()
                 );
                 // lets do this!
-                if !(altitude.value <= self.altitude_floor) && error.abs() <= self.error_ready {
-                    info!(
-                        "{} m is close enough to target {} m --> Stabilize!",
-                        altitude.value, self.target_altitude
-                    );
-                    self.mode = ControlMode::Stabilize;
-                    self.status.set(ControlStatus::ACTIVE, true);
-                    // reset the integral to avoid accumulated error
-                    self.controller.reset_integral();
+                #[allow(clippy::neg_cmp_op_on_partial_ord)]
+                if altitude.is_good() && !(altitude.value <= self.altitude_floor) {
+                    if self.rate_commanded {
+                        // asked to hold a rate before we got here
+                        self.enter_hold_rate(self.target_rate, true);
+                    } else if error.abs() <= self.error_ready {
+                        info!(
+                            "{} m is close enough to target {} m --> Stabilize!",
+                            altitude.value, self.target_altitude
+                        );
+                        self.enter_stabilize();
+                    } else {
+                        // head for the target at the profile's rate
+                        self.follow_profile(error);
+                    }
                 }
             }
             ControlMode::Stabilize => {
-                // abort if there's no ballast left, doesn't matter if tlm is stale
-                self.abort_if_out_of_ballast(ballast_mass.value);
-                // abort if there's a problem
-                if self.status.intersects(ControlStatus::PROBLEM) {
+                if self.check_for_problems(&altitude, &ascent_rate, &ballast_mass) {
                     self.mode = ControlMode::Abort;
-                    // run mission abort procedure immediately
-                    return self.update(altitude, ascent_rate, ballast_mass);
+                    // run mission abort procedure immediately, without
+                    // estimating everything again for the same cycle
+                    self.run_abort(ballast_mass.value);
+                    return self.command();
+                }
+                // well away from the target, get there at the profile's rate
+                if error.abs() > self.profile_reentry_error && self.follow_profile(error) {
+                    return self.command();
                 }
+                // look up the gains for the air we're in
+                let density = self.atmosphere.density(altitude.value);
+                self.vent_valve.schedule_gains(altitude.value, density);
+                self.dump_valve.schedule_gains(altitude.value, density);
                 // switch gains depending on ascent rate
                 if ascent_rate.value > 0.0 {
                     // if rising, vent
-                    self.controller.set_gains(
-                        self.vent_valve.kp,
-                        self.vent_valve.ki,
-                        self.vent_valve.kd,
-                    );
+                    self.gains = self.vent_valve.get_gains();
                     self.status.set(ControlStatus::VENT, true);
                     self.status.set(ControlStatus::DUMP, false);
                 } else {
                     // otherwise, dump
-                    self.controller.set_gains(
-                        self.dump_valve.kp,
-                        self.dump_valve.ki,
-                        self.dump_valve.kd,
-                    );
+                    self.gains = self.dump_valve.get_gains();
                     self.status.set(ControlStatus::VENT, false);
                     self.status.set(ControlStatus::DUMP, true);
                 };
+                self.controller
+                    .set_gains(self.gains.kp, self.gains.ki, self.gains.kd);
                 // always update the controller even if no action is taken
                 let control_effort = self.controller.update_control(altitude.value);
                 // decide what to do in order to converge toward the target
                 if altitude.value > self.altitude_floor {
-                    // determine what the PWM should be for each valve
-                    let vent_pwm = self.vent_valve.ctrl2pwm(control_effort);
-                    let dump_pwm = self.dump_valve.ctrl2pwm(control_effort);
-
                     // configure registers for reasons to not actuate
-                    if is_stale(&altitude, self.tlm_max_age)
-                        | is_stale(&ascent_rate, self.tlm_max_age)
-                    {
-                        // altitude telemetry is stale
-                        warn!(
-                            "Altitude telemetry is stale! ({:#?} s old)",
-                            &altitude.timestamp.elapsed()
-                        );
-                        self.status.set(ControlStatus::STALE_TELEMETRY, true)
-                    } else {
-                        self.status.set(ControlStatus::STALE_TELEMETRY, false)
-                    }
+                    self.flag_unusable_telemetry(&altitude, &ascent_rate);
 
                     if error.abs() < self.error_deadzone {
                         // altitude error is within the deadzone
@@ -309,34 +1103,8 @@ impl ControlMngr {
                     let control_ok = telem_ok
                         & !(self.status.intersects(ControlStatus::ALTITUDE_DEADZONE)
                             & self.status.intersects(ControlStatus::SPEED_DEADZONE));
-
-                    if control_ok & (ascent_rate.value > 0.0) {
-                        // lower altitude for error to converge to zero
-                        // close the dump valve, set the vent PWM
-                        self.status.set(ControlStatus::VENT, true);
-                        self.status.set(ControlStatus::DUMP, false);
-                    } else if control_ok & (ascent_rate.value < 0.0) {
-                        // raise altitude for error to converge to zero
-                        // close the vent valve, set the dump PWM
-                        self.status.set(ControlStatus::VENT, false);
-                        self.status.set(ControlStatus::DUMP, true);
-                    } else {
-                        // if in dead zone or telemetry is stale, do nothing
-                        // close the vent valve and close the dump valve
-                        self.status.set(ControlStatus::VENT, false);
-                        self.status.set(ControlStatus::DUMP, false);
-                    };
-                    // actuate the valves
-                    if self.status.intersects(ControlStatus::VENT) {
-                        self.vent_valve.set_pwm(vent_pwm)
-                    } else {
-                        self.vent_valve.set_pwm(0.0)
-                    }
-                    if self.status.intersects(ControlStatus::DUMP) {
-                        self.dump_valve.set_pwm(dump_pwm)
-                    } else {
-                        self.dump_valve.set_pwm(0.0)
-                    }
+                    // lower altitude if rising, raise it if falling
+                    self.drive_valves(control_effort, control_ok, ascent_rate.value, dt);
                     info!("{}:[{:#?}]", self.mode, self.status,);
                 } else {
                     // abort if altitude is lower than the lowest allowed value
@@ -347,33 +1115,210 @@ impl ControlMngr {
                     self.mode = ControlMode::Abort;
                 }
             }
-            ControlMode::Safe => {
-                // close the valves and sit tight
-                // close the vent valve
-                self.vent_valve.set_pwm(0.0);
-                // close the dump valve
-                self.dump_valve.set_pwm(0.0);
-                self.status.set(ControlStatus::ACTIVE, false);
-                self.status.set(ControlStatus::VENT, false);
-                self.status.set(ControlStatus::DUMP, false);
+            ControlMode::HoldRate => {
+                if self.check_for_problems(&altitude, &ascent_rate, &ballast_mass) {
+                    self.mode = ControlMode::Abort;
+                    // run mission abort procedure immediately, without
+                    // estimating everything again for the same cycle
+                    self.run_abort(ballast_mass.value);
+                    return self.command();
+                }
+                // a profile climb or descent ends near the target altitude
+                if !self.rate_commanded && error.abs() <= self.error_ready {
+                    info!(
+                        "{} m is close enough to target {} m --> Stabilize!",
+                        altitude.value, self.target_altitude
+                    );
+                    self.enter_stabilize();
+                    return self.command();
+                }
+                self.gains = self.rate_gains;
+                self.controller
+                    .set_gains(self.gains.kp, self.gains.ki, self.gains.kd);
+                // always update the controller even if no action is taken
+                let control_effort = self.controller.update_control(ascent_rate.value);
+                let rate_error = ascent_rate.value - self.target_rate;
+                if altitude.value > self.altitude_floor {
+                    self.flag_unusable_telemetry(&altitude, &ascent_rate);
+                    self.status.set(ControlStatus::ALTITUDE_DEADZONE, false);
+                    if rate_error.abs() < self.speed_deadzone {
+                        debug!(
+                            "Ascent rate error is {}, which is within the deadzone of {}",
+                            rate_error.abs(),
+                            self.speed_deadzone
+                        );
+                        self.status.set(ControlStatus::SPEED_DEADZONE, true)
+                    } else {
+                        self.status.set(ControlStatus::SPEED_DEADZONE, false)
+                    }
+                    let control_ok = !self
+                        .status
+                        .intersects(ControlStatus::STALE_TELEMETRY | ControlStatus::SPEED_DEADZONE);
+                    // slow down if going up too fast, speed up if too slow
+                    self.drive_valves(control_effort, control_ok, rate_error, dt);
+                    info!(
+                        "{}:[{:#?}] Ascent rate {} m/s, target {} m/s",
+                        self.mode, self.status, ascent_rate.value, self.target_rate
+                    );
+                } else {
+                    // abort if altitude is lower than the lowest allowed value
+                    warn!(
+                        "{} m lower than minimum {} m --> Abort!",
+                        altitude.value, self.altitude_floor
+                    );
+                    self.mode = ControlMode::Abort;
+                }
             }
-            ControlMode::Abort => {
-                self.status.set(ControlStatus::PROBLEM, true);
-                // keep the balloon valve closed and dump all ballast
-                if ballast_mass.value <= 0.0 {
-                    warn!("Out of ballast mass!");
+            ControlMode::Descend => {
+                if self.check_for_problems(&altitude, &ascent_rate, &ballast_mass) {
+                    self.mode = ControlMode::Abort;
+                    // run mission abort procedure immediately, without
+                    // estimating everything again for the same cycle
+                    self.run_abort(ballast_mass.value);
+                    return self.command();
+                }
+                let height = altitude.value - self.descent.ground_altitude;
+                if self.landed(height, &ascent_rate) {
+                    info!("Landed at {} m --> Safe!", altitude.value);
                     self.mode = ControlMode::Safe;
+                    self.run_safe();
+                    return self.command();
+                }
+                // vent down at the descent rate, then slow down for landing
+                let flaring = height <= self.descent.flare_height;
+                let target_rate = if flaring {
+                    self.descent.landing_rate
                 } else {
-                    // close the vent valve
-                    self.vent_valve.set_pwm(0.0);
-                    // open the dump valve
-                    self.dump_valve.set_pwm(1.0);
-                    self.status.set(ControlStatus::VENT, false);
-                    self.status.set(ControlStatus::DUMP, true);
+                    self.descent.rate
+                };
+                if target_rate != self.target_rate {
+                    info!("Descending at {} m/s", target_rate);
+                    self.target_rate = target_rate;
+                    self.controller.set_target(target_rate);
+                }
+                self.gains = self.rate_gains;
+                self.controller
+                    .set_gains(self.gains.kp, self.gains.ki, self.gains.kd);
+                // always update the controller even if no action is taken
+                let control_effort = self.controller.update_control(ascent_rate.value);
+                let rate_error = ascent_rate.value - self.target_rate;
+                self.flag_unusable_telemetry(&altitude, &ascent_rate);
+                self.status.set(ControlStatus::ALTITUDE_DEADZONE, false);
+                self.status.set(
+                    ControlStatus::SPEED_DEADZONE,
+                    rate_error.abs() < self.speed_deadzone,
+                );
+                let control_ok = !self
+                    .status
+                    .intersects(ControlStatus::STALE_TELEMETRY | ControlStatus::SPEED_DEADZONE);
+                // save the ballast for landing, and don't vent while landing
+                let too_high = if flaring {
+                    rate_error.min(0.0)
+                } else if ballast_mass.value <= self.descent.landing_ballast {
+                    rate_error.max(0.0)
+                } else {
+                    rate_error
+                };
+                self.drive_valves(control_effort, control_ok, too_high, dt);
+                info!(
+                    "{}:[{:#?}] {} m above ground, descending at {} m/s, target {} m/s",
+                    self.mode, self.status, height, -ascent_rate.value, -self.target_rate
+                );
+            }
+            ControlMode::Economy => {
+                if self.check_for_problems(&altitude, &ascent_rate, &ballast_mass) {
+                    self.mode = ControlMode::Abort;
+                    // run mission abort procedure immediately, without
+                    // estimating everything again for the same cycle
+                    self.run_abort(ballast_mass.value);
+                    return self.command();
+                }
+                if altitude.value <= self.altitude_floor {
+                    // abort if altitude is lower than the lowest allowed value
+                    warn!(
+                        "{} m lower than minimum {} m --> Abort!",
+                        altitude.value, self.altitude_floor
+                    );
+                    self.mode = ControlMode::Abort;
+                    return self.command();
+                }
+                self.flag_unusable_telemetry(&altitude, &ascent_rate);
+                self.status.set(ControlStatus::SPEED_DEADZONE, false);
+                self.gains = self.rate_gains;
+                self.controller
+                    .set_gains(self.gains.kp, self.gains.ki, self.gains.kd);
+                match self.band_exit(altitude.value, ascent_rate.value) {
+                    Some(edge) => {
+                        // slow down enough to reach the edge no sooner than
+                        // the horizon
+                        let horizon = self.economy.horizon.as_secs_f32();
+                        self.target_rate = (edge - altitude.value) / horizon;
+                        self.controller.set_target(self.target_rate);
+                        let control_effort = self.controller.update_control(ascent_rate.value);
+                        let rate_error = ascent_rate.value - self.target_rate;
+                        self.status.set(ControlStatus::ALTITUDE_DEADZONE, false);
+                        let control_ok = !self.status.intersects(ControlStatus::STALE_TELEMETRY);
+                        self.drive_valves(control_effort, control_ok, rate_error, dt);
+                        info!(
+                            "{}:[{:#?}] Headed out of the band past {} m, slowing to {} m/s",
+                            self.mode, self.status, edge, self.target_rate
+                        );
+                    }
+                    None => {
+                        // staying in the band, leave it be
+                        self.target_rate = 0.0;
+                        self.controller.set_target(self.target_rate);
+                        self.controller.reset_integral();
+                        self.status.set(ControlStatus::ALTITUDE_DEADZONE, true);
+                        self.drive_valves(0.0, false, 0.0, dt);
+                        debug!(
+                            "{}:[{:#?}] {} m is within the band",
+                            self.mode, self.status, altitude.value
+                        );
+                    }
                 }
             }
+            ControlMode::Safe => self.run_safe(),
+            ControlMode::Abort => self.run_abort(ballast_mass.value),
+        }
+
+        return self.command();
+    }
+
+    fn run_safe(&mut self) {
+        // close the valves and sit tight
+        // close the vent valve
+        self.vent_valve.set_pwm(0.0);
+        // close the dump valve
+        self.dump_valve.set_pwm(0.0);
+        self.status.set(ControlStatus::ACTIVE, false);
+        self.status.set(ControlStatus::VENT, false);
+        self.status.set(ControlStatus::DUMP, false);
+        self.status.set(ControlStatus::BUDGET_LIMITED, false);
+        self.status.set(ControlStatus::SOLAR_FEED_FORWARD, false);
+    }
+
+    fn run_abort(&mut self, ballast_mass: f32) {
+        // budgets don't apply, getting rid of ballast is the point
+        self.status.set(ControlStatus::PROBLEM, true);
+        self.status.set(ControlStatus::BUDGET_LIMITED, false);
+        self.status.set(ControlStatus::SOLAR_FEED_FORWARD, false);
+        // keep the balloon valve closed and dump all ballast
+        if ballast_mass <= 0.0 {
+            warn!("Out of ballast mass!");
+            self.mode = ControlMode::Safe;
+        } else {
+            // close the vent valve
+            self.vent_valve.set_pwm(0.0);
+            // open the dump valve
+            self.dump_valve.set_pwm(1.0);
+            self.status.set(ControlStatus::VENT, false);
+            self.status.set(ControlStatus::DUMP, true);
         }
+    }
 
+    fn command(&self) -> ControlCommand {
+        // what the valves are set to
         return ControlCommand {
             vent_pwm: self.vent_valve.get_pwm(),
             dump_pwm: self.dump_valve.get_pwm(),
@@ -384,3 +1329,56 @@ impl ControlMngr {
 fn is_stale(telemetry: &Measurement<f32>, max_age: Duration) -> bool {
     return telemetry.timestamp.elapsed() > max_age;
 }
+
+fn column(config: &Value, key: &str) -> Vec<f32> {
+    // read an array of floats from the config
+    return config[key]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v.as_float().unwrap() as f32)
+        .collect();
+}
+
+fn calibration(config: &Value, valve: &str) -> Calibration {
+    // read a valve's PWM to mass flow table from the config
+    let pwm = column(config, &format!("{}_cal_pwm", valve));
+    let flow = column(config, &format!("{}_cal_flow_kg_s", valve));
+    return Calibration::new(pwm, flow)
+        .unwrap_or_else(|e| panic!("Invalid {} valve calibration: {}", valve, e));
+}
+
+fn gain_schedule(config: &Value, valve: &str) -> GainSchedule {
+    // read a valve's controller gains by altitude or density from the config
+    let gain = |name: &str| config[format!("{}_{}", valve, name).as_str()].as_float();
+    let schedule = || -> Result<GainSchedule, String> {
+        // single gains hold everywhere, without any breakpoints
+        match (gain("kp"), gain("ki"), gain("kd")) {
+            (Some(kp), Some(ki), Some(kd)) => {
+                return Ok(GainSchedule::fixed(Gains {
+                    kp: kp as f32,
+                    ki: ki as f32,
+                    kd: kd as f32,
+                }));
+            }
+            (None, None, None) => (),
+            _ => {
+                return Err(String::from(
+                    "kp/ki/kd must all be single gains or all be arrays",
+                ))
+            }
+        }
+        let key = config[format!("{}_gain_schedule_by", valve).as_str()]
+            .as_str()
+            .unwrap()
+            .parse()?;
+        return GainSchedule::new(
+            key,
+            column(config, &format!("{}_gain_schedule_at", valve)),
+            column(config, &format!("{}_kp", valve)),
+            column(config, &format!("{}_ki", valve)),
+            column(config, &format!("{}_kd", valve)),
+        );
+    };
+    return schedule().unwrap_or_else(|e| panic!("Invalid {} gain schedule: {}", valve, e));
+}
@@ -5,6 +5,8 @@
 // and the commands that regulate control reaction mass.
 // ----------------------------------------------------------------------------
 
+use std::str::FromStr;
+
 use log::{debug, warn};
 
 use pid::Pid;
@@ -51,32 +53,281 @@ impl Controller {
     }
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct Calibration {
+    // Measured mass flow through a valve at a set of PWM settings, covering
+    // dead bands, minimum opening PWM and nonlinear flow
+    pwm: Vec<f32>,  // PWM settings [0, 1], strictly increasing
+    flow: Vec<f32>, // mass flow at each setting in kg/s, never decreasing
+}
+
+impl Calibration {
+    pub fn new(pwm: Vec<f32>, flow: Vec<f32>) -> Result<Self, String> {
+        // check the table can be interpolated and inverted
+        if pwm.len() != flow.len() {
+            return Err(format!(
+                "{} PWM settings but {} flow rates",
+                pwm.len(),
+                flow.len()
+            ));
+        }
+        if pwm.len() < 2 {
+            return Err(String::from("need at least two points"));
+        }
+        if pwm[0] != 0.0 || flow[0] != 0.0 {
+            return Err(String::from("must start with no flow at PWM 0"));
+        }
+        if pwm.iter().chain(flow.iter()).any(|v| !v.is_finite()) {
+            return Err(String::from("values must be finite"));
+        }
+        if pwm.iter().any(|p| !(0.0..=1.0).contains(p)) {
+            return Err(String::from("PWM settings must be within [0, 1]"));
+        }
+        if pwm.windows(2).any(|w| w[1] <= w[0]) {
+            return Err(String::from("PWM settings must be strictly increasing"));
+        }
+        if flow.windows(2).any(|w| w[1] < w[0]) {
+            return Err(String::from("flow must not decrease as PWM increases"));
+        }
+        if flow[flow.len() - 1] <= 0.0 {
+            return Err(String::from("valve never flows"));
+        }
+        return Ok(Calibration { pwm, flow });
+    }
+
+    pub fn linear(max_flow: f32) -> Self {
+        // flow proportional to PWM
+        return Calibration {
+            pwm: vec![0.0, 1.0],
+            flow: vec![0.0, max_flow],
+        };
+    }
+
+    pub fn max_flow(&self) -> f32 {
+        return self.flow[self.flow.len() - 1];
+    }
+
+    pub fn flow_at(&self, pwm: f32) -> f32 {
+        // mass flow at a PWM setting, interpolated between points
+        let pwm = clamp(pwm, 0.0, 1.0);
+        let last = self.pwm.len() - 1;
+        if pwm >= self.pwm[last] {
+            return self.flow[last];
+        }
+        let i = self.pwm.iter().rposition(|p| *p <= pwm).unwrap_or(0);
+        let fraction = (pwm - self.pwm[i]) / (self.pwm[i + 1] - self.pwm[i]);
+        return self.flow[i] + fraction * (self.flow[i + 1] - self.flow[i]);
+    }
+
+    pub fn pwm_for(&self, flow: f32) -> f32 {
+        // lowest PWM setting that gives a mass flow, skipping dead bands
+        if flow <= 0.0 {
+            return 0.0;
+        }
+        let i = match self.flow.iter().position(|f| *f >= flow) {
+            Some(v) => v,
+            None => return self.pwm[self.pwm.len() - 1], // as open as it goes
+        };
+        // flow[i - 1] < flow <= flow[i], and flow[0] is 0 so i > 0
+        let fraction = (flow - self.flow[i - 1]) / (self.flow[i] - self.flow[i - 1]);
+        return self.pwm[i - 1] + fraction * (self.pwm[i] - self.pwm[i - 1]);
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Gains {
+    pub kp: f32, // proportional gain
+    pub ki: f32, // integral gain
+    pub kd: f32, // derivative gain
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ScheduleKey {
+    // What a gain schedule is looked up by
+    Altitude, // altitude in m
+    Density,  // air density in kg/m^3
+}
+
+impl FromStr for ScheduleKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "altitude" => Ok(ScheduleKey::Altitude),
+            "density" => Ok(ScheduleKey::Density),
+            other => Err(format!(
+                "can't schedule by '{}', expected 'altitude' or 'density'",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GainSchedule {
+    // Controller gains tuned at a set of altitudes or densities, since the
+    // balloon responds very differently to venting and dumping in thin air
+    key: ScheduleKey,
+    at: Vec<f32>,      // breakpoints, strictly increasing
+    gains: Vec<Gains>, // gains at each breakpoint
+}
+
+impl GainSchedule {
+    pub fn new(
+        key: ScheduleKey,
+        at: Vec<f32>,
+        kp: Vec<f32>,
+        ki: Vec<f32>,
+        kd: Vec<f32>,
+    ) -> Result<Self, String> {
+        // check there is one set of gains per breakpoint
+        if at.is_empty() {
+            return Err(String::from("need at least one breakpoint"));
+        }
+        if [&kp, &ki, &kd]
+            .iter()
+            .any(|column| column.len() != at.len())
+        {
+            return Err(format!(
+                "{} breakpoints but {}/{}/{} kp/ki/kd gains",
+                at.len(),
+                kp.len(),
+                ki.len(),
+                kd.len()
+            ));
+        }
+        let values = at.iter().chain(&kp).chain(&ki).chain(&kd);
+        if values.clone().any(|v| !v.is_finite()) {
+            return Err(String::from("values must be finite"));
+        }
+        if kp.iter().chain(&ki).chain(&kd).any(|v| *v < 0.0) {
+            return Err(String::from("gains must not be negative"));
+        }
+        if at.windows(2).any(|w| w[1] <= w[0]) {
+            return Err(String::from("breakpoints must be strictly increasing"));
+        }
+        let gains = (0..at.len())
+            .map(|i| Gains {
+                kp: kp[i],
+                ki: ki[i],
+                kd: kd[i],
+            })
+            .collect();
+        return Ok(GainSchedule { key, at, gains });
+    }
+
+    pub fn fixed(gains: Gains) -> Self {
+        // the same gains everywhere
+        return GainSchedule {
+            key: ScheduleKey::Altitude,
+            at: vec![0.0],
+            gains: vec![gains],
+        };
+    }
+
+    pub fn key(&self) -> ScheduleKey {
+        return self.key;
+    }
+
+    pub fn gains_at(&self, altitude: f32, density: f32) -> Gains {
+        // gains interpolated between breakpoints, held at the ends
+        let x = match self.key {
+            ScheduleKey::Altitude => altitude,
+            ScheduleKey::Density => density,
+        };
+        let last = self.at.len() - 1;
+        if x <= self.at[0] {
+            return self.gains[0];
+        }
+        if x >= self.at[last] {
+            return self.gains[last];
+        }
+        let i = self.at.iter().rposition(|a| *a <= x).unwrap_or(0);
+        let fraction = (x - self.at[i]) / (self.at[i + 1] - self.at[i]);
+        let lerp = |low: f32, high: f32| low + fraction * (high - low);
+        let (low, high) = (self.gains[i], self.gains[i + 1]);
+        return Gains {
+            kp: lerp(low.kp, high.kp),
+            ki: lerp(low.ki, high.ki),
+            kd: lerp(low.kd, high.kd),
+        };
+    }
+}
+
 pub struct Valve {
     // Altitude control mass flow control valve
-    pwm: f32,      // instantaneous PWM setting [0, 1]
-    min_ctrl: f32, // control effort upper limit
-    max_ctrl: f32, // control effort upper limit
-    pub kp: f32,   // valve controller proportional gain
-    pub ki: f32,   // valve controller integral gain
-    pub kd: f32,   // valve controller derivatitve gain
-    pub clamped: bool, // whether the valve is at its min or max value
-    pub name: String,    // label for the valve
+    pwm: f32,                 // instantaneous PWM setting [0, 1]
+    min_ctrl: f32,            // control effort upper limit
+    max_ctrl: f32,            // control effort upper limit
+    pub kp: f32,              // valve controller proportional gain
+    pub ki: f32,              // valve controller integral gain
+    pub kd: f32,              // valve controller derivatitve gain
+    pub clamped: bool,        // whether the valve is at its min or max value
+    pub name: String,         // label for the valve
+    calibration: Calibration, // PWM to mass flow
+    schedule: GainSchedule,   // gains by altitude or density
 }
 
 impl Valve {
     pub fn new(min_ctrl: f32, max_ctrl: f32, kp: f32, ki: f32, kd: f32, name: String) -> Self {
         Valve {
-            pwm: 0.0, // PWM setting for open/close duty cycle
-            min_ctrl, // control effort upper limit
-            max_ctrl, // control effort upper limit
-            kp,       // valve controller proportional gain
-            ki,       // valve controller integral gain
-            kd,       // valve controller derivatitve gain
+            pwm: 0.0,       // PWM setting for open/close duty cycle
+            min_ctrl,       // control effort upper limit
+            max_ctrl,       // control effort upper limit
+            kp,             // valve controller proportional gain
+            ki,             // valve controller integral gain
+            kd,             // valve controller derivatitve gain
             clamped: false, // whether the valve is at its min or max value
-            name,     // label for this valve
+            name,           // label for this valve
+            // until told otherwise
+            calibration: Calibration::linear(1.0),
+            schedule: GainSchedule::fixed(Gains { kp, ki, kd }),
         }
     }
 
+    pub fn set_schedule(&mut self, schedule: GainSchedule) {
+        // look gains up from a schedule rather than keeping them fixed
+        self.schedule = schedule;
+    }
+
+    pub fn schedule_gains(&mut self, altitude: f32, density: f32) -> Gains {
+        // switch to the scheduled gains for where the balloon is
+        let gains = self.schedule.gains_at(altitude, density);
+        self.kp = gains.kp;
+        self.ki = gains.ki;
+        self.kd = gains.kd;
+        return gains;
+    }
+
+    pub fn get_gains(&self) -> Gains {
+        return Gains {
+            kp: self.kp,
+            ki: self.ki,
+            kd: self.kd,
+        };
+    }
+
+    pub fn set_calibration(&mut self, calibration: Calibration) {
+        // use a measured PWM to mass flow table
+        self.calibration = calibration;
+    }
+
+    pub fn get_flow(&self) -> f32 {
+        // mass flow at the current PWM setting in kg/s
+        return self.calibration.flow_at(self.pwm);
+    }
+
+    pub fn get_max_flow(&self) -> f32 {
+        // mass flow when fully open in kg/s
+        return self.calibration.max_flow();
+    }
+
+    pub fn get_flow_fraction(&self) -> f32 {
+        // mass flow at the current PWM setting relative to fully open
+        return self.get_flow() / self.calibration.max_flow();
+    }
+
+    #[allow(clippy::manual_range_contains)]
     pub fn set_pwm(&mut self, pwm_value: f32) {
         // set valve open/close PWM
         if pwm_value >= 0.0 && pwm_value <= 1.0 {
@@ -97,11 +348,17 @@ impl Valve {
     }
 
     pub fn ctrl2pwm(&mut self, control_effort: f32) -> f32 {
-        // translate control effort to PWM
-        let new_pwm = clamp(control_effort, self.min_ctrl, self.max_ctrl);
-        debug!("[{}] PID effort: {:} | PWM {:}", self.name, control_effort, new_pwm.abs());
-        self.clamped = (new_pwm == self.min_ctrl) | (new_pwm == self.max_ctrl);
-        return new_pwm.abs();
+        // translate control effort to a mass flow as a fraction of fully open,
+        // then look up the PWM that gives it
+        let effort = clamp(control_effort, self.min_ctrl, self.max_ctrl);
+        self.clamped = (effort == self.min_ctrl) | (effort == self.max_ctrl);
+        let flow = effort.abs() * self.calibration.max_flow();
+        let new_pwm = self.calibration.pwm_for(flow);
+        debug!(
+            "[{}] PID effort: {:} | flow {:} kg/s | PWM {:}",
+            self.name, control_effort, flow, new_pwm
+        );
+        return new_pwm;
     }
 }
 
@@ -0,0 +1,194 @@
+// ----------------------------------------------------------------------------
+// Endurance
+// ---------
+// How much longer the consumables can keep us floating. What the controller
+// has actually spent by day and by night is run forward through the coming
+// sunsets and sunrises until the ballast is down to the abort limit, or so
+// much lift gas is gone that dumping all the ballast couldn't make up for it.
+// ----------------------------------------------------------------------------
+
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use crate::solar::sun_is_up;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Usage {
+    // Consumables spent over some time
+    pub ballast: f32,   // ballast dumped in kg
+    pub gas: f32,       // lift gas vented in kg
+    pub time: Duration, // time it took
+}
+
+impl Usage {
+    fn add(&mut self, ballast: f32, gas: f32, dt: Duration) {
+        self.ballast += ballast;
+        self.gas += gas;
+        self.time += dt;
+    }
+
+    fn rates(&self) -> Rates {
+        let time = self.time.as_secs_f32();
+        return Rates {
+            ballast: self.ballast / time,
+            gas: self.gas / time,
+        };
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Rates {
+    pub ballast: f32, // ballast dumped in kg/s
+    pub gas: f32,     // lift gas vented in kg/s
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ConsumptionHistory {
+    // What the controller has spent while floating
+    day: Usage,   // with the sun up
+    night: Usage, // with the sun down
+    total: Usage, // all of it, including when we didn't know where the sun was
+}
+
+impl ConsumptionHistory {
+    pub fn new() -> Self {
+        return ConsumptionHistory::default();
+    }
+
+    pub fn record(&mut self, ballast: f32, gas: f32, dt: Duration, sun_up: Option<bool>) {
+        // count what was spent over dt, by day or night if we know which
+        match sun_up {
+            Some(true) => self.day.add(ballast, gas, dt),
+            Some(false) => self.night.add(ballast, gas, dt),
+            None => (),
+        }
+        self.total.add(ballast, gas, dt);
+    }
+
+    pub fn day(&self) -> Usage {
+        return self.day;
+    }
+
+    pub fn night(&self) -> Usage {
+        return self.night;
+    }
+
+    pub fn total(&self) -> Usage {
+        return self.total;
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Limit {
+    Ballast, // ballast down to the abort limit
+    LiftGas, // lost more gas than the ballast can make up for
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Limit::Ballast => write!(f, "Ballast"),
+            Limit::LiftGas => write!(f, "LiftGas"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Endurance {
+    pub remaining: Option<Duration>, // until the abort, None if past the horizon
+    pub limit: Option<Limit>,        // what runs out first, if anything does
+    pub usable_ballast: f32,         // ballast above the abort limit in kg
+    pub spare_gas: f32,              // lift gas the ballast can still make up for in kg
+    pub day: Rates,                  // expected use with the sun up
+    pub night: Rates,                // expected use with the sun down
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct EnduranceModel {
+    pub default_rates: Rates,  // expected use until there is enough history
+    pub min_history: Duration, // trust a day or night rate after seeing this much of it
+    pub step: Duration,        // forecast time step
+    pub horizon: Duration,     // stop looking this far ahead
+}
+
+impl EnduranceModel {
+    pub fn rates(&self, history: &ConsumptionHistory, sun_up: Option<bool>) -> Rates {
+        // what we expect to spend by day, by night, or either if we don't
+        // know where the sun is. Fall back on the whole history when there
+        // isn't enough of a day or night yet, then on the defaults.
+        let usage = match sun_up {
+            Some(true) => history.day(),
+            Some(false) => history.night(),
+            None => history.total(),
+        };
+        if usage.time >= self.min_history && !usage.time.is_zero() {
+            return usage.rates();
+        }
+        if history.total().time >= self.min_history && !history.total().time.is_zero() {
+            return history.total().rates();
+        }
+        return self.default_rates;
+    }
+
+    pub fn predict(
+        &self,
+        history: &ConsumptionHistory,
+        usable_ballast: f32,
+        spare_gas: f32,
+        position: Option<(f32, f32)>, // latitude and longitude in degrees
+        altitude: f32,
+        now: SystemTime,
+    ) -> Endurance {
+        // spend the inventory a step at a time at the rate for whether the
+        // sun will be up then, until something runs out
+        let mut endurance = Endurance {
+            remaining: None,
+            limit: None,
+            usable_ballast,
+            spare_gas,
+            day: self.rates(history, Some(true)),
+            night: self.rates(history, Some(false)),
+        };
+        let step = self.step.as_secs_f32();
+        if step <= 0.0 {
+            return endurance;
+        }
+        let mut ballast = usable_ballast.max(0.0);
+        let mut gas = spare_gas.max(0.0);
+        let mut elapsed = Duration::ZERO;
+        while elapsed < self.horizon {
+            let sun_up = position.map(|(latitude, longitude)| {
+                sun_is_up(latitude, longitude, altitude, now + elapsed)
+            });
+            let rates = self.rates(history, sun_up);
+            // how far into this step each one would run out
+            let ballast_left = time_left(ballast, rates.ballast);
+            let gas_left = time_left(gas, rates.gas);
+            if ballast_left.min(gas_left) <= step {
+                let (left, limit) = if ballast_left <= gas_left {
+                    (ballast_left, Limit::Ballast)
+                } else {
+                    (gas_left, Limit::LiftGas)
+                };
+                endurance.remaining = Some(elapsed + Duration::from_secs_f32(left));
+                endurance.limit = Some(limit);
+                return endurance;
+            }
+            ballast -= rates.ballast * step;
+            gas -= rates.gas * step;
+            elapsed += self.step;
+        }
+        return endurance; // lasts past the horizon
+    }
+}
+
+fn time_left(amount: f32, rate: f32) -> f32 {
+    // seconds until an amount is used up at a rate
+    if amount <= 0.0 {
+        return 0.0;
+    }
+    if rate <= 0.0 {
+        return f32::INFINITY;
+    }
+    return amount / rate;
+}
@@ -0,0 +1,145 @@
+// ----------------------------------------------------------------------------
+// Flight Phase
+// ------------
+// Works out whether we're on the pad, ascending, floating, descending or
+// landed from the fused altitude and ascent rate. Entering and leaving a
+// phase use different thresholds, and a new phase has to hold for a dwell
+// time before it is believed, so noise near a threshold doesn't flap.
+// ----------------------------------------------------------------------------
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use log::info;
+
+use crate::measurement::Measurement;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FlightPhase {
+    Pad,     // waiting to launch
+    Ascent,  // going up
+    Float,   // neither going up nor down
+    Descent, // coming down
+    Landed,  // stopped on the ground
+}
+
+impl fmt::Display for FlightPhase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FlightPhase::Pad => write!(f, "Pad"),
+            FlightPhase::Ascent => write!(f, "Ascent"),
+            FlightPhase::Float => write!(f, "Float"),
+            FlightPhase::Descent => write!(f, "Descent"),
+            FlightPhase::Landed => write!(f, "Landed"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct PhaseThresholds {
+    // Keep float_rate below ascent_rate and -descent_rate for hysteresis
+    pub ascent_rate: f32,     // rising faster than this in m/s is ascending
+    pub float_rate: f32,      // slower than this either way in m/s is floating
+    pub descent_rate: f32,    // falling faster than this in m/s is descending, negative
+    pub landed_altitude: f32, // stopping below this in m after descending is landing
+    pub dwell: Duration,      // how long a new phase has to hold
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PhaseTransition {
+    pub from: FlightPhase,
+    pub to: FlightPhase,
+    pub at: Instant, // when the new phase began
+}
+
+pub struct PhaseDetector {
+    thresholds: PhaseThresholds,
+    phase: FlightPhase,                        // current phase
+    previous: FlightPhase,                     // phase before the current one
+    since: Instant,                            // when the current phase began
+    candidate: Option<(FlightPhase, Instant)>, // phase that might be next, and since when
+}
+
+impl PhaseDetector {
+    pub fn new(thresholds: PhaseThresholds, now: Instant) -> Self {
+        return PhaseDetector {
+            thresholds,
+            phase: FlightPhase::Pad,
+            previous: FlightPhase::Pad,
+            since: now,
+            candidate: None,
+        };
+    }
+
+    pub fn phase(&self) -> FlightPhase {
+        return self.phase;
+    }
+
+    pub fn previous(&self) -> FlightPhase {
+        return self.previous;
+    }
+
+    pub fn since(&self) -> Instant {
+        return self.since;
+    }
+
+    fn classify(&self, altitude: f32, ascent_rate: f32) -> FlightPhase {
+        // the phase this sample on its own points to
+        let rising = ascent_rate > self.thresholds.ascent_rate;
+        let falling = ascent_rate < self.thresholds.descent_rate;
+        let still = ascent_rate.abs() < self.thresholds.float_rate;
+        return match self.phase {
+            FlightPhase::Pad | FlightPhase::Landed if rising => FlightPhase::Ascent,
+            FlightPhase::Pad | FlightPhase::Landed => self.phase,
+            _ if rising => FlightPhase::Ascent,
+            _ if falling => FlightPhase::Descent,
+            FlightPhase::Descent if still && altitude < self.thresholds.landed_altitude => {
+                FlightPhase::Landed
+            }
+            _ if still => FlightPhase::Float,
+            _ => self.phase,
+        };
+    }
+
+    pub fn update(
+        &mut self,
+        altitude: &Measurement<f32>,
+        ascent_rate: &Measurement<f32>,
+    ) -> Option<PhaseTransition> {
+        // move to a new phase once the telemetry has pointed to it for the
+        // dwell time, returning the transition if there was one
+        if !altitude.is_good() || !ascent_rate.is_good() {
+            return None; // can't tell anything from this
+        }
+        let now = ascent_rate.timestamp;
+        let next = self.classify(altitude.value, ascent_rate.value);
+        if next == self.phase {
+            self.candidate = None;
+            return None;
+        }
+        let since = match self.candidate {
+            Some((phase, since)) if phase == next => since,
+            _ => {
+                self.candidate = Some((next, now));
+                now
+            }
+        };
+        if now.saturating_duration_since(since) < self.thresholds.dwell {
+            return None;
+        }
+        let transition = PhaseTransition {
+            from: self.phase,
+            to: next,
+            at: since,
+        };
+        info!(
+            "Flight phase {} --> {} at {} m, {} m/s",
+            self.phase, next, altitude.value, ascent_rate.value
+        );
+        self.previous = self.phase;
+        self.phase = next;
+        self.since = since;
+        self.candidate = None;
+        return Some(transition);
+    }
+}
@@ -0,0 +1,275 @@
+// ----------------------------------------------------------------------------
+// Altitude Fusion
+// ---------------
+// Combine barometric and GPS altitude into one altitude and ascent rate
+// estimate, keeping an eye on each source so a frozen or wild sensor is
+// de-weighted instead of silently steering the balloon.
+// ----------------------------------------------------------------------------
+
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+
+use crate::measurement::{Measurement, Quality};
+
+// consecutive outliers before a source is considered failed
+const FAIL_AFTER_OUTLIERS: u32 = 5;
+// consecutive good samples before a failed source is trusted again
+const RECOVER_AFTER_SAMPLES: u32 = 10;
+// smallest change in m that counts as a sensor still updating, so a value
+// stuck up to float rounding still reads as frozen
+const FROZEN_TOLERANCE: f32 = 0.01;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Source {
+    Baro,
+    Gps,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SourceHealth {
+    Ok,      // used at its modeled noise
+    Suspect, // residual outside the gate, de-weighted
+    Failed,  // frozen or persistently wrong, ignored
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SourceModel {
+    // How much to trust a source
+    pub noise: f32,        // 1-sigma measurement noise in m
+    pub latency: Duration, // how old a measurement is when it arrives
+}
+
+struct SourceState {
+    model: SourceModel,
+    health: SourceHealth,
+    last_value: Option<f32>,          // most recent raw measurement
+    last_change: Option<Instant>,     // when the raw value last changed
+    residual: Option<(f32, Instant)>, // measurement minus prediction
+    outliers: u32,                    // consecutive samples outside the gate
+    good: u32,                        // consecutive samples inside the gate
+}
+
+impl SourceState {
+    fn new(model: SourceModel) -> Self {
+        return SourceState {
+            model,
+            health: SourceHealth::Ok,
+            last_value: None,
+            last_change: None,
+            residual: None,
+            outliers: 0,
+            good: 0,
+        };
+    }
+
+    fn is_frozen(&mut self, value: f32, timestamp: Instant, frozen_after: Duration) -> bool {
+        // a real sensor is never perfectly still, so an unchanging value
+        // for long enough means it stopped updating
+        let changed = match self.last_value {
+            Some(last) => (value - last).abs() > FROZEN_TOLERANCE,
+            None => true,
+        };
+        if changed || self.last_change.is_none() {
+            self.last_value = Some(value);
+            self.last_change = Some(timestamp);
+            return false;
+        }
+        let unchanged = timestamp.saturating_duration_since(self.last_change.unwrap());
+        return unchanged > frozen_after;
+    }
+}
+
+pub struct FusedAltitude {
+    pub altitude: Measurement<f32>,      // fused altitude in m
+    pub ascent_rate: Measurement<f32>,   // fused ascent rate in m/s
+    pub baro_residual: Measurement<f32>, // baro minus prediction in m
+    pub gps_residual: Measurement<f32>,  // GPS minus prediction in m
+}
+
+pub struct AltitudeFusion {
+    // Two state (altitude, ascent rate) Kalman filter fed by each source
+    altitude: f32,    // estimated altitude in m
+    ascent_rate: f32, // estimated ascent rate in m/s
+    covariance: [[f32; 2]; 2],
+    time: Option<Instant>,     // time of the estimate
+    last_fix: Option<Instant>, // last time a source was accepted
+    accel_noise: f32,          // process noise as a 1-sigma acceleration in m/s^2
+    gate: f32,                 // residuals beyond this many sigma are outliers
+    frozen_after: Duration,    // unchanged this long means the sensor is frozen
+    baro: SourceState,
+    gps: SourceState,
+}
+
+impl AltitudeFusion {
+    pub fn new(
+        baro: SourceModel,
+        gps: SourceModel,
+        accel_noise: f32,
+        gate: f32,
+        frozen_after: Duration,
+    ) -> Self {
+        return AltitudeFusion {
+            altitude: 0.0,
+            ascent_rate: 0.0,
+            covariance: [[0.0; 2]; 2],
+            time: None,
+            last_fix: None,
+            accel_noise,
+            gate,
+            frozen_after,
+            baro: SourceState::new(baro),
+            gps: SourceState::new(gps),
+        };
+    }
+
+    pub fn health(&self, source: Source) -> SourceHealth {
+        return self.source(source).health;
+    }
+
+    fn source(&self, source: Source) -> &SourceState {
+        return match source {
+            Source::Baro => &self.baro,
+            Source::Gps => &self.gps,
+        };
+    }
+
+    fn source_mut(&mut self, source: Source) -> &mut SourceState {
+        return match source {
+            Source::Baro => &mut self.baro,
+            Source::Gps => &mut self.gps,
+        };
+    }
+
+    fn predict(&mut self, timestamp: Instant) {
+        // propagate the estimate forward to a new time
+        let dt = match self.time {
+            Some(time) => timestamp.saturating_duration_since(time).as_secs_f32(),
+            None => 0.0,
+        };
+        if dt <= 0.0 {
+            return;
+        }
+        self.altitude += self.ascent_rate * dt;
+        // P = F P F' + Q for a constant velocity model
+        let [[p00, p01], [p10, p11]] = self.covariance;
+        let q = self.accel_noise * self.accel_noise;
+        self.covariance = [
+            [
+                p00 + dt * (p01 + p10) + dt * dt * p11 + q * dt.powi(4) / 4.0,
+                p01 + dt * p11 + q * dt.powi(3) / 2.0,
+            ],
+            [p10 + dt * p11 + q * dt.powi(3) / 2.0, p11 + q * dt * dt],
+        ];
+        self.time = Some(timestamp);
+    }
+
+    pub fn update(&mut self, source: Source, measurement: Measurement<f32>) {
+        // fold in an altitude measurement from one of the sources
+        let value = measurement.value;
+        let timestamp = measurement.timestamp;
+        if !value.is_finite() || measurement.quality == Quality::Bad {
+            return;
+        }
+
+        // start from the first measurement we get
+        if self.time.is_none() {
+            let noise = self.source(source).model.noise;
+            self.altitude = value;
+            self.ascent_rate = 0.0;
+            self.covariance = [[noise * noise, 0.0], [0.0, 100.0]];
+            self.time = Some(timestamp);
+            self.last_fix = Some(timestamp);
+            let frozen_after = self.frozen_after;
+            self.source_mut(source)
+                .is_frozen(value, timestamp, frozen_after);
+            return;
+        }
+        self.predict(timestamp);
+
+        // the measurement was taken `latency` ago: h(t - latency) = h - v * latency
+        let latency = self.source(source).model.latency.as_secs_f32();
+        let h = [1.0, -latency];
+        let predicted = self.altitude - self.ascent_rate * latency;
+        let residual = value - predicted;
+        let [[p00, p01], [p10, p11]] = self.covariance;
+        let ph = [p00 * h[0] + p01 * h[1], p10 * h[0] + p11 * h[1]];
+        let noise = self.source(source).model.noise;
+        let predicted_variance = h[0] * ph[0] + h[1] * ph[1];
+        let sigma = (predicted_variance + noise * noise).sqrt();
+        let normalized = residual.abs() / sigma;
+
+        let frozen_after = self.frozen_after;
+        let gate = self.gate;
+        let state = self.source_mut(source);
+        state.residual = Some((residual, timestamp));
+        let frozen = state.is_frozen(value, timestamp, frozen_after);
+        if normalized > gate {
+            state.outliers += 1;
+            state.good = 0;
+        } else {
+            state.outliers = 0;
+            state.good += 1;
+        }
+
+        let previous = state.health;
+        let recovering = previous == SourceHealth::Failed && state.good < RECOVER_AFTER_SAMPLES;
+        state.health = if frozen || recovering || state.outliers >= FAIL_AFTER_OUTLIERS {
+            SourceHealth::Failed
+        } else if normalized > gate {
+            SourceHealth::Suspect
+        } else {
+            SourceHealth::Ok
+        };
+        if state.health != previous {
+            match state.health {
+                SourceHealth::Failed => warn!("{:?} altitude source failed", source),
+                SourceHealth::Suspect => warn!("{:?} altitude source is suspect", source),
+                SourceHealth::Ok => info!("{:?} altitude source is ok", source),
+            }
+        }
+
+        // de-weight suspect sources by how far outside the gate they are
+        let variance = match state.health {
+            SourceHealth::Failed => return,
+            SourceHealth::Suspect => noise * noise * (normalized / gate).powi(2),
+            SourceHealth::Ok => noise * noise,
+        };
+        let s = predicted_variance + variance;
+        let gain = [ph[0] / s, ph[1] / s];
+        self.altitude += gain[0] * residual;
+        self.ascent_rate += gain[1] * residual;
+        self.covariance = [
+            [p00 - gain[0] * ph[0], p01 - gain[0] * ph[1]],
+            [p10 - gain[1] * ph[0], p11 - gain[1] * ph[1]],
+        ];
+        self.last_fix = Some(timestamp);
+    }
+
+    pub fn estimate(&self) -> Option<FusedAltitude> {
+        // Latest fused altitude and ascent rate, stamped with the last time a
+        // source was accepted so stale estimates look stale to the controller
+        let fix = self.last_fix?;
+        let residual = |state: &SourceState| match state.residual {
+            Some((value, timestamp)) => Measurement::new(value, timestamp),
+            None => Measurement::new(0.0, fix),
+        };
+        // only as good as the best source that has reported
+        let quality = [&self.baro, &self.gps]
+            .iter()
+            .filter(|state| state.residual.is_some())
+            .map(|state| match state.health {
+                SourceHealth::Ok => Quality::Good,
+                SourceHealth::Suspect => Quality::Suspect,
+                SourceHealth::Failed => Quality::Bad,
+            })
+            .min_by_key(|quality| *quality as u8)
+            .unwrap_or(Quality::Good);
+        return Some(FusedAltitude {
+            altitude: Measurement::new(self.altitude, fix).with_quality(quality),
+            ascent_rate: Measurement::new(self.ascent_rate, fix).with_quality(quality),
+            baro_residual: residual(&self.baro),
+            gps_residual: residual(&self.gps),
+        });
+    }
+}
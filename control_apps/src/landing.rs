@@ -0,0 +1,177 @@
+// ----------------------------------------------------------------------------
+// Landing
+// -------
+// Where the payload will come down. The descent is stepped down through the
+// wind profile to the ground, drifting with the wind in each layer for as
+// long as it takes to fall through it, and the uncertainty in the winds and
+// the descent rate is carried along as an ellipse around the landing point.
+// The wind error is the same all through a wind layer and independent from
+// one layer to the next, so the ellipse doesn't depend on the step size.
+// ----------------------------------------------------------------------------
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::atmosphere::{Atmosphere, EARTH_RADIUS, G0};
+use crate::wind::WindProfile;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DescentModel {
+    // How fast the payload comes down
+    Parachute {
+        mass: f32,      // what hangs under the canopy in kg
+        drag_area: f32, // drag coefficient times canopy area in m^2
+    },
+    Venting {
+        rate: f32,         // ascent rate while venting down in m/s, negative
+        landing_rate: f32, // ascent rate after the flare in m/s, negative
+        flare_height: f32, // height above ground to slow down at in m
+    },
+    Steady {
+        rate: f32, // ascent rate held or drifted down at in m/s, negative
+    },
+}
+
+impl fmt::Display for DescentModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DescentModel::Parachute { .. } => write!(f, "Parachute"),
+            DescentModel::Venting { .. } => write!(f, "Venting"),
+            DescentModel::Steady { .. } => write!(f, "Steady"),
+        }
+    }
+}
+
+impl DescentModel {
+    pub fn descent_rate(&self, height: f32, density: f32) -> f32 {
+        // how fast it falls in m/s at a height above the ground in m
+        return match *self {
+            DescentModel::Parachute { mass, drag_area } => {
+                // terminal velocity, where drag holds up the weight
+                (2.0 * mass * G0 / (density * drag_area)).sqrt()
+            }
+            DescentModel::Venting {
+                rate,
+                landing_rate,
+                flare_height,
+            } => {
+                if height > flare_height {
+                    -rate
+                } else {
+                    -landing_rate
+                }
+            }
+            DescentModel::Steady { rate } => -rate,
+        };
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ellipse {
+    // 1-sigma uncertainty around a point
+    pub semi_major: f32, // in m
+    pub semi_minor: f32, // in m
+    pub bearing: f32,    // of the major axis in degrees from north
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LandingPrediction {
+    pub latitude: f32,          // degrees north
+    pub longitude: f32,         // degrees east
+    pub time_to_land: Duration, // from now
+    pub drift: (f32, f32),      // east and north from here in m
+    pub ellipse: Ellipse,       // how sure we are of it
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct LandingModel {
+    pub ground_altitude: f32, // altitude of the landing site in m
+    pub step: f32,            // altitude step to fall through at a time in m
+    pub wind_sigma: f32,      // 1-sigma error of each layer's wind in m/s
+    pub wind_layer: f32,      // how thick a layer with one wind error is in m
+    pub rate_sigma: f32,      // 1-sigma error of the descent rate as a fraction
+}
+
+impl LandingModel {
+    pub fn predict(
+        &self,
+        latitude: f32,
+        longitude: f32,
+        altitude: f32,
+        descent: &DescentModel,
+        wind: &WindProfile,
+        atmosphere: &dyn Atmosphere,
+    ) -> Option<LandingPrediction> {
+        // fall from an altitude to the ground, drifting with the wind.
+        // None if already down, or the model doesn't come down.
+        if altitude <= self.ground_altitude || self.step <= 0.0 {
+            return None;
+        }
+        let mut top = altitude;
+        let mut time = 0.0;
+        let (mut east, mut north) = (0.0, 0.0);
+        let mut wind_variance = 0.0; // each layer's wind error is its own
+        let mut layer = None; // which wind layer we're in
+        let mut layer_time = 0.0; // how long we've been in it in s
+        while top > self.ground_altitude {
+            // don't step across a layer boundary, its time belongs below
+            let layer_base = self.layer_base(top);
+            let bottom = (top - self.step).max(self.ground_altitude).max(layer_base);
+            let fall = top - bottom;
+            let middle = top - 0.5 * fall;
+            let density = atmosphere.density(middle);
+            let rate = descent.descent_rate(middle - self.ground_altitude, density);
+            if rate.is_nan() || rate <= 0.0 {
+                return None; // not coming down
+            }
+            let dt = fall / rate;
+            let (wind_east, wind_north) = wind.velocity_at(middle);
+            east += wind_east * dt;
+            north += wind_north * dt;
+            time += dt;
+            // within a layer the error drifts the same way the whole time
+            if layer != Some(layer_base) {
+                wind_variance += (self.wind_sigma * layer_time).powi(2);
+                layer = Some(layer_base);
+                layer_time = 0.0;
+            }
+            layer_time += dt;
+            top = bottom;
+        }
+        wind_variance += (self.wind_sigma * layer_time).powi(2);
+
+        // a descent rate error stretches the whole drift, so the ellipse is
+        // longest along it
+        let drift = (east * east + north * north).sqrt();
+        let ellipse = Ellipse {
+            semi_major: (wind_variance + (self.rate_sigma * drift).powi(2)).sqrt(),
+            semi_minor: wind_variance.sqrt(),
+            bearing: east.atan2(north).to_degrees().rem_euclid(360.0),
+        };
+
+        // small enough distances to treat the ground as flat
+        let landing_latitude = latitude + (north / EARTH_RADIUS).to_degrees();
+        let landing_longitude =
+            longitude + (east / (EARTH_RADIUS * latitude.to_radians().cos())).to_degrees();
+        return Some(LandingPrediction {
+            latitude: landing_latitude,
+            longitude: (landing_longitude + 540.0).rem_euclid(360.0) - 180.0,
+            time_to_land: Duration::from_secs_f32(time),
+            drift: (east, north),
+            ellipse,
+        });
+    }
+
+    fn layer_base(&self, altitude: f32) -> f32 {
+        // bottom of the wind layer an altitude is in, the top belongs to
+        // the layer above
+        if self.wind_layer <= 0.0 {
+            return f32::NEG_INFINITY; // all one layer
+        }
+        let base = (altitude / self.wind_layer).floor() * self.wind_layer;
+        if base == altitude {
+            return base - self.wind_layer;
+        }
+        return base;
+    }
+}
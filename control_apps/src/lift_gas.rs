@@ -0,0 +1,131 @@
+// ----------------------------------------------------------------------------
+// Lift Gas
+// --------
+// Lift gas inventory from the vent valve history. Gas flows out of the vent
+// at the top of the balloon driven by the buoyancy head across the valve,
+// which is used to estimate what is left, the free lift, and where the
+// balloon would float.
+// ----------------------------------------------------------------------------
+
+use std::time::{Duration, Instant};
+
+use crate::atmosphere::{Atmosphere, G0};
+
+/// Molar mass of dry air in kg/mol
+pub const AIR_MOLAR_MASS: f32 = 0.028_964_4;
+/// Molar mass of helium in kg/mol
+pub const HELIUM_MOLAR_MASS: f32 = 0.004_002_6;
+
+#[derive(Copy, Clone, Debug)]
+pub struct BalloonModel {
+    // Balloon and vent properties the flow and lift models need
+    pub gas_molar_mass: f32, // molar mass of the lift gas in kg/mol
+    pub vent_area: f32,      // effective vent area (discharge coefficient x area) in m^2
+    pub vent_head: f32,      // height of gas column above the vent's outside in m
+    pub volume: f32,         // volume of the fully inflated balloon in m^3
+    pub system_mass: f32,    // balloon, payload and rigging without gas or ballast in kg
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LiftGasReport {
+    pub remaining: f32,                    // lift gas left in kg
+    pub vented: f32,                       // lift gas vented so far in kg
+    pub vent_open_time: Duration,          // equivalent time fully open
+    pub free_lift: f32,                    // lift beyond the system weight in kg
+    pub equilibrium_altitude: Option<f32>, // where the balloon would float in m
+}
+
+pub struct LiftGasEstimator {
+    model: BalloonModel,
+    atmosphere: Box<dyn Atmosphere>,
+    initial: f32,   // lift gas at launch in kg
+    remaining: f32, // lift gas left in kg
+    vent_open_time: Duration,
+    ballast_mass: f32,     // latest ballast mass in kg
+    time: Option<Instant>, // time of the last update
+}
+
+impl LiftGasEstimator {
+    pub fn new(model: BalloonModel, atmosphere: Box<dyn Atmosphere>, initial: f32) -> Self {
+        return LiftGasEstimator {
+            model,
+            atmosphere,
+            initial,
+            remaining: initial,
+            vent_open_time: Duration::ZERO,
+            ballast_mass: 0.0,
+            time: None,
+        };
+    }
+
+    fn gas_ratio(&self) -> f32 {
+        // density of the lift gas relative to the air around it
+        return self.model.gas_molar_mass / AIR_MOLAR_MASS;
+    }
+
+    pub fn lift_per_kg(&self) -> f32 {
+        // lift each kg of lift gas gives, beyond carrying itself
+        return 1.0 / self.gas_ratio() - 1.0;
+    }
+
+    pub fn vent_flow(&self, altitude: f32) -> f32 {
+        // mass flow out of the fully open vent in kg/s. The gas is at ambient
+        // pressure and temperature, so its density follows the air's.
+        let air_density = self.atmosphere.density(altitude);
+        let gas_density = air_density * self.gas_ratio();
+        let differential_pressure = (air_density - gas_density) * G0 * self.model.vent_head;
+        return self.model.vent_area * (2.0 * gas_density * differential_pressure).sqrt();
+    }
+
+    pub fn update(&mut self, vent_opening: f32, altitude: f32, ballast_mass: f32, now: Instant) {
+        // take out what flowed through the vent since the last update, given
+        // how open it was as a fraction of its fully open flow
+        if let Some(time) = self.time {
+            let dt = now.saturating_duration_since(time);
+            let vented = vent_opening * self.vent_flow(altitude) * dt.as_secs_f32();
+            self.remaining = (self.remaining - vented).max(0.0);
+            self.vent_open_time += dt.mul_f32(vent_opening);
+        }
+        self.ballast_mass = ballast_mass;
+        self.time = Some(now);
+    }
+
+    pub fn report(&self) -> LiftGasReport {
+        // gas displaces more than its own mass in air
+        let gross_lift = self.remaining * (1.0 / self.gas_ratio() - 1.0);
+        let free_lift = gross_lift - self.model.system_mass - self.ballast_mass;
+        return LiftGasReport {
+            remaining: self.remaining,
+            vented: self.initial - self.remaining,
+            vent_open_time: self.vent_open_time,
+            free_lift,
+            equilibrium_altitude: self.equilibrium_altitude(free_lift),
+        };
+    }
+
+    fn equilibrium_altitude(&self, free_lift: f32) -> Option<f32> {
+        // With positive free lift the balloon rises until it is full, then
+        // floats where the air it displaces weighs as much as everything it
+        // carries, gas included
+        if free_lift <= 0.0 {
+            return None; // it's coming down
+        }
+        let total_mass = self.model.system_mass + self.ballast_mass + self.remaining;
+        let float_density = total_mass / self.model.volume;
+        // density only ever falls with altitude, so bisect for it
+        let mut low = 0.0;
+        let mut high = 60_000.0;
+        if self.atmosphere.density(high) > float_density {
+            return None; // would go higher than we can model
+        }
+        for _ in 0..64 {
+            let mid = 0.5 * (low + high);
+            if self.atmosphere.density(mid) > float_density {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        return Some(0.5 * (low + high));
+    }
+}
@@ -1,16 +1,34 @@
 use std::time::Instant;
 
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Quality {
+    // How far a measurement can be trusted
+    Good,    // passed every check
+    Suspect, // passed, but something about it looks off
+    Bad,     // failed a check, don't act on it
+}
 
+#[derive(Clone, Copy, Debug)]
 pub struct Measurement<T> {
     pub value: T,
     pub timestamp: Instant,
+    pub quality: Quality,
 }
 
 impl<T> Measurement<T> {
-    fn new(value: T, timestamp: Instant) -> Self {
-        return Measurement{
+    pub fn new(value: T, timestamp: Instant) -> Self {
+        return Measurement {
             value,
             timestamp,
-        }
+            quality: Quality::Good,
+        };
+    }
+
+    pub fn with_quality(self, quality: Quality) -> Self {
+        return Measurement { quality, ..self };
+    }
+
+    pub fn is_good(&self) -> bool {
+        return self.quality == Quality::Good;
     }
 }
@@ -0,0 +1,118 @@
+// ----------------------------------------------------------------------------
+// Solar
+// -----
+// Where the sun is in the sky from a GPS position and UTC time, and the
+// feed-forward that gets ahead of the lift lost at sunset and gained at
+// sunrise. The ephemeris is the low precision one from the Astronomical
+// Almanac, good to about 0.01 degrees for the next few decades.
+// ----------------------------------------------------------------------------
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::atmosphere::EARTH_RADIUS;
+
+// Julian date of the unix epoch, and of the J2000.0 epoch the ephemeris uses
+const JD_UNIX_EPOCH: f64 = 2_440_587.5;
+const JD_J2000: f64 = 2_451_545.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SunPosition {
+    pub elevation: f32, // degrees above the horizon, negative below
+    pub azimuth: f32,   // degrees clockwise from true north
+}
+
+pub fn sun_position(latitude: f32, longitude: f32, time: SystemTime) -> SunPosition {
+    // where the sun is seen from a latitude and longitude in degrees
+    // (north and east positive) at a UTC time
+    let unix = match time.duration_since(UNIX_EPOCH) {
+        Ok(v) => v.as_secs_f64(),
+        Err(e) => -e.duration().as_secs_f64(),
+    };
+    let n = unix / 86_400.0 + JD_UNIX_EPOCH - JD_J2000; // days since J2000.0
+
+    // ecliptic longitude of the sun
+    let mean_longitude = (280.460 + 0.985_647_4 * n).rem_euclid(360.0);
+    let mean_anomaly = (357.528 + 0.985_600_3 * n).rem_euclid(360.0).to_radians();
+    let ecliptic_longitude =
+        (mean_longitude + 1.915 * mean_anomaly.sin() + 0.020 * (2.0 * mean_anomaly).sin())
+            .to_radians();
+    let obliquity = (23.439 - 0.000_000_4 * n).to_radians();
+
+    // right ascension and declination
+    let right_ascension =
+        (obliquity.cos() * ecliptic_longitude.sin()).atan2(ecliptic_longitude.cos());
+    let declination = (obliquity.sin() * ecliptic_longitude.sin()).asin();
+
+    // local hour angle from sidereal time
+    let sidereal = (280.460_618_37 + 360.985_647_366_29 * n).rem_euclid(360.0);
+    let hour_angle = (sidereal + longitude as f64).to_radians() - right_ascension;
+
+    let latitude = (latitude as f64).to_radians();
+    let elevation = (latitude.sin() * declination.sin()
+        + latitude.cos() * declination.cos() * hour_angle.cos())
+    .asin();
+    let azimuth = (-hour_angle.sin())
+        .atan2(declination.tan() * latitude.cos() - latitude.sin() * hour_angle.cos());
+    return SunPosition {
+        elevation: elevation.to_degrees() as f32,
+        azimuth: azimuth.to_degrees().rem_euclid(360.0) as f32,
+    };
+}
+
+pub fn horizon_dip(altitude: f32) -> f32 {
+    // how far below level the horizon is from an altitude in m, in degrees.
+    // The sun sets at the balloon when it drops below this, not below 0.
+    let altitude = altitude.max(0.0);
+    return (EARTH_RADIUS / (EARTH_RADIUS + altitude))
+        .acos()
+        .to_degrees();
+}
+
+pub fn sun_is_up(latitude: f32, longitude: f32, altitude: f32, time: SystemTime) -> bool {
+    // whether the sun is above the horizon as seen from the balloon
+    return sun_position(latitude, longitude, time).elevation > -horizon_dip(altitude);
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SolarModel {
+    // How much to spend ahead of the sun setting or rising
+    pub lead: Duration,      // start this long before sunset or sunrise
+    pub sunset_ballast: f32, // ballast to dump over the lead before sunset in kg
+    pub sunrise_vent: f32,   // fully open vent time over the lead before sunrise in s
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct FeedForward {
+    pub vent: f32, // vent flow as a fraction of fully open
+    pub dump: f32, // ballast flow in kg/s
+}
+
+impl SolarModel {
+    pub fn feed_forward(
+        &self,
+        latitude: f32,
+        longitude: f32,
+        altitude: f32,
+        time: SystemTime,
+    ) -> FeedForward {
+        // spread what the model spends evenly over the lead time, so it is
+        // all gone by the time the sun sets or rises
+        let lead = self.lead.as_secs_f32();
+        if lead <= 0.0 {
+            return FeedForward::default();
+        }
+        let up_now = sun_is_up(latitude, longitude, altitude, time);
+        let up_later = sun_is_up(latitude, longitude, altitude, time + self.lead);
+        return match (up_now, up_later) {
+            (true, false) => FeedForward {
+                vent: 0.0,
+                dump: self.sunset_ballast / lead, // sunset is coming
+            },
+            (false, true) => FeedForward {
+                vent: self.sunrise_vent / lead, // sunrise is coming
+                dump: 0.0,
+            },
+            _ => FeedForward::default(),
+        };
+    }
+}
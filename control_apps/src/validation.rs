@@ -0,0 +1,99 @@
+// ----------------------------------------------------------------------------
+// Validation
+// ----------
+// Plausibility checks on incoming telemetry, so a single glitch is flagged
+// and filtered out before it reaches the controller.
+// ----------------------------------------------------------------------------
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use log::warn;
+
+use crate::measurement::{Measurement, Quality};
+
+#[derive(Copy, Clone, Debug)]
+pub struct ChannelLimits {
+    // What a sane value of one telemetry channel looks like
+    pub min: f32,             // lowest plausible value
+    pub max: f32,             // highest plausible value
+    pub max_rate: f32,        // fastest plausible change per second
+    pub median_window: usize, // samples in the spike filter, 1 to disable
+}
+
+pub struct ChannelValidator {
+    // Checks and filters one telemetry channel
+    name: String,
+    limits: ChannelLimits,
+    window: VecDeque<f32>,               // recent samples that passed
+    last_output: Option<(f32, Instant)>, // last filtered value
+}
+
+impl ChannelValidator {
+    pub fn new(name: &str, limits: ChannelLimits) -> Self {
+        return ChannelValidator {
+            name: String::from(name),
+            limits,
+            window: VecDeque::with_capacity(limits.median_window),
+            last_output: None,
+        };
+    }
+
+    pub fn validate(&mut self, measurement: Measurement<f32>) -> Measurement<f32> {
+        // Check a raw sample and return the filtered value with its quality.
+        // Rejected samples carry the last good value, flagged Bad.
+        let value = measurement.value;
+        let timestamp = measurement.timestamp;
+
+        let rejection = if !value.is_finite() {
+            Some(String::from("not a number"))
+        } else if value < self.limits.min || value > self.limits.max {
+            Some(format!(
+                "outside [{}, {}]",
+                self.limits.min, self.limits.max
+            ))
+        } else {
+            None
+        };
+        if let Some(reason) = rejection {
+            warn!("[{}] Rejecting {}: {}", self.name, value, reason);
+            let fallback = self.last_output.map_or(value, |(last, _)| last);
+            return Measurement::new(fallback, timestamp).with_quality(Quality::Bad);
+        }
+
+        // a jump faster than the channel can physically change is suspect,
+        // judged against the filtered value so one spike doesn't taint the next
+        let mut quality = Quality::Good;
+        if let Some((last, last_time)) = self.last_output {
+            let dt = timestamp.saturating_duration_since(last_time).as_secs_f32();
+            if dt > 0.0 && (value - last).abs() / dt > self.limits.max_rate {
+                warn!(
+                    "[{}] {} changed faster than {}/s from {}",
+                    self.name, value, self.limits.max_rate, last
+                );
+                quality = Quality::Suspect;
+            }
+        }
+
+        // median of the last few samples knocks down single spikes
+        if self.window.len() >= self.limits.median_window.max(1) {
+            self.window.pop_front();
+        }
+        self.window.push_back(value);
+        let filtered = median(&self.window);
+        self.last_output = Some((filtered, timestamp));
+        return Measurement::new(filtered, timestamp).with_quality(quality);
+    }
+}
+
+fn median(values: &VecDeque<f32>) -> f32 {
+    let mut sorted: Vec<f32> = values.iter().copied().collect();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mid = sorted.len() / 2;
+    // is_multiple_of needs a newer toolchain than the flight computer's
+    #[allow(clippy::manual_is_multiple_of)]
+    if sorted.len() % 2 == 0 {
+        return 0.5 * (sorted[mid - 1] + sorted[mid]);
+    }
+    return sorted[mid];
+}
@@ -0,0 +1,169 @@
+// ----------------------------------------------------------------------------
+// Valve Driver
+// ------------
+// Turns the PWM fraction the controller asks for into open/close events for
+// an on/off solenoid valve, keeping every pulse within what the valve can
+// physically and thermally take, and reports the duty actually delivered.
+// ----------------------------------------------------------------------------
+
+use std::time::{Duration, Instant};
+
+use log::{debug, warn};
+
+#[derive(Copy, Clone, Debug)]
+pub struct PulseLimits {
+    // What an on/off valve can take
+    pub min_on: Duration,   // shortest pulse worth opening for
+    pub min_off: Duration,  // shortest time closed between pulses
+    pub max_open: Duration, // longest the coil can stay energized
+    pub cooldown: Duration, // time closed after hitting max_open
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ValveEvent {
+    Open(Instant),
+    Close(Instant),
+}
+
+pub struct ValveDriver {
+    // Delta-sigma style pulse scheduler: open time owed accumulates at the
+    // commanded duty and is paid back while the valve is open
+    name: String,
+    limits: PulseLimits,
+    duty: f32,                   // commanded fraction of time open [0, 1]
+    open: bool,                  // whether the valve is open right now
+    cooling: bool,               // closed because it hit max_open
+    last_change: Instant,        // when the valve last opened or closed
+    last_tick: Instant,          // when the schedule was last advanced
+    credit: f32,                 // open time owed in s
+    open_time: Duration,         // time open since the last report
+    report_start: Instant,       // start of the current report
+    test_until: Option<Instant>, // end of a dry-run test pulse
+}
+
+impl ValveDriver {
+    pub fn new(name: &str, limits: PulseLimits, now: Instant) -> Self {
+        return ValveDriver {
+            name: String::from(name),
+            limits,
+            duty: 0.0,
+            open: false,
+            cooling: false,
+            last_change: now,
+            last_tick: now,
+            credit: 0.0,
+            open_time: Duration::ZERO,
+            report_start: now,
+            test_until: None,
+        };
+    }
+
+    pub fn set_duty(&mut self, duty: f32) {
+        // set the fraction of time the valve should be open, unless a test
+        // pulse has it
+        if self.test_until.is_some() {
+            return;
+        }
+        self.duty = duty.clamp(0.0, 1.0);
+        if self.duty == 0.0 {
+            // closed means closed, forget what was owed
+            self.credit = self.credit.min(0.0);
+        }
+    }
+
+    pub fn duty(&self) -> f32 {
+        // the duty in effect, as last set
+        return self.duty;
+    }
+
+    pub fn is_open(&self) -> bool {
+        return self.open;
+    }
+
+    pub fn test_pulse(&mut self, length: Duration, now: Instant) {
+        // hold the valve fully open for a pre-flight test, then closed,
+        // ignoring the duty asked for until it has closed again
+        self.test_until = None;
+        self.set_duty(1.0);
+        self.test_until = Some(now + length);
+    }
+
+    pub fn is_testing(&self) -> bool {
+        return self.test_until.is_some();
+    }
+
+    pub fn tick(&mut self, now: Instant) -> Option<ValveEvent> {
+        // advance the schedule, returning the event if the valve should
+        // open or close now
+        let test_over = matches!(self.test_until, Some(until) if now >= until);
+        if test_over {
+            self.duty = 0.0;
+            self.credit = self.credit.min(0.0);
+            if !self.open {
+                self.test_until = None;
+            }
+        }
+        let dt = now.saturating_duration_since(self.last_tick);
+        self.last_tick = now;
+        self.credit += self.duty * dt.as_secs_f32();
+        if self.open {
+            self.credit -= dt.as_secs_f32();
+            self.open_time += dt;
+        }
+        // don't build up more than one maximum pulse, or pay back more than
+        // one minimum pulse
+        self.credit = self.credit.clamp(
+            -self.limits.min_on.as_secs_f32(),
+            self.limits.max_open.as_secs_f32(),
+        );
+
+        let since_change = now.saturating_duration_since(self.last_change);
+        if self.open {
+            if since_change >= self.limits.max_open {
+                warn!(
+                    "[{}] Open for {:?}, closing to cool down for {:?}",
+                    self.name, since_change, self.limits.cooldown
+                );
+                self.cooling = true;
+                return Some(self.close(now));
+            }
+            if since_change >= self.limits.min_on && self.credit <= 0.0 {
+                return Some(self.close(now));
+            }
+        } else {
+            let min_off = if self.cooling {
+                self.limits.min_off.max(self.limits.cooldown)
+            } else {
+                self.limits.min_off
+            };
+            if since_change >= min_off && self.credit >= self.limits.min_on.as_secs_f32() {
+                self.cooling = false;
+                self.open = true;
+                self.last_change = now;
+                debug!("[{}] Open", self.name);
+                return Some(ValveEvent::Open(now));
+            }
+        }
+        return None;
+    }
+
+    fn close(&mut self, now: Instant) -> ValveEvent {
+        self.open = false;
+        self.last_change = now;
+        debug!("[{}] Close", self.name);
+        return ValveEvent::Close(now);
+    }
+
+    pub fn take_delivered_duty(&mut self, now: Instant) -> f32 {
+        // fraction of time actually open since the last call
+        let elapsed = now.saturating_duration_since(self.report_start);
+        let duty = if elapsed.is_zero() {
+            0.0
+        } else {
+            self.open_time.as_secs_f32() / elapsed.as_secs_f32()
+        };
+        self.open_time = Duration::ZERO;
+        self.report_start = now;
+        return duty;
+    }
+}
@@ -0,0 +1,213 @@
+// ----------------------------------------------------------------------------
+// Wind
+// ----
+// Horizontal wind by altitude layer, from a forecast or measured on the way
+// up. Directions are the meteorological kind: where the wind blows from, in
+// degrees clockwise from true north.
+// ----------------------------------------------------------------------------
+
+use std::path::Path;
+
+use toml::Value;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WindLayer {
+    pub base: f32,      // bottom of the layer in m
+    pub top: f32,       // top of the layer in m
+    pub speed: f32,     // wind speed in m/s
+    pub direction: f32, // where it blows from in degrees from north
+    pub samples: u32,   // measurements that went into it, 0 for a forecast
+}
+
+impl WindLayer {
+    pub fn velocity(&self) -> (f32, f32) {
+        // where the air is going, east and north in m/s
+        let from = self.direction.to_radians();
+        return (-self.speed * from.sin(), -self.speed * from.cos());
+    }
+
+    fn middle(&self) -> f32 {
+        return 0.5 * (self.base + self.top);
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WindProfile {
+    layers: Vec<WindLayer>, // lowest first, not overlapping
+}
+
+impl WindProfile {
+    pub fn new(mut layers: Vec<WindLayer>) -> Result<Self, String> {
+        layers.sort_by(|a, b| a.base.total_cmp(&b.base));
+        for layer in &layers {
+            if layer.top <= layer.base {
+                return Err(format!(
+                    "layer from {} m must end above its base, not at {} m",
+                    layer.base, layer.top
+                ));
+            }
+            if layer.speed < 0.0 {
+                return Err(format!("layer from {} m has a negative speed", layer.base));
+            }
+        }
+        if let Some(w) = layers.windows(2).find(|w| w[1].base < w[0].top) {
+            return Err(format!(
+                "layers from {} m and {} m overlap",
+                w[0].base, w[1].base
+            ));
+        }
+        return Ok(WindProfile { layers });
+    }
+
+    pub fn calm() -> Self {
+        // no wind anywhere, for when nothing better is known
+        return WindProfile::default();
+    }
+
+    pub fn layers(&self) -> &[WindLayer] {
+        return &self.layers;
+    }
+
+    pub fn velocity_at(&self, altitude: f32) -> (f32, f32) {
+        // where the air is going at an altitude, east and north in m/s,
+        // interpolated between the middles of the layers and held beyond
+        // the lowest and highest
+        let (first, last) = match (self.layers.first(), self.layers.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return (0.0, 0.0),
+        };
+        if altitude <= first.middle() {
+            return first.velocity();
+        }
+        if altitude >= last.middle() {
+            return last.velocity();
+        }
+        let i = self
+            .layers
+            .iter()
+            .rposition(|layer| layer.middle() <= altitude)
+            .unwrap_or(0);
+        let (below, above) = (&self.layers[i], &self.layers[i + 1]);
+        let fraction = (altitude - below.middle()) / (above.middle() - below.middle());
+        let (below_east, below_north) = below.velocity();
+        let (above_east, above_north) = above.velocity();
+        return (
+            below_east + fraction * (above_east - below_east),
+            below_north + fraction * (above_north - below_north),
+        );
+    }
+
+    pub fn overlay(&self, measured: &WindProfile) -> WindProfile {
+        // measured winds where there are any, this profile above and below
+        // them, cut back where a layer reaches into the measured span
+        let (lowest, highest) = match (measured.layers.first(), measured.layers.last()) {
+            (Some(first), Some(last)) => (first.base, last.top),
+            _ => return self.clone(),
+        };
+        let mut layers = measured.layers.clone();
+        for layer in &self.layers {
+            if layer.base < lowest {
+                layers.push(WindLayer {
+                    top: layer.top.min(lowest),
+                    ..*layer
+                });
+            }
+            if layer.top > highest {
+                layers.push(WindLayer {
+                    base: layer.base.max(highest),
+                    ..*layer
+                });
+            }
+        }
+        // both were valid and none of these overlap
+        return WindProfile::new(layers).unwrap_or_default();
+    }
+
+    pub fn from_toml(value: &Value) -> Result<Self, String> {
+        // read a profile from parallel arrays, one entry per layer
+        let column = |key: &str| -> Result<Vec<f64>, String> {
+            let array = value
+                .get(key)
+                .and_then(|v| v.as_array())
+                .ok_or(format!("missing array {}", key))?;
+            return array
+                .iter()
+                .map(|v| {
+                    v.as_float()
+                        .or(v.as_integer().map(|i| i as f64))
+                        .ok_or(format!("{} must hold numbers", key))
+                })
+                .collect();
+        };
+        let base = column("base_m")?;
+        let top = column("top_m")?;
+        let speed = column("speed_m_s")?;
+        let direction = column("direction_deg")?;
+        // a forecast has no samples
+        let samples = match value.get("samples") {
+            Some(_) => column("samples")?,
+            None => vec![0.0; base.len()],
+        };
+        let n = base.len();
+        if [top.len(), speed.len(), direction.len(), samples.len()]
+            .iter()
+            .any(|len| *len != n)
+        {
+            return Err(String::from("every column needs one entry per layer"));
+        }
+        let layers = (0..n)
+            .map(|i| WindLayer {
+                base: base[i] as f32,
+                top: top[i] as f32,
+                speed: speed[i] as f32,
+                direction: direction[i] as f32,
+                samples: samples[i].max(0.0) as u32,
+            })
+            .collect();
+        return WindProfile::new(layers);
+    }
+
+    pub fn to_toml(&self) -> Value {
+        // the parallel arrays from_toml reads back
+        let column = |f: &dyn Fn(&WindLayer) -> f32, places: i32| -> Value {
+            let scale = 10f64.powi(places);
+            return Value::Array(
+                self.layers
+                    .iter()
+                    .map(|layer| Value::Float((f(layer) as f64 * scale).round() / scale))
+                    .collect(),
+            );
+        };
+        let mut table = toml::value::Table::new();
+        table.insert(String::from("base_m"), column(&|l| l.base, 1));
+        table.insert(String::from("top_m"), column(&|l| l.top, 1));
+        table.insert(String::from("speed_m_s"), column(&|l| l.speed, 2));
+        table.insert(String::from("direction_deg"), column(&|l| l.direction, 1));
+        table.insert(
+            String::from("samples"),
+            Value::Array(
+                self.layers
+                    .iter()
+                    .map(|layer| Value::Integer(layer.samples as i64))
+                    .collect(),
+            ),
+        );
+        return Value::Table(table);
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = toml::to_string(&self.to_toml())
+            .map_err(|e| format!("can't write the profile as TOML: {}", e))?;
+        return std::fs::write(path, contents)
+            .map_err(|e| format!("can't write {}: {}", path.display(), e));
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+        let value = contents
+            .parse::<Value>()
+            .map_err(|e| format!("{} is not valid TOML: {}", path.display(), e))?;
+        return WindProfile::from_toml(&value).map_err(|e| format!("{}: {}", path.display(), e));
+    }
+}
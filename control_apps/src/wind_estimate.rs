@@ -0,0 +1,153 @@
+// ----------------------------------------------------------------------------
+// Wind Estimate
+// -------------
+// Measures the winds on the way up. A balloon drifts with the air around it,
+// so its horizontal GPS velocity is the wind at its altitude. Successive
+// fixes are differenced into velocities and averaged by altitude layer into a
+// measured wind profile. A profile saved before a restart can be restored to
+// carry on from.
+// ----------------------------------------------------------------------------
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use crate::atmosphere::EARTH_RADIUS;
+use crate::wind::{WindLayer, WindProfile};
+
+#[derive(Copy, Clone, Debug)]
+pub struct SurveySettings {
+    pub layer_depth: f32,       // thickness of each altitude layer in m
+    pub min_samples: u32,       // velocities a layer needs before it's trusted
+    pub min_baseline: Duration, // shortest time between fixes to difference
+    pub max_gap: Duration,      // longest time between fixes to difference
+    pub max_speed: f32,         // faster than this in m/s is a bad fix
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Fix {
+    latitude: f32,  // degrees north
+    longitude: f32, // degrees east
+    altitude: f32,  // in m
+    time: Instant,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+struct Bin {
+    east: f32,    // sum of east velocities in m/s
+    north: f32,   // sum of north velocities in m/s
+    samples: u32, // velocities summed
+}
+
+#[derive(Clone, Debug)]
+pub struct WindEstimator {
+    settings: SurveySettings,
+    anchor: Option<Fix>,      // fix the next velocity is measured from
+    bins: BTreeMap<i32, Bin>, // by layer index, counting up from 0 m
+    samples: u32,             // velocities taken so far
+}
+
+impl WindEstimator {
+    pub fn new(settings: SurveySettings) -> Self {
+        return WindEstimator {
+            settings,
+            anchor: None,
+            bins: BTreeMap::new(),
+            samples: 0,
+        };
+    }
+
+    pub fn samples(&self) -> u32 {
+        return self.samples;
+    }
+
+    pub fn record(&mut self, latitude: f32, longitude: f32, altitude: f32, time: Instant) {
+        // take a GPS fix, turning it into a velocity once it's far enough in
+        // time from the last one. Too long a gap starts over from this fix.
+        let fix = Fix {
+            latitude,
+            longitude,
+            altitude,
+            time,
+        };
+        let anchor = match self.anchor {
+            Some(v) => v,
+            None => {
+                self.anchor = Some(fix);
+                return;
+            }
+        };
+        let dt = time.saturating_duration_since(anchor.time);
+        if dt < self.settings.min_baseline {
+            return;
+        }
+        self.anchor = Some(fix);
+        if dt > self.settings.max_gap {
+            return;
+        }
+
+        // small enough distances to treat the ground as flat
+        let dt = dt.as_secs_f32();
+        let mean_latitude = 0.5 * (latitude + anchor.latitude);
+        // wrap across the date line only when needed, f32 can't spare the
+        // precision otherwise
+        let mut d_longitude = longitude - anchor.longitude;
+        if d_longitude > 180.0 {
+            d_longitude -= 360.0;
+        } else if d_longitude < -180.0 {
+            d_longitude += 360.0;
+        }
+        let east = d_longitude.to_radians() * EARTH_RADIUS * mean_latitude.to_radians().cos() / dt;
+        let north = (latitude - anchor.latitude).to_radians() * EARTH_RADIUS / dt;
+        if (east * east + north * north).sqrt() > self.settings.max_speed {
+            return; // a jump in position, not wind
+        }
+
+        // the velocity belongs halfway between the two fixes
+        let middle = 0.5 * (altitude + anchor.altitude);
+        let index = (middle / self.settings.layer_depth).floor() as i32;
+        let bin = self.bins.entry(index).or_default();
+        bin.east += east;
+        bin.north += north;
+        bin.samples += 1;
+        self.samples += 1;
+    }
+
+    pub fn restore(&mut self, measured: &WindProfile) {
+        // take back the layers of a saved profile as if their samples had
+        // just been measured, forecast layers have none to give
+        for layer in measured.layers().iter().filter(|layer| layer.samples > 0) {
+            let middle = 0.5 * (layer.base + layer.top);
+            let index = (middle / self.settings.layer_depth).floor() as i32;
+            let (east, north) = layer.velocity();
+            let bin = self.bins.entry(index).or_default();
+            bin.east += east * layer.samples as f32;
+            bin.north += north * layer.samples as f32;
+            bin.samples += layer.samples;
+            self.samples += layer.samples;
+        }
+    }
+
+    pub fn profile(&self) -> WindProfile {
+        // the average wind in each layer with enough samples
+        let depth = self.settings.layer_depth;
+        let layers = self
+            .bins
+            .iter()
+            .filter(|(_, bin)| bin.samples >= self.settings.min_samples.max(1))
+            .map(|(index, bin)| {
+                let east = bin.east / bin.samples as f32;
+                let north = bin.north / bin.samples as f32;
+                WindLayer {
+                    base: *index as f32 * depth,
+                    top: (*index + 1) as f32 * depth,
+                    speed: (east * east + north * north).sqrt(),
+                    // where it's blowing from, opposite to where it goes
+                    direction: (-east).atan2(-north).to_degrees().rem_euclid(360.0),
+                    samples: bin.samples,
+                }
+            })
+            .collect();
+        // one layer per bin, so they never overlap
+        return WindProfile::new(layers).unwrap_or_default();
+    }
+}
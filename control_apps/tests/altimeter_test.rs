@@ -0,0 +1,30 @@
+use control_apps::altimeter::BaroAltimeter;
+use control_apps::atmosphere::{Atmosphere, StandardAtmosphere};
+
+#[test]
+fn test_standard_qnh() {
+    let altimeter = BaroAltimeter::default();
+    let pressure = StandardAtmosphere.pressure(24_000.0);
+    let baro = altimeter.derive(pressure, Some(StandardAtmosphere.temperature(24_000.0)));
+    assert!((baro.altitude - 24_000.0).abs() < 1.0);
+    assert!((baro.pressure_altitude - baro.altitude).abs() < 0.01);
+    // geopotential meters are a little shorter than geometric ones up here
+    assert!(baro.geopotential_altitude < baro.altitude);
+    assert!(baro.temperature_deviation.unwrap().abs() < 0.01);
+}
+
+#[test]
+fn test_low_qnh() {
+    // with low pressure at the launch site a given pressure is found lower down
+    let altimeter = BaroAltimeter::new(Box::new(StandardAtmosphere), 100_325.0);
+    let pressure = StandardAtmosphere.pressure(0.0) - 1_000.0;
+    let baro = altimeter.derive(pressure, None);
+    // 10 hPa is roughly 84 m near the ground
+    assert!(baro.altitude.abs() < 1.0, "{}", baro.altitude);
+    assert!(
+        (baro.pressure_altitude - 84.0).abs() < 2.0,
+        "{}",
+        baro.pressure_altitude
+    );
+    assert_eq!(baro.temperature_deviation, None);
+}
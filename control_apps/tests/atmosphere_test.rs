@@ -0,0 +1,89 @@
+use control_apps::atmosphere::{
+    Atmosphere, OffsetAtmosphere, SoundingAtmosphere, SoundingLevel, StandardAtmosphere,
+};
+
+fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+    // relative tolerance
+    assert!(
+        ((actual - expected) / expected).abs() <= tolerance,
+        "{} is not within {} of {}",
+        actual,
+        tolerance,
+        expected
+    );
+}
+
+#[test]
+fn test_standard_atmosphere_table() {
+    // geometric altitude (m), temperature (K), pressure (Pa), density (kg/m^3)
+    // from the U.S. Standard Atmosphere, 1976 (NOAA-S/T 76-1562), table I
+    let table = [
+        (0.0, 288.150, 101_325.0, 1.2250),
+        (1_000.0, 281.651, 89_876.0, 1.1117),
+        (5_000.0, 255.676, 54_048.0, 0.73643),
+        (11_000.0, 216.774, 22_700.0, 0.36480),
+        (20_000.0, 216.650, 5_529.3, 0.088910),
+        (32_000.0, 228.490, 889.06, 0.013555),
+        (50_000.0, 270.650, 79.779, 0.0010269),
+    ];
+    let atmosphere = StandardAtmosphere;
+    for (altitude, temperature, pressure, density) in table {
+        assert_close(atmosphere.temperature(altitude), temperature, 1e-4);
+        assert_close(atmosphere.pressure(altitude), pressure, 1e-3);
+        assert_close(atmosphere.density(altitude), density, 1e-3);
+    }
+}
+
+#[test]
+fn test_standard_atmosphere_altitude_from_pressure() {
+    let atmosphere = StandardAtmosphere;
+    for altitude in [
+        0.0, 3_000.0, 11_000.0, 15_000.0, 24_000.0, 40_000.0, 49_000.0,
+    ] {
+        let pressure = atmosphere.pressure(altitude);
+        let found = atmosphere.altitude(pressure);
+        assert!((found - altitude).abs() < 1.0, "{} != {}", found, altitude);
+    }
+}
+
+#[test]
+fn test_offset_atmosphere() {
+    let hot_day = OffsetAtmosphere::new(Box::new(StandardAtmosphere), 15.0);
+    let standard = StandardAtmosphere;
+    assert_close(hot_day.temperature(5_000.0), 255.676 + 15.0, 1e-4);
+    assert_close(hot_day.pressure(5_000.0), standard.pressure(5_000.0), 1e-6);
+    // warmer air at the same pressure is thinner
+    assert!(hot_day.density(5_000.0) < standard.density(5_000.0));
+}
+
+#[test]
+fn test_sounding_atmosphere() {
+    let contents = "\
+# radiosonde launched near the site
+altitude_m, pressure_hpa, temperature_c
+0, 1000.0, 20.0
+1000, 900.0, 14.0
+2000, 800.0, 8.0
+";
+    let sounding = SoundingAtmosphere::parse(contents).unwrap();
+    // levels are matched exactly
+    assert_close(sounding.pressure(1_000.0), 90_000.0, 1e-6);
+    assert_close(sounding.temperature(2_000.0), 281.15, 1e-6);
+    // and interpolated between
+    assert_close(sounding.temperature(500.0), 290.15, 1e-6);
+    let pressure = sounding.pressure(500.0);
+    assert!(pressure < 100_000.0 && pressure > 90_000.0);
+    assert!((sounding.altitude(pressure) - 500.0).abs() < 1.0);
+}
+
+#[test]
+fn test_sounding_atmosphere_errors() {
+    let level = SoundingLevel {
+        altitude: 0.0,
+        pressure: 100_000.0,
+        temperature: 288.0,
+    };
+    assert!(SoundingAtmosphere::new(vec![level]).is_err());
+    assert!(SoundingAtmosphere::parse("0 1000.0\n").is_err());
+    assert!(SoundingAtmosphere::parse("0 1000.0 20.0\n1000 nine 14.0\n").is_err());
+}
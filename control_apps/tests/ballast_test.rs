@@ -0,0 +1,34 @@
+use std::time::{Duration, Instant};
+
+use control_apps::ballast::BallastEstimator;
+use control_apps::measurement::Measurement;
+
+#[test]
+fn test_follows_dump_history() {
+    let mut ballast = BallastEstimator::new(0.2, 0.2, 0.5);
+    let start = Instant::now();
+    for i in 0..=100 {
+        let t = start + Duration::from_secs(i);
+        // valve half open the whole time, load cell swinging +/- 0.3 kg
+        ballast.predict(0.005, t);
+        let truth = 5.0 - 0.005 * i as f32;
+        let swing = if i % 2 == 0 { 0.3 } else { -0.3 };
+        ballast.update(&Measurement::new(truth + swing, t));
+    }
+    let estimate = ballast.estimate().unwrap();
+    assert!((estimate.value - 4.5).abs() < 0.1, "{}", estimate.value);
+    assert!(!ballast.is_faulted());
+}
+
+#[test]
+fn test_flags_stuck_valve() {
+    let mut ballast = BallastEstimator::new(0.2, 0.2, 0.5);
+    let start = Instant::now();
+    for i in 0..=200 {
+        let t = start + Duration::from_secs(i);
+        // commanded open, but nothing comes out
+        ballast.predict(0.01, t);
+        ballast.update(&Measurement::new(5.0, t));
+    }
+    assert!(ballast.is_faulted());
+}
@@ -0,0 +1,33 @@
+use std::time::{Duration, Instant};
+
+use control_apps::budget::{slew_limit, Budget};
+
+#[test]
+fn test_rolling_window() {
+    let start = Instant::now();
+    let mut budget = Budget::new("Vent", 600.0, Duration::from_secs(3600));
+    // wide open for ten minutes uses it all up
+    for i in 1..=600 {
+        budget.consume(1.0, start + Duration::from_secs(i));
+    }
+    assert_eq!(budget.remaining(), 0.0);
+    assert!(!budget.allow());
+    // an hour after the first of it was spent, it starts coming back
+    budget.consume(0.0, start + Duration::from_secs(3601 + 60));
+    assert!(
+        (budget.remaining() - 60.0).abs() < 1e-3,
+        "{}",
+        budget.remaining()
+    );
+    assert!(budget.allow());
+}
+
+#[test]
+fn test_slew_limit() {
+    let dt = Duration::from_secs(1);
+    // opens no faster than the rate
+    assert!((slew_limit(0.0, 1.0, 0.2, dt) - 0.2).abs() < 1e-6);
+    assert!((slew_limit(0.5, 0.6, 0.2, dt) - 0.6).abs() < 1e-6);
+    // closes right away
+    assert_eq!(slew_limit(1.0, 0.0, 0.2, dt), 0.0);
+}
@@ -0,0 +1,107 @@
+use std::time::{Duration, Instant};
+
+use control_apps::burst::{Anomaly, BurstDetector, BurstLimits};
+use control_apps::measurement::Measurement;
+
+fn detector() -> BurstDetector {
+    BurstDetector::new(BurstLimits {
+        burst_rate: -15.0,
+        burst_deceleration: 2.0,
+        descent_rate: -3.0,
+        confirm: Duration::from_secs(5),
+        leak_confirm: Duration::from_secs(20),
+    })
+}
+
+fn fly(
+    burst: &mut BurstDetector,
+    start: Instant,
+    rates: &[f32],
+    free_lift: f32,
+    commanded: bool,
+) -> Option<Anomaly> {
+    let mut found = None;
+    for (i, rate) in rates.iter().enumerate() {
+        let t = start + Duration::from_secs(i as u64);
+        found = found.or(burst.update(&Measurement::new(*rate, t), free_lift, commanded));
+    }
+    found
+}
+
+#[test]
+fn test_burst() {
+    let mut burst = detector();
+    // steady ascent, then the balloon goes
+    let mut rates = vec![5.0; 10];
+    rates.extend([0.0, -10.0, -25.0, -30.0, -30.0, -30.0, -29.0, -28.0, -28.0]);
+    assert_eq!(
+        fly(&mut burst, Instant::now(), &rates, 1.0, false),
+        Some(Anomaly::Burst)
+    );
+    assert_eq!(burst.detected(), Some(Anomaly::Burst));
+}
+
+#[test]
+fn test_leak() {
+    // slowly coming down, though the lift gas model says we shouldn't
+    let rates = vec![-4.0; 30];
+    assert_eq!(
+        fly(&mut detector(), Instant::now(), &rates, 0.5, false),
+        Some(Anomaly::UncommandedDescent)
+    );
+    // fine if we vented enough to lose the lift, or meant to come down
+    assert_eq!(
+        fly(&mut detector(), Instant::now(), &rates, -0.5, false),
+        None
+    );
+    assert_eq!(
+        fly(&mut detector(), Instant::now(), &rates, 0.5, true),
+        None
+    );
+}
+
+#[test]
+fn test_brief_dip() {
+    // a gust, not a burst
+    let rates = [5.0, 5.0, -20.0, -20.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0];
+    assert_eq!(
+        fly(&mut detector(), Instant::now(), &rates, 1.0, false),
+        None
+    );
+}
+
+#[test]
+fn test_leak_must_hold() {
+    // a short sag isn't enough to believe the lift gas model over the
+    // ascent rate
+    let mut rates = vec![-4.0; 15];
+    rates.extend([-1.0; 15]);
+    assert_eq!(
+        fly(&mut detector(), Instant::now(), &rates, 0.5, false),
+        None
+    );
+}
+
+#[test]
+fn test_reset() {
+    let mut burst = detector();
+    let start = Instant::now();
+    let rates = vec![-4.0; 30];
+    assert_eq!(
+        fly(&mut burst, start, &rates, 0.5, false),
+        Some(Anomaly::UncommandedDescent)
+    );
+    // reported once, then latched
+    let later = start + Duration::from_secs(30);
+    assert_eq!(fly(&mut burst, later, &rates, 0.5, false), None);
+    assert_eq!(burst.detected(), Some(Anomaly::UncommandedDescent));
+    // cleared from the ground, it has to be seen again to come back
+    burst.reset();
+    assert_eq!(burst.detected(), None);
+    let later = start + Duration::from_secs(60);
+    assert_eq!(fly(&mut burst, later, &rates[..10], 0.5, false), None);
+    assert_eq!(
+        fly(&mut burst, later, &rates, 0.5, false),
+        Some(Anomaly::UncommandedDescent)
+    );
+}
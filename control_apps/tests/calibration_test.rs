@@ -0,0 +1,47 @@
+use control_apps::controller::{Calibration, Valve};
+
+fn dump_calibration() -> Calibration {
+    // dead band below PWM 0.1, then nonlinear
+    Calibration::new(
+        vec![0.0, 0.1, 0.25, 0.5, 1.0],
+        vec![0.0, 0.0, 0.003, 0.007, 0.01],
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_lookup_and_inverse() {
+    let calibration = dump_calibration();
+    assert_eq!(calibration.flow_at(0.05), 0.0);
+    assert!((calibration.flow_at(0.375) - 0.005).abs() < 1e-6);
+    // the smallest opening past the dead band
+    assert!((calibration.pwm_for(0.0015) - 0.175).abs() < 1e-6);
+    assert_eq!(calibration.pwm_for(0.0), 0.0);
+    assert_eq!(calibration.pwm_for(1.0), 1.0);
+    for pwm in [0.2, 0.3, 0.6, 0.9] {
+        let flow = calibration.flow_at(pwm);
+        assert!((calibration.pwm_for(flow) - pwm).abs() < 1e-5, "{}", pwm);
+    }
+}
+
+#[test]
+fn test_valve_commands_flow() {
+    let mut valve = Valve::new(0.0, 1.0, 0.0, 0.0, 0.0, String::from("DUMPER"));
+    valve.set_calibration(dump_calibration());
+    // half the effort is half the fully open flow
+    let pwm = valve.ctrl2pwm(0.5);
+    valve.set_pwm(pwm);
+    assert!((valve.get_flow() - 0.005).abs() < 1e-6);
+    assert!((valve.get_flow_fraction() - 0.5).abs() < 1e-5);
+}
+
+#[test]
+fn test_rejects_bad_tables() {
+    // not monotonic
+    assert!(Calibration::new(vec![0.0, 0.5, 1.0], vec![0.0, 0.02, 0.01]).is_err());
+    assert!(Calibration::new(vec![0.0, 0.5, 0.5], vec![0.0, 0.01, 0.02]).is_err());
+    // mismatched, out of range or never flowing
+    assert!(Calibration::new(vec![0.0, 1.0], vec![0.0]).is_err());
+    assert!(Calibration::new(vec![0.0, 1.5], vec![0.0, 0.01]).is_err());
+    assert!(Calibration::new(vec![0.0, 1.0], vec![0.0, 0.0]).is_err());
+}
@@ -0,0 +1,306 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use control_apps::burst::Anomaly;
+use control_apps::control_mngr::{ControlMngr, ControlMode, ControlStatus};
+use control_apps::landing::DescentModel;
+use control_apps::measurement::{Measurement, Quality};
+use control_apps::solar::sun_is_up;
+use control_apps::wind::WindProfile;
+use toml::Value;
+
+fn mngr() -> ControlMngr {
+    mngr_with(&[])
+}
+
+fn mngr_with(overrides: &[(&str, f64)]) -> ControlMngr {
+    // the config that flies, targeting 24 km from a 15 km floor
+    let mut config: Value = std::fs::read_to_string("../support_apps/config/control_config.toml")
+        .unwrap()
+        .parse()
+        .unwrap();
+    let table = config.as_table_mut().unwrap();
+    for (key, value) in overrides {
+        table.insert(key.to_string(), Value::Float(*value));
+    }
+    ControlMngr::new(config)
+}
+
+fn update(mngr: &mut ControlMngr, altitude: f32, ascent_rate: f32) {
+    let now = Instant::now();
+    mngr.update(
+        Measurement::new(altitude, now),
+        Measurement::new(ascent_rate, now),
+        Measurement::new(5.0, now),
+    );
+}
+
+#[test]
+fn test_profile_climb_to_target() {
+    let mut mngr = mngr();
+    update(&mut mngr, 10_000.0, 5.0); // Init
+    update(&mut mngr, 10_000.0, 5.0);
+    // below the floor, nothing to do yet
+    assert_eq!(mngr.get_mode(), ControlMode::Ready);
+    update(&mut mngr, 18_000.0, 5.0);
+    assert_eq!(mngr.get_mode(), ControlMode::HoldRate);
+    assert_eq!(mngr.get_target_rate(), Some(2.0));
+    // rising faster than the profile, so vent
+    update(&mut mngr, 18_005.0, 5.0);
+    assert!(mngr.get_status().contains(ControlStatus::VENT));
+    // near the target, hold altitude
+    update(&mut mngr, 23_500.0, 2.0);
+    assert_eq!(mngr.get_mode(), ControlMode::Stabilize);
+    assert_eq!(mngr.get_target_rate(), None);
+}
+
+#[test]
+fn test_profile_reentry() {
+    let mut mngr = mngr();
+    update(&mut mngr, 24_000.0, 0.0); // Init
+    update(&mut mngr, 24_000.0, 0.0);
+    assert_eq!(mngr.get_mode(), ControlMode::Stabilize);
+    // past error_ready_threshold_m but within profile_reentry_error_m
+    mngr.set_target(22_500.0);
+    update(&mut mngr, 24_000.0, 0.0);
+    assert_eq!(mngr.get_mode(), ControlMode::Stabilize);
+    mngr.set_target(21_500.0);
+    update(&mut mngr, 24_000.0, 0.0);
+    assert_eq!(mngr.get_mode(), ControlMode::HoldRate);
+    assert_eq!(mngr.get_target_rate(), Some(-3.0));
+}
+
+#[test]
+fn test_commanded_rate_hold() {
+    let mut mngr = mngr();
+    update(&mut mngr, 24_000.0, 0.0); // Init
+    update(&mut mngr, 24_000.0, 0.0);
+    assert_eq!(mngr.get_mode(), ControlMode::Stabilize);
+    // a slow descent, even though it's far from the target
+    mngr.hold_rate(-3.0);
+    update(&mut mngr, 24_000.0, -1.0);
+    assert_eq!(mngr.get_mode(), ControlMode::HoldRate);
+    assert!(mngr.get_status().contains(ControlStatus::VENT));
+    update(&mut mngr, 20_000.0, -5.0);
+    assert_eq!(mngr.get_mode(), ControlMode::HoldRate);
+    assert!(mngr.get_status().contains(ControlStatus::DUMP));
+    // coming down on the balloon, not under the parachute
+    assert_eq!(mngr.landing_descent(), DescentModel::Steady { rate: -3.0 });
+    // the same floor as altitude hold
+    update(&mut mngr, 14_000.0, -3.0);
+    assert_eq!(mngr.get_mode(), ControlMode::Abort);
+    // dumping in the same cycle it aborted
+    assert!(mngr.get_status().contains(ControlStatus::DUMP));
+    assert!(!mngr.get_status().contains(ControlStatus::VENT));
+}
+
+#[test]
+fn test_back_to_altitude_hold() {
+    let mut mngr = mngr();
+    update(&mut mngr, 24_000.0, 0.0); // Init
+    update(&mut mngr, 24_000.0, 0.0);
+    mngr.hold_rate(1.0);
+    assert_eq!(mngr.get_mode(), ControlMode::HoldRate);
+    mngr.hold_altitude();
+    assert_eq!(mngr.get_mode(), ControlMode::Stabilize);
+}
+
+#[test]
+fn test_descend_and_land() {
+    let mut mngr = mngr_with(&[("landed_time_s", 0.0)]);
+    update(&mut mngr, 24_000.0, 0.0); // Init
+    update(&mut mngr, 24_000.0, 0.0);
+    mngr.descend();
+    assert_eq!(mngr.get_mode(), ControlMode::Descend);
+    // too slow, vent
+    update(&mut mngr, 24_000.0, -1.0);
+    assert!(mngr.get_status().contains(ControlStatus::VENT));
+    // well below the floor is fine
+    update(&mut mngr, 8_000.0, -5.0);
+    assert_eq!(mngr.get_mode(), ControlMode::Descend);
+    // close to the ground, dump to slow down and never vent
+    update(&mut mngr, 1_000.0, -5.0);
+    assert!(mngr.get_status().contains(ControlStatus::DUMP));
+    update(&mut mngr, 900.0, -1.0);
+    assert!(!mngr.get_status().contains(ControlStatus::VENT));
+    // hovering on the way down isn't landing
+    update(&mut mngr, 1_400.0, 0.0);
+    assert_eq!(mngr.get_mode(), ControlMode::Descend);
+    update(&mut mngr, 1_400.0, 0.0);
+    assert_eq!(mngr.get_mode(), ControlMode::Descend);
+    // on the ground
+    update(&mut mngr, 20.0, 0.0);
+    assert_eq!(mngr.get_mode(), ControlMode::Safe);
+}
+
+#[test]
+fn test_keep_descending_below_the_floor() {
+    let mut mngr = mngr();
+    update(&mut mngr, 24_000.0, 0.0); // Init
+    update(&mut mngr, 24_000.0, 0.0);
+    mngr.descend();
+    update(&mut mngr, 10_000.0, -5.0);
+    // leaving Descend down here would abort and dump all the ballast
+    mngr.economize();
+    assert_eq!(mngr.get_mode(), ControlMode::Descend);
+    mngr.hold_rate(-3.0);
+    assert_eq!(mngr.get_mode(), ControlMode::Descend);
+    mngr.hold_altitude();
+    assert_eq!(mngr.get_mode(), ControlMode::Descend);
+    update(&mut mngr, 9_995.0, -5.0);
+    assert_eq!(mngr.get_mode(), ControlMode::Descend);
+    assert!(!mngr.get_status().contains(ControlStatus::DUMP));
+    // above the floor it can still be called off
+    update(&mut mngr, 16_000.0, -5.0);
+    mngr.economize();
+    assert_eq!(mngr.get_mode(), ControlMode::Economy);
+}
+
+#[test]
+fn test_burst_goes_safe() {
+    let mut mngr = mngr_with(&[("burst_confirm_s", 0.0)]);
+    update(&mut mngr, 24_000.0, 0.0); // Init
+    update(&mut mngr, 24_000.0, 0.0);
+    assert_eq!(mngr.get_mode(), ControlMode::Stabilize);
+    // falling fast, but ballast won't help
+    update(&mut mngr, 23_990.0, -30.0);
+    assert_eq!(mngr.get_mode(), ControlMode::Safe);
+    assert!(mngr.get_status().contains(ControlStatus::BURST));
+    assert_eq!(mngr.get_anomaly(), Some(Anomaly::Burst));
+    assert_eq!(mngr.landing_descent().to_string(), "Parachute");
+    assert!(!mngr.get_status().contains(ControlStatus::DUMP));
+}
+
+#[test]
+fn test_rearm_after_burst() {
+    let mut mngr = mngr_with(&[("burst_confirm_s", 0.0)]);
+    update(&mut mngr, 24_000.0, 0.0); // Init
+    update(&mut mngr, 24_000.0, 0.0);
+    update(&mut mngr, 23_990.0, -30.0);
+    assert_eq!(mngr.get_mode(), ControlMode::Safe);
+    // a false alarm, the ground takes control back
+    mngr.rearm();
+    assert_eq!(mngr.get_mode(), ControlMode::Ready);
+    assert_eq!(mngr.get_anomaly(), None);
+    assert!(!mngr.get_status().contains(ControlStatus::BURST));
+    update(&mut mngr, 23_990.0, 0.0);
+    assert_eq!(mngr.get_mode(), ControlMode::Stabilize);
+    // nothing to clear, so nothing changes
+    mngr.rearm();
+    assert_eq!(mngr.get_mode(), ControlMode::Stabilize);
+}
+
+#[test]
+fn test_economy_band() {
+    let mut mngr = mngr();
+    update(&mut mngr, 24_000.0, 0.0); // Init
+    update(&mut mngr, 24_000.0, 0.0);
+    mngr.economize();
+    assert_eq!(mngr.get_mode(), ControlMode::Economy);
+    // well off the target but staying in the band, so leave it be
+    update(&mut mngr, 23_000.0, 0.5);
+    assert!(mngr.get_status().contains(ControlStatus::ALTITUDE_DEADZONE));
+    assert!(!mngr
+        .get_status()
+        .intersects(ControlStatus::VENT | ControlStatus::DUMP));
+    // headed out of the top
+    update(&mut mngr, 25_500.0, 1.0);
+    assert!(mngr.get_status().contains(ControlStatus::VENT));
+    // headed out of the bottom
+    update(&mut mngr, 22_500.0, -1.0);
+    assert!(mngr.get_status().contains(ControlStatus::DUMP));
+    // 5 kg of ballast lasts a while at 0.3 kg a day, as the endurance
+    // forecast has it
+    let forecast = mngr.forecast_endurance().unwrap();
+    assert_eq!(mngr.get_expected_duration(), forecast.remaining);
+    let days = forecast.remaining.unwrap().as_secs_f32() / 86_400.0;
+    assert!(days > 0.0 && days <= 5.0 / 0.3);
+    mngr.hold_altitude();
+    assert_eq!(mngr.get_mode(), ControlMode::Stabilize);
+}
+
+#[test]
+fn test_single_gains_hold_everywhere() {
+    let mngr = mngr_with(&[("vent_kp", 1.0e-5), ("vent_ki", 1.0e-8), ("vent_kd", 0.1)]);
+    let gains = mngr.get_gains();
+    assert_eq!((gains.kp, gains.ki, gains.kd), (1.0e-5, 1.0e-8, 0.1));
+}
+
+fn sunset_longitude(altitude: f32, lead: Duration) -> f32 {
+    // somewhere on the equator the sun sets within the lead from now
+    let now = SystemTime::now();
+    let setting: Vec<f32> = (-180..180)
+        .map(|longitude| longitude as f32)
+        .filter(|longitude| {
+            sun_is_up(0.0, *longitude, altitude, now)
+                && !sun_is_up(0.0, *longitude, altitude, now + lead)
+        })
+        .collect();
+    setting[setting.len() / 2]
+}
+
+#[test]
+fn test_no_sunset_dump_while_venting() {
+    let mut mngr = mngr();
+    mngr.set_position(0.0, sunset_longitude(24_000.0, Duration::from_secs(1800)));
+    update(&mut mngr, 24_000.0, 0.0); // Init
+    update(&mut mngr, 24_000.0, 0.0);
+    assert_eq!(mngr.get_mode(), ControlMode::Stabilize);
+    // holding still, so dump ahead of sunset
+    let now = Instant::now();
+    let pwms = mngr.update(
+        Measurement::new(24_000.0, now),
+        Measurement::new(0.0, now),
+        Measurement::new(5.0, now),
+    );
+    assert!(mngr
+        .get_status()
+        .contains(ControlStatus::SOLAR_FEED_FORWARD));
+    assert!(pwms.dump_pwm > 0.0);
+    // rising past the target, the controller vents and nothing is dumped
+    let now = Instant::now();
+    let pwms = mngr.update(
+        Measurement::new(24_300.0, now),
+        Measurement::new(3.0, now),
+        Measurement::new(5.0, now),
+    );
+    assert!(mngr.get_status().contains(ControlStatus::VENT));
+    assert_eq!(pwms.dump_pwm, 0.0);
+}
+
+#[test]
+fn test_drift_skips_bad_gps_altitude() {
+    let mut mngr = mngr();
+    let start = Instant::now();
+    let fix = |seconds: u64, altitude: f32| {
+        Measurement::new(altitude, start + Duration::from_secs(seconds))
+    };
+    mngr.observe_drift(40.0, -105.0, &fix(0, 10_000.0));
+    // a wild altitude that failed validation says nothing about the wind
+    mngr.observe_drift(
+        40.001,
+        -105.0,
+        &fix(20, 90_000.0).with_quality(Quality::Bad),
+    );
+    assert!(mngr.survey_wind().is_none());
+    mngr.observe_drift(40.002, -105.0, &fix(40, 10_100.0));
+    let measured = mngr.survey_wind().unwrap();
+    assert!(measured.layers().iter().all(|layer| layer.top <= 11_000.0));
+}
+
+#[test]
+fn test_restore_measured_wind() {
+    // a restart keeps the winds measured before it
+    let mut before = mngr();
+    let start = Instant::now();
+    for i in 0..5 {
+        let time = start + Duration::from_secs(20 * i);
+        let altitude = Measurement::new(10_000.0 + 20.0 * i as f32, time);
+        before.observe_drift(40.0 + 0.001 * i as f32, -105.0, &altitude);
+    }
+    let measured = before.survey_wind().unwrap();
+    assert_eq!(measured.layers().len(), 1);
+    let mut restarted = mngr();
+    restarted.set_wind_profile(WindProfile::calm());
+    restarted.restore_wind(&measured);
+    assert_eq!(restarted.get_wind_profile(), before.get_wind_profile());
+}
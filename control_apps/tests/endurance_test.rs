@@ -0,0 +1,80 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use control_apps::endurance::{ConsumptionHistory, EnduranceModel, Limit, Rates};
+
+const HOUR: f32 = 3600.0;
+const DAY: f32 = 86_400.0;
+
+// 2024-03-20 12:00 UTC, the March equinox
+fn equinox_noon() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(1_710_936_000)
+}
+
+fn model() -> EnduranceModel {
+    EnduranceModel {
+        default_rates: Rates {
+            ballast: 0.5 / DAY,
+            gas: 0.0,
+        },
+        min_history: Duration::from_secs(3600),
+        step: Duration::from_secs(600),
+        horizon: Duration::from_secs(30 * 86_400),
+    }
+}
+
+fn hours(duration: Option<Duration>) -> f32 {
+    duration.unwrap().as_secs_f32() / HOUR
+}
+
+#[test]
+fn test_defaults_without_history() {
+    let history = ConsumptionHistory::new();
+    let endurance = model().predict(&history, 1.0, 1.0, None, 24_000.0, equinox_noon());
+    assert!((hours(endurance.remaining) - 48.0).abs() < 0.1);
+    assert_eq!(endurance.limit, Some(Limit::Ballast));
+}
+
+#[test]
+fn test_ballast_goes_at_night() {
+    // nothing dumped by day, 0.1 kg an hour by night
+    let mut history = ConsumptionHistory::new();
+    history.record(0.0, 0.0, Duration::from_secs(6 * 3600), Some(true));
+    history.record(0.6, 0.0, Duration::from_secs(6 * 3600), Some(false));
+    let model = model();
+    assert_eq!(model.rates(&history, Some(true)).ballast, 0.0);
+    assert!((model.rates(&history, Some(false)).ballast * HOUR - 0.1).abs() < 1e-6);
+    // sunset at 30 km is about 18:30 and sunrise 05:45, so the first night
+    // takes 1.13 kg and the rest goes early the next night
+    let endurance = model.predict(
+        &history,
+        1.2,
+        1.0,
+        Some((0.0, 0.0)),
+        30_000.0,
+        equinox_noon(),
+    );
+    let remaining = hours(endurance.remaining);
+    assert!(remaining > 30.0 && remaining < 32.5, "{} h", remaining);
+    assert_eq!(endurance.limit, Some(Limit::Ballast));
+    // without knowing where the sun is, it's the average
+    let average = model.predict(&history, 1.2, 1.0, None, 30_000.0, equinox_noon());
+    assert!((hours(average.remaining) - 24.0).abs() < 0.1);
+}
+
+#[test]
+fn test_lift_gas_runs_out_first() {
+    let mut history = ConsumptionHistory::new();
+    history.record(0.1, 0.01, Duration::from_secs(10 * 3600), None);
+    let endurance = model().predict(&history, 1.0, 0.05, None, 24_000.0, equinox_noon());
+    assert!((hours(endurance.remaining) - 50.0).abs() < 0.1);
+    assert_eq!(endurance.limit, Some(Limit::LiftGas));
+}
+
+#[test]
+fn test_beyond_horizon() {
+    let mut history = ConsumptionHistory::new();
+    history.record(0.0, 0.0, Duration::from_secs(10 * 3600), None);
+    let endurance = model().predict(&history, 1.0, 1.0, None, 24_000.0, equinox_noon());
+    assert_eq!(endurance.remaining, None);
+    assert_eq!(endurance.limit, None);
+}
@@ -0,0 +1,63 @@
+use std::time::{Duration, Instant};
+
+use control_apps::flight_phase::{FlightPhase, PhaseDetector, PhaseThresholds};
+use control_apps::measurement::Measurement;
+
+fn detector(start: Instant) -> PhaseDetector {
+    let thresholds = PhaseThresholds {
+        ascent_rate: 1.0,
+        float_rate: 0.5,
+        descent_rate: -1.0,
+        landed_altitude: 6000.0,
+        dwell: Duration::from_secs(30),
+    };
+    PhaseDetector::new(thresholds, start)
+}
+
+fn fly(
+    phases: &mut PhaseDetector,
+    start: Instant,
+    from_s: u64,
+    to_s: u64,
+    altitude: f32,
+    rate: f32,
+) {
+    for i in from_s..to_s {
+        let t = start + Duration::from_secs(i);
+        phases.update(&Measurement::new(altitude, t), &Measurement::new(rate, t));
+    }
+}
+
+#[test]
+fn test_whole_flight() {
+    let start = Instant::now();
+    let mut phases = detector(start);
+    fly(&mut phases, start, 0, 60, 200.0, 0.0);
+    assert_eq!(phases.phase(), FlightPhase::Pad);
+    fly(&mut phases, start, 60, 120, 1_000.0, 5.0);
+    assert_eq!(phases.phase(), FlightPhase::Ascent);
+    // stamped with when it started, not when it was believed
+    assert_eq!(phases.since(), start + Duration::from_secs(60));
+    fly(&mut phases, start, 120, 200, 24_000.0, 0.1);
+    assert_eq!(phases.phase(), FlightPhase::Float);
+    fly(&mut phases, start, 200, 300, 12_000.0, -8.0);
+    assert_eq!(phases.phase(), FlightPhase::Descent);
+    fly(&mut phases, start, 300, 400, 300.0, 0.0);
+    assert_eq!(phases.phase(), FlightPhase::Landed);
+    assert_eq!(phases.previous(), FlightPhase::Descent);
+}
+
+#[test]
+fn test_hysteresis_and_dwell() {
+    let start = Instant::now();
+    let mut phases = detector(start);
+    fly(&mut phases, start, 0, 60, 1_000.0, 5.0);
+    assert_eq!(phases.phase(), FlightPhase::Ascent);
+    // slowing, but not enough to call it floating
+    fly(&mut phases, start, 60, 200, 20_000.0, 0.8);
+    assert_eq!(phases.phase(), FlightPhase::Ascent);
+    // a short dip doesn't count as descending
+    fly(&mut phases, start, 200, 220, 20_000.0, -3.0);
+    fly(&mut phases, start, 220, 240, 20_000.0, 3.0);
+    assert_eq!(phases.phase(), FlightPhase::Ascent);
+}
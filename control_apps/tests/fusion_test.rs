@@ -0,0 +1,80 @@
+use std::time::{Duration, Instant};
+
+use control_apps::fusion::{AltitudeFusion, Source, SourceHealth, SourceModel};
+use control_apps::measurement::Measurement;
+
+fn fusion() -> AltitudeFusion {
+    let model = SourceModel {
+        noise: 5.0,
+        latency: Duration::from_secs(0),
+    };
+    AltitudeFusion::new(model, model, 0.5, 4.0, Duration::from_secs(10))
+}
+
+#[test]
+fn test_tracks_ascent() {
+    let mut fusion = fusion();
+    let start = Instant::now();
+    for i in 0..120 {
+        let t = start + Duration::from_secs(i);
+        let altitude = 1_000.0 + 5.0 * i as f32;
+        fusion.update(Source::Baro, Measurement::new(altitude, t));
+        fusion.update(Source::Gps, Measurement::new(altitude + 1.0, t));
+    }
+    let fused = fusion.estimate().unwrap();
+    assert!(
+        (fused.altitude.value - 1_595.5).abs() < 2.0,
+        "{}",
+        fused.altitude.value
+    );
+    assert!(
+        (fused.ascent_rate.value - 5.0).abs() < 0.2,
+        "{}",
+        fused.ascent_rate.value
+    );
+    assert_eq!(fusion.health(Source::Baro), SourceHealth::Ok);
+    assert_eq!(fusion.health(Source::Gps), SourceHealth::Ok);
+}
+
+#[test]
+fn test_frozen_baro_is_ignored() {
+    let mut fusion = fusion();
+    let start = Instant::now();
+    for i in 0..120 {
+        let t = start + Duration::from_secs(i);
+        let altitude = 1_000.0 + 5.0 * i as f32;
+        // baro stops updating after a minute
+        let baro = if i < 60 { altitude } else { 1_300.0 };
+        fusion.update(Source::Baro, Measurement::new(baro, t));
+        fusion.update(Source::Gps, Measurement::new(altitude, t));
+    }
+    assert_eq!(fusion.health(Source::Baro), SourceHealth::Failed);
+    assert_eq!(fusion.health(Source::Gps), SourceHealth::Ok);
+    let fused = fusion.estimate().unwrap();
+    assert!(
+        (fused.altitude.value - 1_595.0).abs() < 5.0,
+        "{}",
+        fused.altitude.value
+    );
+    assert!(fused.baro_residual.value < -200.0);
+}
+
+#[test]
+fn test_baro_stuck_within_rounding_is_frozen() {
+    let mut fusion = fusion();
+    let start = Instant::now();
+    for i in 0..120 {
+        let t = start + Duration::from_secs(i);
+        let altitude = 1_000.0 + 5.0 * i as f32;
+        // baro stops updating but its value still wobbles in the last bits
+        let baro = if i < 60 {
+            altitude
+        } else {
+            1_300.0 + 1e-4 * (i % 2) as f32
+        };
+        fusion.update(Source::Baro, Measurement::new(baro, t));
+        fusion.update(Source::Gps, Measurement::new(altitude, t));
+    }
+    assert_eq!(fusion.health(Source::Baro), SourceHealth::Failed);
+    assert_eq!(fusion.health(Source::Gps), SourceHealth::Ok);
+}
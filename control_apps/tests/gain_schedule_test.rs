@@ -0,0 +1,65 @@
+use control_apps::controller::{GainSchedule, Gains, ScheduleKey, Valve};
+
+fn density_schedule() -> GainSchedule {
+    GainSchedule::new(
+        ScheduleKey::Density,
+        vec![0.02, 0.1, 0.4],
+        vec![4.0, 2.0, 1.0],
+        vec![0.4, 0.2, 0.1],
+        vec![40.0, 20.0, 10.0],
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_interpolates_between_breakpoints() {
+    let schedule = density_schedule();
+    // altitude doesn't matter when scheduling by density
+    let gains = schedule.gains_at(0.0, 0.06);
+    assert!((gains.kp - 3.0).abs() < 1e-5);
+    assert!((gains.ki - 0.3).abs() < 1e-6);
+    assert!((gains.kd - 30.0).abs() < 1e-4);
+    // held at the ends
+    assert_eq!(schedule.gains_at(0.0, 0.001).kp, 4.0);
+    assert_eq!(schedule.gains_at(0.0, 1.2).kp, 1.0);
+}
+
+#[test]
+fn test_valve_switches_gains() {
+    let mut valve = Valve::new(0.0, 1.0, 1.0, 1.0, 1.0, String::from("DUMPER"));
+    valve.set_schedule(density_schedule());
+    let gains = valve.schedule_gains(30_000.0, 0.02);
+    assert_eq!(
+        gains,
+        Gains {
+            kp: 4.0,
+            ki: 0.4,
+            kd: 40.0
+        }
+    );
+    assert_eq!(valve.get_gains(), gains);
+}
+
+#[test]
+fn test_rejects_bad_schedules() {
+    let gains = || vec![1.0, 1.0];
+    assert!(GainSchedule::new(
+        ScheduleKey::Altitude,
+        vec![2.0, 1.0],
+        gains(),
+        gains(),
+        gains()
+    )
+    .is_err());
+    assert!(GainSchedule::new(
+        ScheduleKey::Altitude,
+        vec![1.0, 2.0],
+        vec![1.0],
+        gains(),
+        gains()
+    )
+    .is_err());
+    assert!(GainSchedule::new(ScheduleKey::Altitude, vec![], vec![], vec![], vec![]).is_err());
+    assert!("pressure".parse::<ScheduleKey>().is_err());
+    assert_eq!("altitude".parse::<ScheduleKey>(), Ok(ScheduleKey::Altitude));
+}
@@ -0,0 +1,158 @@
+use control_apps::atmosphere::StandardAtmosphere;
+use control_apps::landing::{DescentModel, LandingModel};
+use control_apps::wind::{WindLayer, WindProfile};
+
+fn model() -> LandingModel {
+    LandingModel {
+        ground_altitude: 0.0,
+        step: 250.0,
+        wind_sigma: 3.0,
+        wind_layer: 500.0,
+        rate_sigma: 0.1,
+    }
+}
+
+fn venting() -> DescentModel {
+    DescentModel::Venting {
+        rate: -5.0,
+        landing_rate: -5.0,
+        flare_height: 500.0,
+    }
+}
+
+fn uniform(speed: f32, direction: f32) -> WindProfile {
+    WindProfile::new(vec![WindLayer {
+        base: 0.0,
+        top: 30_000.0,
+        speed,
+        direction,
+        samples: 0,
+    }])
+    .unwrap()
+}
+
+#[test]
+fn test_calm_air_lands_below() {
+    let prediction = model()
+        .predict(
+            40.0,
+            -105.0,
+            10_000.0,
+            &venting(),
+            &WindProfile::calm(),
+            &StandardAtmosphere,
+        )
+        .unwrap();
+    assert!((prediction.latitude - 40.0).abs() < 1e-5);
+    assert!((prediction.longitude + 105.0).abs() < 1e-5);
+    // 10 km at 5 m/s
+    assert!((prediction.time_to_land.as_secs_f32() - 2000.0).abs() < 1.0);
+}
+
+#[test]
+fn test_drifts_downwind() {
+    // 10 m/s from the west for 2000 s is 20 km east
+    let prediction = model()
+        .predict(
+            0.0,
+            0.0,
+            10_000.0,
+            &venting(),
+            &uniform(10.0, 270.0),
+            &StandardAtmosphere,
+        )
+        .unwrap();
+    assert!((prediction.drift.0 - 20_000.0).abs() < 1.0);
+    assert!(prediction.drift.1.abs() < 1.0);
+    assert!(prediction.longitude > 0.17 && prediction.longitude < 0.19);
+    // longest along the drift
+    assert!(prediction.ellipse.semi_major > prediction.ellipse.semi_minor);
+    assert!((prediction.ellipse.bearing - 90.0).abs() < 0.1);
+}
+
+#[test]
+fn test_ellipse_ignores_the_step() {
+    // the wind error belongs to the layer, so finer steps through the same
+    // layers give the same ellipse
+    let parachute = DescentModel::Parachute {
+        mass: 2.5,
+        drag_area: 1.0,
+    };
+    for descent in [venting(), parachute] {
+        let predict = |step: f32, altitude: f32| {
+            let model = LandingModel { step, ..model() };
+            model
+                .predict(
+                    0.0,
+                    0.0,
+                    altitude,
+                    &descent,
+                    &uniform(10.0, 270.0),
+                    &StandardAtmosphere,
+                )
+                .unwrap()
+                .ellipse
+        };
+        for altitude in [10_000.0, 10_123.0] {
+            let coarse = predict(250.0, altitude);
+            let fine = predict(125.0, altitude);
+            let tolerance = 0.01 * coarse.semi_minor;
+            assert!((coarse.semi_minor - fine.semi_minor).abs() < tolerance);
+            assert!((coarse.semi_major - fine.semi_major).abs() < tolerance);
+        }
+    }
+    // 20 layers of 100 s each at 3 m/s
+    let ellipse = model()
+        .predict(
+            0.0,
+            0.0,
+            10_000.0,
+            &venting(),
+            &WindProfile::calm(),
+            &StandardAtmosphere,
+        )
+        .unwrap()
+        .ellipse;
+    assert!((ellipse.semi_minor - 300.0 * 20.0f32.sqrt()).abs() < 1.0);
+}
+
+#[test]
+fn test_parachute_slows_near_the_ground() {
+    let parachute = DescentModel::Parachute {
+        mass: 2.5,
+        drag_area: 1.0,
+    };
+    let high = parachute.descent_rate(20_000.0, 0.089);
+    let low = parachute.descent_rate(100.0, 1.225);
+    assert!(high > 3.0 * low);
+    assert!(model()
+        .predict(
+            0.0,
+            0.0,
+            -10.0,
+            &parachute,
+            &WindProfile::calm(),
+            &StandardAtmosphere
+        )
+        .is_none());
+}
+
+#[test]
+fn test_wind_profile() {
+    let layer = |base: f32, top: f32, speed: f32| WindLayer {
+        base,
+        top,
+        speed,
+        direction: 0.0,
+        samples: 0,
+    };
+    // out of order is fine, overlapping isn't
+    let profile =
+        WindProfile::new(vec![layer(1000.0, 2000.0, 20.0), layer(0.0, 1000.0, 10.0)]).unwrap();
+    assert_eq!(profile.layers()[0].base, 0.0);
+    assert!(WindProfile::new(vec![layer(0.0, 1500.0, 10.0), layer(1000.0, 2000.0, 20.0)]).is_err());
+    // from the north blows south, interpolated between the middles
+    assert!((profile.velocity_at(0.0).1 + 10.0).abs() < 1e-4);
+    assert!((profile.velocity_at(1000.0).1 + 15.0).abs() < 1e-4);
+    assert!((profile.velocity_at(5000.0).1 + 20.0).abs() < 1e-4);
+}
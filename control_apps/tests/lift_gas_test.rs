@@ -0,0 +1,59 @@
+use std::time::{Duration, Instant};
+
+use control_apps::atmosphere::{Atmosphere, StandardAtmosphere};
+use control_apps::lift_gas::{BalloonModel, LiftGasEstimator, HELIUM_MOLAR_MASS};
+
+fn estimator(initial: f32) -> LiftGasEstimator {
+    let model = BalloonModel {
+        gas_molar_mass: HELIUM_MOLAR_MASS,
+        vent_area: 1.0e-3,
+        vent_head: 1.5,
+        volume: 100.0,
+        system_mass: 3.0,
+    };
+    LiftGasEstimator::new(model, Box::new(StandardAtmosphere), initial)
+}
+
+#[test]
+fn test_venting() {
+    let mut lift_gas = estimator(0.8);
+    let start = Instant::now();
+    lift_gas.update(0.0, 24_000.0, 1.0, start);
+    let before = lift_gas.report();
+    // closed valve, nothing lost
+    lift_gas.update(0.0, 24_000.0, 1.0, start + Duration::from_secs(60));
+    assert_eq!(lift_gas.report().remaining, before.remaining);
+
+    // ten minutes half open
+    let flow = lift_gas.vent_flow(24_000.0);
+    lift_gas.update(0.5, 24_000.0, 1.0, start + Duration::from_secs(660));
+    let after = lift_gas.report();
+    assert!(
+        (after.vented - 0.5 * flow * 600.0).abs() < 1e-4,
+        "{}",
+        after.vented
+    );
+    assert_eq!(after.vent_open_time, Duration::from_secs(300));
+    assert!(after.free_lift < before.free_lift);
+}
+
+#[test]
+fn test_equilibrium_altitude() {
+    let mut lift_gas = estimator(0.8);
+    lift_gas.update(0.0, 0.0, 1.0, Instant::now());
+    let report = lift_gas.report();
+    assert!(report.free_lift > 0.0);
+    // floats where the displaced air weighs as much as everything carried
+    let altitude = report.equilibrium_altitude.unwrap();
+    let density = StandardAtmosphere.density(altitude);
+    assert!(
+        (density * 100.0 - (3.0 + 1.0 + 0.8)).abs() < 0.01,
+        "{}",
+        altitude
+    );
+
+    // too much ballast to get off the ground
+    lift_gas.update(0.0, 0.0, 5.0, Instant::now());
+    assert!(lift_gas.report().free_lift < 0.0);
+    assert_eq!(lift_gas.report().equilibrium_altitude, None);
+}
@@ -0,0 +1,62 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use control_apps::solar::{horizon_dip, sun_position, FeedForward, SolarModel};
+
+fn utc(unix_s: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(unix_s)
+}
+
+// 2024-03-20, the March equinox
+const EQUINOX_0530: u64 = 1_710_912_600;
+const EQUINOX_1200: u64 = 1_710_936_000;
+const EQUINOX_1810: u64 = 1_710_958_200;
+
+fn model() -> SolarModel {
+    SolarModel {
+        lead: Duration::from_secs(1800),
+        sunset_ballast: 0.36,
+        sunrise_vent: 90.0,
+    }
+}
+
+#[test]
+fn test_sun_position() {
+    // 2024-06-21 12:00 UTC, the sun is over the tropic of cancer at noon
+    let noon = sun_position(0.0, 0.0, utc(1_718_971_200));
+    assert!((noon.elevation - 66.56).abs() < 0.1);
+    assert!(noon.azimuth < 5.0 || noon.azimuth > 355.0); // looking north
+    let midnight = sun_position(0.0, 0.0, utc(1_718_928_000));
+    assert!((midnight.elevation + 66.56).abs() < 0.1);
+    // 2024-12-21 12:00 UTC from 40 N, low in the south
+    let winter = sun_position(40.0, 0.0, utc(1_734_782_400));
+    assert!((winter.elevation - 26.56).abs() < 0.1);
+    assert!((winter.azimuth - 180.0).abs() < 2.0);
+    // rising in the east and setting in the west on the equinox
+    assert!((sun_position(0.0, 0.0, utc(EQUINOX_0530)).azimuth - 90.0).abs() < 1.0);
+    assert!((sun_position(0.0, 0.0, utc(EQUINOX_1810)).azimuth - 270.0).abs() < 1.0);
+}
+
+#[test]
+fn test_horizon_dip() {
+    assert_eq!(horizon_dip(0.0), 0.0);
+    assert!((horizon_dip(30_000.0) - 5.56).abs() < 0.01);
+}
+
+#[test]
+fn test_feed_forward() {
+    let model = model();
+    // sunset at 30 km is about 18:29, so dump ahead of it
+    let sunset = model.feed_forward(0.0, 0.0, 30_000.0, utc(EQUINOX_1810));
+    assert_eq!(sunset.vent, 0.0);
+    assert!((sunset.dump - 0.36 / 1800.0).abs() < 1e-9);
+    // sunrise at 30 km is about 05:45, so vent ahead of it
+    let sunrise = model.feed_forward(0.0, 0.0, 30_000.0, utc(EQUINOX_0530));
+    assert!((sunrise.vent - 0.05).abs() < 1e-6);
+    assert_eq!(sunrise.dump, 0.0);
+    // on the ground the sun rises later, so it's too early to start
+    let ground = model.feed_forward(0.0, 0.0, 0.0, utc(EQUINOX_0530));
+    assert_eq!(ground, FeedForward::default());
+    // nothing to do in the middle of the day
+    let noon = model.feed_forward(0.0, 0.0, 30_000.0, utc(EQUINOX_1200));
+    assert_eq!(noon, FeedForward::default());
+}
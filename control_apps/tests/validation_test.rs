@@ -0,0 +1,50 @@
+use std::time::{Duration, Instant};
+
+use control_apps::measurement::{Measurement, Quality};
+use control_apps::validation::{ChannelLimits, ChannelValidator};
+
+fn altitude_validator() -> ChannelValidator {
+    let limits = ChannelLimits {
+        min: -500.0,
+        max: 45_000.0,
+        max_rate: 60.0,
+        median_window: 5,
+    };
+    ChannelValidator::new("altitude", limits)
+}
+
+#[test]
+fn test_rejects_garbage() {
+    let mut validator = altitude_validator();
+    let start = Instant::now();
+    let good = validator.validate(Measurement::new(20_000.0, start));
+    assert_eq!(good.quality, Quality::Good);
+
+    for (i, value) in [f32::NAN, f32::INFINITY, -1_000.0, 90_000.0]
+        .iter()
+        .enumerate()
+    {
+        let t = start + Duration::from_secs(i as u64 + 1);
+        let checked = validator.validate(Measurement::new(*value, t));
+        assert_eq!(checked.quality, Quality::Bad);
+        // the last good value is carried instead
+        assert_eq!(checked.value, 20_000.0);
+    }
+}
+
+#[test]
+fn test_filters_spikes() {
+    let mut validator = altitude_validator();
+    let start = Instant::now();
+    for i in 0..5 {
+        let t = start + Duration::from_secs(i);
+        validator.validate(Measurement::new(20_000.0 + i as f32, t));
+    }
+    // altitude 0 is in range but can't be reached in a second
+    let spike = validator.validate(Measurement::new(0.0, start + Duration::from_secs(5)));
+    assert_eq!(spike.quality, Quality::Suspect);
+    assert!(spike.value > 19_990.0, "{}", spike.value);
+    // and it doesn't taint the next sample
+    let next = validator.validate(Measurement::new(20_006.0, start + Duration::from_secs(6)));
+    assert_eq!(next.quality, Quality::Good);
+}
@@ -0,0 +1,91 @@
+use std::time::{Duration, Instant};
+
+use control_apps::valve_driver::{PulseLimits, ValveDriver, ValveEvent};
+
+const TICK: Duration = Duration::from_millis(50);
+
+fn limits() -> PulseLimits {
+    PulseLimits {
+        min_on: Duration::from_millis(500),
+        min_off: Duration::from_millis(500),
+        max_open: Duration::from_secs(10),
+        cooldown: Duration::from_secs(5),
+    }
+}
+
+fn run(driver: &mut ValveDriver, start: Instant, ticks: u32) -> Vec<ValveEvent> {
+    (1..=ticks)
+        .filter_map(|i| driver.tick(start + TICK * i))
+        .collect()
+}
+
+fn event_time(event: &ValveEvent) -> Instant {
+    match event {
+        ValveEvent::Open(t) | ValveEvent::Close(t) => *t,
+    }
+}
+
+#[test]
+fn test_pulses_respect_limits() {
+    let start = Instant::now();
+    let mut driver = ValveDriver::new("dump", limits(), start);
+    // a duty too small to hold open any one tick
+    driver.set_duty(0.1);
+    let events = run(&mut driver, start, 1200);
+    assert!(events.len() > 4);
+    for pair in events.windows(2) {
+        let gap = event_time(&pair[1]) - event_time(&pair[0]);
+        assert!(gap >= Duration::from_millis(500), "{:?}", pair);
+    }
+    // a minute at 10% is about six seconds open
+    let delivered = driver.take_delivered_duty(start + TICK * 1200);
+    assert!((delivered - 0.1).abs() < 0.02, "{}", delivered);
+    // reported next to the duty it was delivering, not the next one
+    assert_eq!(driver.duty(), 0.1);
+    driver.set_duty(1.5);
+    assert_eq!(driver.duty(), 1.0);
+}
+
+#[test]
+fn test_closed_stays_closed() {
+    let start = Instant::now();
+    let mut driver = ValveDriver::new("vent", limits(), start);
+    driver.set_duty(0.0);
+    assert!(run(&mut driver, start, 200).is_empty());
+    assert_eq!(driver.take_delivered_duty(start + TICK * 200), 0.0);
+}
+
+#[test]
+fn test_max_open_forces_cooldown() {
+    let start = Instant::now();
+    let mut driver = ValveDriver::new("vent", limits(), start);
+    driver.set_duty(1.0);
+    let events = run(&mut driver, start, 400);
+    // held open until max_open, then closed for the cooldown
+    assert!(matches!(events[0], ValveEvent::Open(_)));
+    assert!(matches!(events[1], ValveEvent::Close(_)));
+    let open_for = event_time(&events[1]) - event_time(&events[0]);
+    assert!(open_for >= limits().max_open && open_for < limits().max_open + TICK * 2);
+    let closed_for = event_time(&events[2]) - event_time(&events[1]);
+    assert!(closed_for >= limits().cooldown, "{:?}", closed_for);
+}
+
+#[test]
+fn test_dry_run_pulse() {
+    let start = Instant::now();
+    let mut driver = ValveDriver::new("vent", limits(), start);
+    driver.test_pulse(Duration::from_secs(1), start);
+    assert!(driver.is_testing());
+    // the controller can't close it early
+    driver.set_duty(0.0);
+    let events = run(&mut driver, start, 40);
+    assert_eq!(events.len(), 2);
+    assert!(matches!(events[0], ValveEvent::Open(_)));
+    assert!(matches!(events[1], ValveEvent::Close(_)));
+    assert!(event_time(&events[1]) <= start + Duration::from_secs(1) + TICK);
+    assert!(!driver.is_testing());
+    assert!(driver.take_delivered_duty(start + TICK * 40) > 0.0);
+    // handed back to the controller once closed
+    driver.set_duty(0.3);
+    assert_eq!(driver.duty(), 0.3);
+}
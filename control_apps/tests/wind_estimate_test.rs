@@ -0,0 +1,163 @@
+use std::time::{Duration, Instant};
+
+use control_apps::atmosphere::EARTH_RADIUS;
+use control_apps::wind::{WindLayer, WindProfile};
+use control_apps::wind_estimate::{SurveySettings, WindEstimator};
+
+fn estimator() -> WindEstimator {
+    WindEstimator::new(SurveySettings {
+        layer_depth: 500.0,
+        min_samples: 3,
+        min_baseline: Duration::from_secs(10),
+        max_gap: Duration::from_secs(60),
+        max_speed: 150.0,
+    })
+}
+
+// climb at 5 m/s from the ground with a fix every second, drifting with a
+// wind that blows toward east and north in m/s
+fn ascend(estimator: &mut WindEstimator, seconds: u64, east: f32, north: f32) {
+    let start = Instant::now();
+    for t in 0..=seconds {
+        let t = t as f32;
+        let latitude = (north * t / EARTH_RADIUS).to_degrees();
+        let longitude = (east * t / EARTH_RADIUS).to_degrees();
+        estimator.record(
+            latitude,
+            longitude,
+            5.0 * t,
+            start + Duration::from_secs_f32(t),
+        );
+    }
+}
+
+#[test]
+fn test_measures_the_drift() {
+    // 10 m/s from the south
+    let mut estimator = estimator();
+    ascend(&mut estimator, 600, 0.0, 10.0);
+    assert_eq!(estimator.samples(), 60);
+    let profile = estimator.profile();
+    // 3000 m in 500 m layers, 10 samples each
+    assert_eq!(profile.layers().len(), 6);
+    for layer in profile.layers() {
+        assert!((layer.speed - 10.0).abs() < 0.01);
+        assert!((layer.direction - 180.0).abs() < 0.1);
+        assert_eq!(layer.samples, 10);
+    }
+    assert_eq!(profile.layers()[5].top, 3000.0);
+}
+
+#[test]
+fn test_skips_bad_fixes() {
+    let mut estimator = estimator();
+    let start = Instant::now();
+    estimator.record(0.0, 0.0, 1000.0, start);
+    // a 10 km jump in 10 s
+    estimator.record(0.09, 0.0, 1050.0, start + Duration::from_secs(10));
+    // too long after the last fix
+    estimator.record(0.09, 0.0, 1500.0, start + Duration::from_secs(100));
+    assert_eq!(estimator.samples(), 0);
+    // too few samples for a layer
+    estimator.record(0.0901, 0.0, 1550.0, start + Duration::from_secs(110));
+    assert_eq!(estimator.samples(), 1);
+    assert!(estimator.profile().layers().is_empty());
+}
+
+#[test]
+fn test_measured_overrides_forecast() {
+    let layer = |base: f32, top: f32, speed: f32| WindLayer {
+        base,
+        top,
+        speed,
+        direction: 270.0,
+        samples: 0,
+    };
+    let forecast =
+        WindProfile::new(vec![layer(0.0, 2000.0, 5.0), layer(2000.0, 10_000.0, 20.0)]).unwrap();
+    let measured = WindProfile::new(vec![
+        layer(1000.0, 1500.0, 8.0),
+        layer(4000.0, 4500.0, 12.0),
+    ])
+    .unwrap();
+    let wind = forecast.overlay(&measured);
+    let spans: Vec<(f32, f32)> = wind.layers().iter().map(|l| (l.base, l.top)).collect();
+    assert_eq!(
+        spans,
+        vec![
+            (0.0, 1000.0),
+            (1000.0, 1500.0),
+            (4000.0, 4500.0),
+            (4500.0, 10_000.0)
+        ]
+    );
+    assert_eq!(wind.layers()[1].speed, 8.0);
+    // nothing measured leaves the forecast alone
+    assert_eq!(forecast.overlay(&WindProfile::calm()), forecast);
+}
+
+#[test]
+fn test_save_and_load() {
+    let mut estimator = estimator();
+    ascend(&mut estimator, 300, 7.0, -7.0);
+    let profile = estimator.profile();
+    let path = std::env::temp_dir().join(format!("measured_wind_{}.toml", std::process::id()));
+    profile.save(&path).unwrap();
+    let loaded = WindProfile::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.layers().len(), profile.layers().len());
+    for (a, b) in loaded.layers().iter().zip(profile.layers()) {
+        assert_eq!((a.base, a.top, a.samples), (b.base, b.top, b.samples));
+        assert!((a.speed - b.speed).abs() < 0.01);
+        // from the north west
+        assert!((a.direction - 315.0).abs() < 0.1);
+    }
+}
+
+#[test]
+fn test_restore() {
+    // measured before a restart, saved, and picked up again after it
+    let mut before = estimator();
+    ascend(&mut before, 300, 7.0, -7.0);
+    let path = std::env::temp_dir().join(format!("restored_wind_{}.toml", std::process::id()));
+    before.profile().save(&path).unwrap();
+    let saved = WindProfile::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let mut after = estimator();
+    after.restore(&saved);
+    assert_eq!(after.samples(), before.samples());
+    for (a, b) in after
+        .profile()
+        .layers()
+        .iter()
+        .zip(before.profile().layers())
+    {
+        assert_eq!((a.base, a.top, a.samples), (b.base, b.top, b.samples));
+        assert!((a.speed - b.speed).abs() < 0.01);
+        assert!((a.direction - b.direction).abs() < 0.1);
+    }
+    // a layer short of samples fills in with new ones, forecast layers
+    // bring none
+    let short = WindProfile::new(vec![WindLayer {
+        base: 500.0,
+        top: 1000.0,
+        speed: 10.0,
+        direction: 180.0,
+        samples: 2,
+    }])
+    .unwrap();
+    let mut after = estimator();
+    after.restore(&short);
+    after.restore(
+        &WindProfile::new(vec![WindLayer {
+            samples: 0,
+            ..short.layers()[0]
+        }])
+        .unwrap(),
+    );
+    assert!(after.profile().layers().is_empty());
+    ascend(&mut after, 300, 0.0, 10.0);
+    let layer = after.profile().layers()[1];
+    assert_eq!(layer.samples, 12);
+    assert!((layer.speed - 10.0).abs() < 0.01);
+}
@@ -1,6 +1,7 @@
-use std::sync::mpsc::{Receiver, Sender};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread::sleep;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 use std::{env};
 
@@ -9,20 +10,34 @@ use nng::options::protocol::pubsub::Subscribe;
 use nng::options::Options;
 use toml::Value;
 
-use rmp_serde::{Deserializer, Serializer};
+use rmp_serde::Deserializer;
 use serde::Deserialize;
 
-use control_apps::control_mngr::{ControlCommand, ControlMngr};
+use control_apps::altimeter::BaroAltimeter;
+use control_apps::atmosphere::StandardAtmosphere;
+use control_apps::burst::Anomaly;
+use control_apps::control_mngr::{ControlCommand, ControlMngr, ControlMode};
+use control_apps::flight_phase::{FlightPhase, PhaseDetector, PhaseThresholds};
+use control_apps::fusion::{AltitudeFusion, Source, SourceModel};
 use control_apps::measurement::Measurement;
+use control_apps::validation::{ChannelLimits, ChannelValidator};
+use control_apps::valve_driver::{PulseLimits, ValveDriver, ValveEvent};
+use control_apps::wind::WindProfile;
 use mfc::common::ipc::{self};
 use mfc::common::mfc_msgs;
-use mfc::common::mfc_msgs::{AltitudeBoardTlm, MessageCache};
+use mfc::common::mfc_msgs::{
+    AltCtrlSetpoint, AltCtrlStatus, AltitudeBoardTlm, EnduranceStatus, FlightEvent,
+    FlightPhaseStatus, Heartbeat, LandingStatus, LiftGasStatus, WindStatus, MessageCache, Severity, ValveActuation, ValveDuty, ValveTest,
+};
 
 const CYCLE_RATE_HZ: f32 = 1.0;
 const BASE_SLEEP_DURATION_US: Duration =
     Duration::from_micros((1_000_000.0 * CYCLE_RATE_HZ) as u64);
 
-use serde::Serialize;
+const APP_NAME: &str = "control_app";
+
+// how often valve pulses are scheduled
+const VALVE_TICK: Duration = Duration::from_millis(50);
 
 fn tlm_listen(most_recent_msg: Arc<Mutex<MessageCache<AltitudeBoardTlm>>>) {
     let s = nng::Socket::new(nng::Protocol::Sub0).unwrap();
@@ -49,62 +64,455 @@ fn tlm_listen(most_recent_msg: Arc<Mutex<MessageCache<AltitudeBoardTlm>>>) {
     }
 }
 
-fn cmd_send(thread_rx: Receiver<ControlCommand>) {
+/// Everything the updater hands over to be sent each control cycle
+struct ControlReport {
+    mode: ControlMode,
+    pwms: ControlCommand,
+    status: AltCtrlStatus,
+    lift_gas: LiftGasStatus,
+    phase: FlightPhaseStatus,
+    events: Vec<FlightEvent>,
+    endurance: Option<EnduranceStatus>,
+    landing: Option<LandingStatus>,
+    wind: Option<WindProfile>,
+}
+
+fn cmd_send(
+    thread_rx: Receiver<ControlReport>,
+    test_rx: Receiver<ValveTest>,
+    mut vent: ValveDriver,
+    mut dump: ValveDriver,
+    measured_wind_file: PathBuf,
+) {
     let s = nng::Socket::new(nng::Protocol::Pub0).unwrap();
     s.listen(ipc::NNG_PWM_ADDR).unwrap();
 
+    // valves can only be tested before the controller takes over
+    let mut controlling = false;
+
+    let mut last_heartbeat = Instant::now();
     loop {
-        let pwms = match thread_rx.recv() {
-            Ok(v) => v,
+        match thread_rx.recv_timeout(VALVE_TICK) {
+            Ok(ControlReport {
+                mode,
+                pwms,
+                status,
+                lift_gas,
+                phase,
+                events,
+                endurance,
+                landing,
+                wind,
+            }) => {
+                // how the last cycle's commands were actually carried out,
+                // before this cycle's take over
+                let now = Instant::now();
+                let duty = ValveDuty {
+                    vent_commanded: vent.duty(),
+                    vent_delivered: vent.take_delivered_duty(now),
+                    dump_commanded: dump.duty(),
+                    dump_delivered: dump.take_delivered_duty(now),
+                };
+                vent.set_duty(pwms.vent_pwm);
+                dump.set_duty(pwms.dump_pwm);
+                controlling = !matches!(mode, ControlMode::Init | ControlMode::Ready);
+                if let Err(e) = ipc::publish(&s, mfc_msgs::VALVE_DUTY_TOPIC, &duty) {
+                    println!("Failed to send ipc msg; {:?}", e)
+                }
+                if let Err(e) = ipc::publish(&s, mfc_msgs::PWM_TOPIC, &(pwms.vent_pwm, pwms.dump_pwm)) {
+                    println!("Failed to send ipc msg; {:?}", e)
+                }
+                if let Err(e) = ipc::publish(&s, mfc_msgs::ALT_CTRL_STATUS_TOPIC, &status) {
+                    println!("Failed to send ipc msg; {:?}", e)
+                }
+                if let Err(e) = ipc::publish(&s, mfc_msgs::LIFT_GAS_TOPIC, &lift_gas) {
+                    println!("Failed to send ipc msg; {:?}", e)
+                }
+                if let Err(e) = ipc::publish(&s, mfc_msgs::FLIGHT_PHASE_TOPIC, &phase) {
+                    println!("Failed to send ipc msg; {:?}", e)
+                }
+                for event in events {
+                    if let Err(e) = ipc::publish(&s, mfc_msgs::FLIGHT_EVENT_TOPIC, &event) {
+                        println!("Failed to send ipc msg; {:?}", e)
+                    }
+                }
+                if let Some(endurance) = endurance {
+                    if let Err(e) = ipc::publish(&s, mfc_msgs::ENDURANCE_TOPIC, &endurance) {
+                        println!("Failed to send ipc msg; {:?}", e)
+                    }
+                }
+                if let Some(landing) = landing {
+                    if let Err(e) = ipc::publish(&s, mfc_msgs::LANDING_TOPIC, &landing) {
+                        println!("Failed to send ipc msg; {:?}", e)
+                    }
+                }
+                // keep the measured winds for after a restart and for replays,
+                // off the control loop
+                if let Some(wind) = wind {
+                    if let Err(e) = wind.save(&measured_wind_file) {
+                        println!("Failed to save measured winds; {}", e)
+                    }
+                    let layers = wind.layers();
+                    let status = WindStatus {
+                        base_m: layers.iter().map(|l| l.base).collect(),
+                        top_m: layers.iter().map(|l| l.top).collect(),
+                        speed_m_s: layers.iter().map(|l| l.speed).collect(),
+                        direction_deg: layers.iter().map(|l| l.direction).collect(),
+                        samples: layers.iter().map(|l| l.samples).collect(),
+                        timestamp_ms: mfc_msgs::unix_time_ms(),
+                    };
+                    if let Err(e) = ipc::publish(&s, mfc_msgs::WIND_TOPIC, &status) {
+                        println!("Failed to send ipc msg; {:?}", e)
+                    }
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => (),
             Err(e) => {
                 println!("Channel disconnected {:?}", e);
                 break;
             }
         };
 
-        let mut buffer = Vec::new();
-        (pwms.vent_pwm, pwms.dump_pwm)
-            .serialize(&mut Serializer::new(&mut buffer))
-            .unwrap();
+        // pre-flight test pulses, fully open for as long as asked
+        let now = Instant::now();
+        while let Ok(test) = test_rx.try_recv() {
+            if controlling {
+                println!("Ignoring a {} valve test while controlling", test.valve);
+                continue;
+            }
+            let pulse = match Duration::try_from_secs_f32(test.pulse_s) {
+                Ok(v) => v,
+                Err(_) => {
+                    println!("Ignoring a {} valve test of {} s", test.valve, test.pulse_s);
+                    continue;
+                }
+            };
+            match test.valve.as_str() {
+                "vent" => vent.test_pulse(pulse, now),
+                "dump" => dump.test_pulse(pulse, now),
+                other => println!("Ignoring a test of unknown valve {}", other),
+            }
+        }
+
+        // open and close the valves on schedule
+        for (name, driver) in [("vent", &mut vent), ("dump", &mut dump)] {
+            // a test pulse is flagged so the valve itself stays shut
+            let dry_run = driver.is_testing();
+            if let Some(event) = driver.tick(now) {
+                let actuation = ValveActuation {
+                    valve: String::from(name),
+                    open: matches!(event, ValveEvent::Open(_)),
+                    timestamp_ms: mfc_msgs::unix_time_ms(),
+                    dry_run,
+                };
+                if let Err(e) = ipc::publish(&s, mfc_msgs::VALVE_ACTUATION_TOPIC, &actuation) {
+                    println!("Failed to send ipc msg; {:?}", e)
+                }
+            }
+        }
+
+        // keep beating even when there is no telemetry to act on
+        if last_heartbeat.elapsed() >= ipc::HEARTBEAT_PERIOD {
+            if let Err(e) = ipc::publish(&s, mfc_msgs::HEARTBEAT_TOPIC, &Heartbeat::new(APP_NAME)) {
+                println!("Failed to send ipc msg; {:?}", e)
+            }
+            last_heartbeat = Instant::now();
+        }
+    }
+}
+
+/// Compare the board's altitude with one derived from its raw pressure and
+/// temperature, and complain when they disagree by more than `max_diff_m`
+fn cross_check_altitude(tlm: &AltitudeBoardTlm, altimeter: &BaroAltimeter, max_diff_m: f32) {
+    if tlm.pressure <= 0.0 {
+        return; // board didn't report pressure
+    }
+    let temperature = if tlm.temperature > 0.0 {
+        Some(tlm.temperature)
+    } else {
+        None
+    };
+    let baro = altimeter.derive(tlm.pressure, temperature);
+    let diff = tlm.altitude - baro.altitude;
+    if diff.abs() > max_diff_m {
+        println!(
+            "Board altitude {} m disagrees with barometric altitude {} m by {} m",
+            tlm.altitude, baro.altitude, diff
+        );
+    }
+}
 
-        let msg_content = ipc::fmt_nng_msg("pwms", buffer.as_slice());
+/// Plausibility checks for each telemetry channel the controller uses
+struct TlmValidators {
+    altitude: ChannelValidator,
+    gps_altitude: ChannelValidator,
+    ballast_mass: ChannelValidator,
+}
 
-        nng::Message::from_slice(msg_content.as_slice()).unwrap();
-        match s.send(nng::Message::from_slice(msg_content.as_slice()).unwrap()) {
-            Ok(_) => (),
-            Err(e) => println!("Failed to send ipc msg; {:?}", e),
+impl TlmValidators {
+    fn new(config: &Value) -> Self {
+        let median_window = config["median_window"].as_integer().unwrap() as usize;
+        let limits = |min_key: &str, max_key: &str, rate_key: &str| ChannelLimits {
+            min: config[min_key].as_float().unwrap() as f32,
+            max: config[max_key].as_float().unwrap() as f32,
+            max_rate: config[rate_key].as_float().unwrap() as f32,
+            median_window,
+        };
+        let altitude_limits = limits("altitude_min_m", "altitude_max_m", "altitude_max_rate_m_s");
+        TlmValidators {
+            altitude: ChannelValidator::new("altitude", altitude_limits),
+            gps_altitude: ChannelValidator::new("gps_altitude", altitude_limits),
+            ballast_mass: ChannelValidator::new(
+                "ballast_mass",
+                limits("ballast_min_kg", "ballast_max_kg", "ballast_max_rate_kg_s"),
+            ),
         }
     }
 }
 
+/// Pass setpoint commands on to the updater
+fn setpoint_listen(setpoint_tx: Sender<AltCtrlSetpoint>) {
+    let s = nng::Socket::new(nng::Protocol::Sub0).unwrap();
+    s.dial(ipc::NNG_TX_ADDR).unwrap();
+    s.set_opt::<Subscribe>(String::from(mfc_msgs::ALT_CTRL_SETPOINT_TOPIC).into_bytes())
+        .unwrap();
+
+    let topic_len: usize = mfc_msgs::ALT_CTRL_SETPOINT_TOPIC.chars().count() + 1;
+
+    loop {
+        let msg = match s.recv() {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+
+        let mut de = Deserializer::new(&msg.as_slice()[topic_len..]);
+        let setpoint = match Deserialize::deserialize(&mut de) as Result<AltCtrlSetpoint, _> {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+
+        if setpoint_tx.send(setpoint).is_err() {
+            break; // updater is gone
+        }
+    }
+}
+
+/// Pass pre-flight valve test requests on to the valve drivers
+fn valve_test_listen(test_tx: Sender<ValveTest>) {
+    let s = nng::Socket::new(nng::Protocol::Sub0).unwrap();
+    s.dial(ipc::NNG_TX_ADDR).unwrap();
+    s.set_opt::<Subscribe>(format!("{}:", mfc_msgs::VALVE_TEST_TOPIC).into_bytes())
+        .unwrap();
+
+    loop {
+        let msg = match s.recv() {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        let test = match ipc::split_nng_msg(msg.as_slice()).and_then(|(_, body)| ipc::decode(body)) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        if test_tx.send(test).is_err() {
+            break; // valve drivers are gone
+        }
+    }
+}
+
+/// Switch between holding an altitude, holding an ascent rate, floating
+/// within the economy band and landing, or take control back after a burst
+fn apply_setpoint(mngr: &mut ControlMngr, setpoint: &AltCtrlSetpoint) {
+    if setpoint.rearm {
+        mngr.rearm();
+    } else if setpoint.descend {
+        mngr.descend();
+    } else if setpoint.economy {
+        mngr.economize();
+    } else if setpoint.hold_rate {
+        mngr.hold_rate(setpoint.target_rate_m_s);
+    } else {
+        mngr.set_target(setpoint.target_altitude_m);
+        mngr.hold_altitude();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn updater(
     most_recent_msg: Arc<Mutex<MessageCache<AltitudeBoardTlm>>>,
     mngr: &mut ControlMngr,
-    thread_tx: Sender<ControlCommand>,
+    altimeter: BaroAltimeter,
+    baro_crosscheck_m: f32,
+    mut fusion: AltitudeFusion,
+    mut validators: TlmValidators,
+    mut phases: PhaseDetector,
+    setpoint_rx: Receiver<AltCtrlSetpoint>,
+    thread_tx: Sender<ControlReport>,
+    anomaly_reminder: Duration,
 ) {
     let mut start = Instant::now();
+    let mut announced: Option<(Anomaly, Instant)> = None;
+    let mut last_fused: Option<Instant> = None;
+    let mut ballast_mass: Option<Measurement<f32>> = None;
     loop {
+        // take any new setpoints before acting on telemetry
+        while let Ok(setpoint) = setpoint_rx.try_recv() {
+            apply_setpoint(mngr, &setpoint);
+        }
+
         let incoming_msg_guard = most_recent_msg.lock().unwrap();
         let incoming_msg = &incoming_msg_guard;
 
         if let Some(timestamp) = incoming_msg.get_timestamp() {
-            let pwms = mngr.update(
-                Measurement {
-                    value: incoming_msg.msg.altitude,
-                    timestamp,
-                },
-                Measurement {
-                    value: 0.0,
-                    timestamp,
-                },
-                Measurement {
-                    value: incoming_msg.msg.ballast_mass,
-                    timestamp,
-                },
-            );
+            // only validate and fuse each message once
+            if last_fused != Some(timestamp) {
+                let tlm = &incoming_msg.msg;
+                cross_check_altitude(tlm, &altimeter, baro_crosscheck_m);
+                let altitude = validators
+                    .altitude
+                    .validate(Measurement::new(tlm.altitude, timestamp));
+                fusion.update(Source::Baro, altitude);
+                if tlm.gps_fix {
+                    let gps_altitude = validators
+                        .gps_altitude
+                        .validate(Measurement::new(tlm.gps_altitude, timestamp));
+                    fusion.update(Source::Gps, gps_altitude);
+                    mngr.set_position(tlm.latitude, tlm.longitude);
+                    // the balloon drifts with the wind on the way up
+                    if phases.phase() == FlightPhase::Ascent {
+                        mngr.observe_drift(tlm.latitude, tlm.longitude, &gps_altitude);
+                    }
+                }
+                ballast_mass = Some(
+                    validators
+                        .ballast_mass
+                        .validate(Measurement::new(tlm.ballast_mass, timestamp)),
+                );
+                last_fused = Some(timestamp);
+            }
             std::mem::drop(incoming_msg_guard); // release the lock
 
-            thread_tx.send(pwms).unwrap();
+            if let (Some(fused), Some(ballast)) = (fusion.estimate(), ballast_mass) {
+                let transition = phases.update(&fused.altitude, &fused.ascent_rate);
+                let pwms = mngr.update(fused.altitude, fused.ascent_rate, ballast);
+
+                let budget = mngr.get_remaining_budget();
+                let gains = mngr.get_gains();
+                let status = AltCtrlStatus {
+                    cutdown: false,
+                    mode: mngr.get_mode().to_string(),
+                    status: mngr.get_status().bits(),
+                    vent_budget_s: budget.vent_open_time,
+                    ballast_budget_kg: budget.ballast_mass,
+                    kp: gains.kp,
+                    ki: gains.ki,
+                    kd: gains.kd,
+                    target_rate_m_s: mngr.get_target_rate(),
+                    sun_elevation_deg: mngr.get_sun_elevation(),
+                    expected_duration_s: mngr.get_expected_duration().map(|d| d.as_secs_f32()),
+                };
+                let report = mngr.get_lift_gas();
+                let lift_gas = LiftGasStatus {
+                    remaining_kg: report.remaining,
+                    vented_kg: report.vented,
+                    vent_open_s: report.vent_open_time.as_secs_f32(),
+                    free_lift_kg: report.free_lift,
+                    equilibrium_altitude_m: report.equilibrium_altitude,
+                };
+                let phase = FlightPhaseStatus {
+                    phase: phases.phase().to_string(),
+                    previous: phases.previous().to_string(),
+                    since_ms: mfc_msgs::unix_time_ms()
+                        .saturating_sub(phases.since().elapsed().as_millis() as u64),
+                };
+                let mut events = Vec::new();
+                if let Some(transition) = transition {
+                    events.push(FlightEvent {
+                        app: String::from(APP_NAME),
+                        severity: Severity::Info,
+                        event: String::from("FlightPhase"),
+                        message: format!("{} --> {}", transition.from, transition.to),
+                        timestamp_ms: mfc_msgs::unix_time_ms()
+                            .saturating_sub(transition.at.elapsed().as_millis() as u64),
+                    });
+                }
+                // let everyone know the balloon is lost when it happens, and
+                // again every so often for a late or dropped subscriber
+                let anomaly = mngr.get_anomaly();
+                let due = match announced {
+                    Some((last, at)) => {
+                        anomaly != Some(last)
+                            || (anomaly_reminder > Duration::from_secs(0)
+                                && at.elapsed() >= anomaly_reminder)
+                    }
+                    None => true,
+                };
+                if anomaly.is_none() {
+                    announced = None; // rearmed, announce the next one
+                }
+                if let (Some(anomaly), true) = (anomaly, due) {
+                    announced = Some((anomaly, Instant::now()));
+                    events.push(FlightEvent {
+                        app: String::from(APP_NAME),
+                        severity: Severity::Error,
+                        event: anomaly.to_string(),
+                        message: format!(
+                            "{} at {} m, {} m/s, control is now {}",
+                            anomaly,
+                            fused.altitude.value,
+                            fused.ascent_rate.value,
+                            mngr.get_mode()
+                        ),
+                        timestamp_ms: mfc_msgs::unix_time_ms(),
+                    });
+                }
+                // how long we can stay up, every so often
+                let endurance = mngr.forecast_endurance().map(|forecast| {
+                    let per_hour = 3600.0;
+                    EnduranceStatus {
+                        remaining_s: forecast.remaining.map(|d| d.as_secs_f32()),
+                        limited_by: forecast.limit.map_or(String::new(), |l| l.to_string()),
+                        usable_ballast_kg: forecast.usable_ballast,
+                        spare_gas_kg: forecast.spare_gas,
+                        day_ballast_kg_hr: forecast.day.ballast * per_hour,
+                        night_ballast_kg_hr: forecast.night.ballast * per_hour,
+                        day_gas_kg_hr: forecast.day.gas * per_hour,
+                        night_gas_kg_hr: forecast.night.gas * per_hour,
+                        timestamp_ms: mfc_msgs::unix_time_ms(),
+                    }
+                });
+                // where recovery should head, every cycle on the way down
+                let descending = phases.phase() == FlightPhase::Descent
+                    || mngr.get_mode() == ControlMode::Descend;
+                let landing = if descending {
+                    mngr.predict_landing().map(|prediction| LandingStatus {
+                        latitude: prediction.latitude,
+                        longitude: prediction.longitude,
+                        time_to_land_s: prediction.time_to_land.as_secs_f32(),
+                        ellipse_major_m: prediction.ellipse.semi_major,
+                        ellipse_minor_m: prediction.ellipse.semi_minor,
+                        ellipse_bearing_deg: prediction.ellipse.bearing,
+                        descent: mngr.landing_descent().to_string(),
+                        timestamp_ms: mfc_msgs::unix_time_ms(),
+                    })
+                } else {
+                    None
+                };
+                let wind = mngr.survey_wind();
+                thread_tx
+                    .send(ControlReport {
+                        mode: mngr.get_mode(),
+                        pwms,
+                        status,
+                        lift_gas,
+                        phase,
+                        events,
+                        endurance,
+                        landing,
+                        wind,
+                    })
+                    .unwrap();
+            }
         } else {
             std::mem::drop(incoming_msg_guard); // release the lock
         }
@@ -127,13 +535,97 @@ fn main() {
 
     let listener_msg_copy = most_recent_msg.clone();
     let listener_thread = std::thread::spawn(move || tlm_listen(listener_msg_copy));
+    let (setpoint_tx, setpoint_rx) = std::sync::mpsc::channel();
+    let setpoint_thread = std::thread::spawn(move || setpoint_listen(setpoint_tx));
+    let (test_tx, test_rx) = std::sync::mpsc::channel();
+    let test_thread = std::thread::spawn(move || valve_test_listen(test_tx));
+
+    // QNH at the launch site, for deriving altitude from raw pressure
+    let qnh = config["qnh_hpa"].as_float().unwrap() as f32 * 100.0; // hPa to Pa
+    let altimeter = BaroAltimeter::new(Box::new(StandardAtmosphere), qnh);
+    let baro_crosscheck_m = config["baro_crosscheck_m"].as_float().unwrap() as f32;
+
+    // altitude and ascent rate the controller acts on
+    let source_model = |noise_key: &str, latency_key: &str| SourceModel {
+        noise: config[noise_key].as_float().unwrap() as f32,
+        latency: Duration::from_secs_f64(config[latency_key].as_float().unwrap()),
+    };
+    let fusion = AltitudeFusion::new(
+        source_model("baro_noise_m", "baro_latency_s"),
+        source_model("gps_noise_m", "gps_latency_s"),
+        config["fusion_accel_noise_m_s2"].as_float().unwrap() as f32,
+        config["fusion_gate_sigma"].as_float().unwrap() as f32,
+        Duration::from_secs_f64(config["sensor_frozen_s"].as_float().unwrap()),
+    );
+
+    let validators = TlmValidators::new(&config);
+
+    // where we are in the flight
+    let phase_thresholds = PhaseThresholds {
+        ascent_rate: config["phase_ascent_rate_m_s"].as_float().unwrap() as f32,
+        float_rate: config["phase_float_rate_m_s"].as_float().unwrap() as f32,
+        descent_rate: config["phase_descent_rate_m_s"].as_float().unwrap() as f32,
+        landed_altitude: config["phase_landed_altitude_m"].as_float().unwrap() as f32,
+        dwell: Duration::from_secs_f64(config["phase_dwell_s"].as_float().unwrap()),
+    };
+    let phases = PhaseDetector::new(phase_thresholds, Instant::now());
+
+    // what the on/off solenoids can take
+    let seconds = |key: &str| Duration::from_secs_f64(config[key].as_float().unwrap());
+    let pulse_limits = PulseLimits {
+        min_on: seconds("valve_min_on_s"),
+        min_off: seconds("valve_min_off_s"),
+        max_open: seconds("valve_max_open_s"),
+        cooldown: seconds("valve_cooldown_s"),
+    };
+    let vent_driver = ValveDriver::new("vent", pulse_limits, Instant::now());
+    let dump_driver = ValveDriver::new("dump", pulse_limits, Instant::now());
+    // how often to repeat a burst or leak after announcing it, 0 for never
+    let anomaly_reminder = seconds("anomaly_reminder_s");
+
+    // forecast winds to predict the landing with, and where to keep the
+    // measured ones, next to this config
+    let config_file = Path::new(&args[1]);
+    let wind_file = config_file.with_file_name(config["wind_profile_file"].as_str().unwrap());
+    let measured_wind_file =
+        config_file.with_file_name(config["measured_wind_file"].as_str().unwrap());
+    let wind = match WindProfile::load(&wind_file) {
+        Ok(v) => v,
+        Err(e) => {
+            println!("No wind profile, assuming calm air: {}", e);
+            WindProfile::calm()
+        }
+    };
 
     let mut mngr = ControlMngr::new(config);
-    let update_thread = std::thread::spawn(move || updater(most_recent_msg, &mut mngr, thread_tx));
+    mngr.set_wind_profile(wind);
+    // pick up the winds measured before a restart
+    if let Ok(measured) = WindProfile::load(&measured_wind_file) {
+        println!("Restored measured winds from {}", measured_wind_file.display());
+        mngr.restore_wind(&measured);
+    }
+    let update_thread = std::thread::spawn(move || {
+        updater(
+            most_recent_msg,
+            &mut mngr,
+            altimeter,
+            baro_crosscheck_m,
+            fusion,
+            validators,
+            phases,
+            setpoint_rx,
+            thread_tx,
+            anomaly_reminder,
+        )
+    });
 
-    let commander_thread = std::thread::spawn(move || cmd_send(thread_rx));
+    let commander_thread = std::thread::spawn(move || {
+        cmd_send(thread_rx, test_rx, vent_driver, dump_driver, measured_wind_file)
+    });
 
     listener_thread.join().unwrap();
+    setpoint_thread.join().unwrap();
+    test_thread.join().unwrap();
     update_thread.join().unwrap();
     commander_thread.join().unwrap();
 }
@@ -1,6 +1,12 @@
+use std::thread::sleep;
 
+use control_apps::burst::Anomaly;
+use mfc::common::ipc;
+use mfc::common::mfc_msgs::{self, DownlinkProfile, Heartbeat, ManagerStatus};
 use mfc::manager::ipc_receiver::*;
 
+const APP_NAME: &str = "manager";
+
 mod manager_state {
     pub struct CutdownStateTracker {
         cutdown_armed: bool,
@@ -38,23 +44,31 @@ mod manager_state {
         pub fn should_we_cutdown(&self) -> bool {
             self.cutdown_armed && (self.cutdown_ground || self.cutdown_ctrl)
         }
+        /// Whether the cutdown safety lock is armed
+        pub fn is_armed(&self) -> bool {
+            self.cutdown_armed
+        }
     }
 }
 
 fn main() {
-    let messages = ManagerIPCReceiver::new();
+    let mut messages = ManagerIPCReceiver::new();
 
-    let mut cutdown_state_tracker = manager_state::CutdownStateTracker::new();
+    let s = nng::Socket::new(nng::Protocol::Pub0).unwrap();
+    s.listen(ipc::NNG_MANAGER_ADDR).unwrap();
 
-    let _alt_ctrl_status_msg = messages.get_alt_ctrl_status();
-    let alt_ctrl_arm_msg = messages.get_alt_ctrl_arm();
-    let ground_cmd_msg = messages.get_ground_cmd();
+    let mut cutdown_state_tracker = manager_state::CutdownStateTracker::new();
+    let mut downlink_profile = DownlinkProfile::Nominal;
 
-    let mut loop_once = false;
     loop {
         // Update received IPC messages
         // TODO: maybe block until we get a message type we want?
-        messages.update().unwrap();
+        if let Err(e) = messages.update() {
+            println!("Failed to receive ipc msgs: {:?}", e)
+        }
+        let _alt_ctrl_status_msg = messages.get_alt_ctrl_status();
+        let alt_ctrl_arm_msg = messages.get_alt_ctrl_arm();
+        let ground_cmd_msg = messages.get_ground_cmd();
 
         //// UPDATE STATE ////
         // Update cutdown state
@@ -70,19 +84,34 @@ fn main() {
             cutdown_state_tracker.set_cutdown_ctrl();
         }
 
+        // once the balloon is lost, track it down for recovery
+        let lost = [Anomaly::Burst, Anomaly::UncommandedDescent].map(|a| a.to_string());
+        if messages.get_flight_events().iter().any(|e| lost.contains(&e.event)) {
+            if downlink_profile != DownlinkProfile::DescentTracking {
+                println!("Balloon lost, switching the downlink to descent tracking");
+            }
+            downlink_profile = DownlinkProfile::DescentTracking;
+        }
+
         //// COMMAND /////
         if cutdown_state_tracker.should_we_cutdown() {
             // SEND CUTDOWN COMMAND
         }
 
-        // tmp for dev
-        println!("Finished loop");
-
-        if loop_once {
-            break;
+        //// REPORT ////
+        let status = ManagerStatus {
+            cutdown_armed: cutdown_state_tracker.is_armed(),
+            cutdown_commanded: cutdown_state_tracker.should_we_cutdown(),
+            downlink_profile,
+        };
+        if let Err(e) = ipc::publish(&s, mfc_msgs::MANAGER_STATUS_TOPIC, &status) {
+            println!("Failed to send ipc msg: {:?}", e)
+        }
+        if let Err(e) = ipc::publish(&s, mfc_msgs::HEARTBEAT_TOPIC, &Heartbeat::new(APP_NAME)) {
+            println!("Failed to send ipc msg: {:?}", e)
         }
 
-        loop_once = true;
+        sleep(ipc::HEARTBEAT_PERIOD);
     }
 
     // 1) recv msgs (cache messages that have been received, may need to add timestamps)
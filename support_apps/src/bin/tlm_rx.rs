@@ -3,10 +3,12 @@ extern crate rmp_serde as rmps;
 use std::collections::HashMap;
 use std::io::{Write};
 use std::net::{SocketAddr, UdpSocket};
-use std::sync::mpsc::{Receiver, SyncSender};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender};
+use std::time::Instant;
 use std::{thread, time};
 
 use mfc::common::mfc_msgs;
+use mfc::common::mfc_msgs::Heartbeat;
 use mfc::common::ipc;
 
 
@@ -14,6 +16,7 @@ use mfc::common::ipc;
 
 
 static UDP_RX_ADDR: ([u8; 4], u16) = ([127, 0, 0, 1], 6666);
+const APP_NAME: &str = "tlm_rx";
 
 #[derive(Debug, PartialEq, serde::Deserialize)]
 struct LatLon {
@@ -80,15 +83,27 @@ fn ipc_tx_loop(thread_rx: Receiver<Vec<u8>>) {
     let ext_to_topic_map: HashMap<u8, &str> = [
         (1, mfc_msgs::ALT_CTRL_TOPIC),
         (2, "power"),
-        (3, "ground"),
+        (3, mfc_msgs::GROUND_CMD_TOPIC),
         (4, "avionics"),
         (5, "altctrl"),
+        (6, mfc_msgs::ALT_CTRL_SETPOINT_TOPIC),
+        (7, mfc_msgs::VALVE_TEST_TOPIC),
     ].iter().cloned().collect();
 
+    let mut last_heartbeat = Instant::now();
     loop {
+        // let the rest of the system know we're alive, even if nothing is coming in
+        if last_heartbeat.elapsed() >= ipc::HEARTBEAT_PERIOD {
+            if let Err(e) = ipc::publish(&s, mfc_msgs::HEARTBEAT_TOPIC, &Heartbeat::new(APP_NAME)) {
+                println!("Failed to send ipc msg: {:?}", e)
+            }
+            last_heartbeat = Instant::now();
+        }
+
         // listen for messages from other threads
-        let buf = match thread_rx.recv() {
+        let buf = match thread_rx.recv_timeout(ipc::HEARTBEAT_PERIOD) {
             Ok(v) => v,
+            Err(RecvTimeoutError::Timeout) => continue,
             Err(e) => {
                 println!("Channel disconnected: {:?}", e);
                 break;
@@ -1,6 +1,39 @@
+use std::time::Duration;
+
+use rmp_serde::{Deserializer, Serializer};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
 pub static NNG_TX_ADDR: &str = "ipc:///tmp/nucleus";
 pub static NNG_PWM_ADDR: &str = "ipc:///tmp/nucleus_pwm"; 
+pub static NNG_MANAGER_ADDR: &str = "ipc:///tmp/nucleus_manager";
+pub static NNG_HEALTH_ADDR: &str = "ipc:///tmp/nucleus_health";
+
+/// How often apps publish a heartbeat on their bus socket
+pub const HEARTBEAT_PERIOD: Duration = Duration::from_secs(1);
 
 pub fn fmt_nng_msg(topic: &str, body: &[u8]) -> Vec<u8> {
     [topic.as_bytes(), ":".as_bytes(), body].concat()
 }
+
+/// Serialize a message to msgpack and publish it on the socket under a topic
+pub fn publish<T: Serialize>(s: &nng::Socket, topic: &str, msg: &T) -> Result<(), nng::Error> {
+    let mut buffer = Vec::new();
+    msg.serialize(&mut Serializer::new(&mut buffer)).unwrap();
+    let msg_content = fmt_nng_msg(topic, buffer.as_slice());
+    s.send(nng::Message::from_slice(msg_content.as_slice()).unwrap())
+        .map_err(|(_, e)| e)
+}
+
+/// Split a received NNG message into its topic and body
+pub fn split_nng_msg(msg: &[u8]) -> Option<(&str, &[u8])> {
+    let sep = msg.iter().position(|b| *b == b':')?;
+    let topic = std::str::from_utf8(&msg[..sep]).ok()?;
+    Some((topic, &msg[sep + 1..]))
+}
+
+/// Deserialize a msgpack message body
+pub fn decode<T: DeserializeOwned>(body: &[u8]) -> Option<T> {
+    let mut de = Deserializer::new(body);
+    Deserialize::deserialize(&mut de).ok()
+}
@@ -1,4 +1,4 @@
-use std::time::Instant;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use serde::{Serialize, Deserialize};
 
 pub trait MFCMessage: Default + Serialize {}
@@ -25,6 +25,7 @@ impl<T: MFCMessage> Default for MessageCache<T> {
 impl<T: MFCMessage> MessageCache<T> {
     pub fn update(&mut self, new_msg: T) {
         self.timestamp = Instant::now();
+        self.updated = true;
         self.msg = new_msg;
     }
 
@@ -47,6 +48,23 @@ pub const ALT_CTRL_TOPIC: &str = "altitude";
 pub struct AltitudeBoardTlm {
     pub altitude: f32,
     pub ballast_mass: f32,
+    /// Static pressure in Pa, 0 if the board didn't report it
+    #[serde(default)]
+    pub pressure: f32,
+    /// Air temperature in K, 0 if the board didn't report it
+    #[serde(default)]
+    pub temperature: f32,
+    /// GPS altitude in m, only meaningful with a fix
+    #[serde(default)]
+    pub gps_altitude: f32,
+    #[serde(default)]
+    pub gps_fix: bool,
+    /// GPS latitude in degrees north, only meaningful with a fix
+    #[serde(default)]
+    pub latitude: f32,
+    /// GPS longitude in degrees east, only meaningful with a fix
+    #[serde(default)]
+    pub longitude: f32,
 }
 
 impl MFCMessage for AltitudeBoardTlm {}
@@ -56,26 +74,389 @@ impl Default for AltitudeBoardTlm {
         AltitudeBoardTlm {
             altitude: 0.0,
             ballast_mass: 0.0,
+            pressure: 0.0,
+            temperature: 0.0,
+            gps_altitude: 0.0,
+            gps_fix: false,
+            latitude: 0.0,
+            longitude: 0.0,
+        }
+    }
+}
+
+//// Lift Gas ////
+pub const LIFT_GAS_TOPIC: &str = "lift_gas";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LiftGasStatus {
+    /// Lift gas left in kg
+    pub remaining_kg: f32,
+    /// Lift gas vented so far in kg
+    pub vented_kg: f32,
+    /// Equivalent time the vent has been fully open in s
+    pub vent_open_s: f32,
+    /// Lift beyond the system weight in kg
+    pub free_lift_kg: f32,
+    /// Where the balloon would float in m, None if it is coming down
+    pub equilibrium_altitude_m: Option<f32>,
+}
+
+impl MFCMessage for LiftGasStatus {}
+
+impl Default for LiftGasStatus {
+    fn default() -> Self {
+        LiftGasStatus {
+            remaining_kg: 0.0,
+            vented_kg: 0.0,
+            vent_open_s: 0.0,
+            free_lift_kg: 0.0,
+            equilibrium_altitude_m: None,
+        }
+    }
+}
+
+//// Altitude Control PWMs ////
+pub const PWM_TOPIC: &str = "pwms";
+
+//// Flight Phase ////
+pub const FLIGHT_PHASE_TOPIC: &str = "flight_phase";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FlightPhaseStatus {
+    /// Name of the current `FlightPhase`
+    pub phase: String,
+    /// Name of the phase before it
+    pub previous: String,
+    /// Unix time (ms) when the current phase began
+    pub since_ms: u64,
+}
+
+impl MFCMessage for FlightPhaseStatus {}
+
+impl Default for FlightPhaseStatus {
+    fn default() -> Self {
+        FlightPhaseStatus {
+            phase: String::from("Pad"),
+            previous: String::from("Pad"),
+            since_ms: 0,
+        }
+    }
+}
+
+//// Flight Event ////
+pub const FLIGHT_EVENT_TOPIC: &str = "flight_event";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FlightEvent {
+    /// Name of the app that raised the event
+    pub app: String,
+    pub severity: Severity,
+    /// Short name of what happened, e.g. "Burst"
+    pub event: String,
+    pub message: String,
+    /// Unix time (ms) when the event was raised
+    pub timestamp_ms: u64,
+}
+
+impl MFCMessage for FlightEvent {}
+
+impl Default for FlightEvent {
+    fn default() -> Self {
+        FlightEvent {
+            app: String::new(),
+            severity: Severity::Info,
+            event: String::new(),
+            message: String::new(),
+            timestamp_ms: 0,
+        }
+    }
+}
+
+//// Endurance ////
+pub const ENDURANCE_TOPIC: &str = "endurance";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnduranceStatus {
+    /// Time until control aborts at `min_ballast_kg` in s, None if it is
+    /// further off than the forecast looks
+    pub remaining_s: Option<f32>,
+    /// What runs out first, "Ballast" or "LiftGas", empty if nothing does
+    pub limited_by: String,
+    /// Ballast above `min_ballast_kg` in kg
+    pub usable_ballast_kg: f32,
+    /// Lift gas that can still be lost and made up for with ballast in kg
+    pub spare_gas_kg: f32,
+    /// Ballast expected to be dumped per hour with the sun up and down
+    pub day_ballast_kg_hr: f32,
+    pub night_ballast_kg_hr: f32,
+    /// Lift gas expected to be vented per hour with the sun up and down
+    pub day_gas_kg_hr: f32,
+    pub night_gas_kg_hr: f32,
+    /// Unix time (ms) when the forecast was made
+    pub timestamp_ms: u64,
+}
+
+impl MFCMessage for EnduranceStatus {}
+
+impl Default for EnduranceStatus {
+    fn default() -> Self {
+        EnduranceStatus {
+            remaining_s: None,
+            limited_by: String::new(),
+            usable_ballast_kg: 0.0,
+            spare_gas_kg: 0.0,
+            day_ballast_kg_hr: 0.0,
+            night_ballast_kg_hr: 0.0,
+            day_gas_kg_hr: 0.0,
+            night_gas_kg_hr: 0.0,
+            timestamp_ms: 0,
+        }
+    }
+}
+
+//// Landing ////
+pub const LANDING_TOPIC: &str = "landing";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LandingStatus {
+    /// Predicted landing point in degrees north and east
+    pub latitude: f32,
+    pub longitude: f32,
+    /// Time until touchdown in s
+    pub time_to_land_s: f32,
+    /// 1-sigma uncertainty ellipse around the landing point, semi-axes in m
+    pub ellipse_major_m: f32,
+    pub ellipse_minor_m: f32,
+    /// Bearing of the ellipse's major axis in degrees from north
+    pub ellipse_bearing_deg: f32,
+    /// How the descent was modeled, "Parachute", "Venting" or "Steady"
+    pub descent: String,
+    /// Unix time (ms) when the prediction was made
+    pub timestamp_ms: u64,
+}
+
+impl MFCMessage for LandingStatus {}
+
+impl Default for LandingStatus {
+    fn default() -> Self {
+        LandingStatus {
+            latitude: 0.0,
+            longitude: 0.0,
+            time_to_land_s: 0.0,
+            ellipse_major_m: 0.0,
+            ellipse_minor_m: 0.0,
+            ellipse_bearing_deg: 0.0,
+            descent: String::new(),
+            timestamp_ms: 0,
+        }
+    }
+}
+
+//// Wind ////
+pub const WIND_TOPIC: &str = "wind";
+
+/// Winds measured from GPS drift on the way up, one entry per altitude layer
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WindStatus {
+    /// Bottom and top of each layer in m
+    pub base_m: Vec<f32>,
+    pub top_m: Vec<f32>,
+    pub speed_m_s: Vec<f32>,
+    /// Where the wind blows from in degrees clockwise from north
+    pub direction_deg: Vec<f32>,
+    /// GPS velocities averaged into each layer
+    pub samples: Vec<u32>,
+    /// Unix time (ms) when the profile was taken
+    pub timestamp_ms: u64,
+}
+
+impl MFCMessage for WindStatus {}
+
+//// Valve Actuation ////
+pub const VALVE_ACTUATION_TOPIC: &str = "valve_actuation";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ValveActuation {
+    /// Name of the valve that moved
+    pub valve: String,
+    /// True if it opened, false if it closed
+    pub open: bool,
+    /// Unix time (ms) of the event
+    pub timestamp_ms: u64,
+    /// True for a pre-flight test pulse, which must not move the valve
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+impl MFCMessage for ValveActuation {}
+
+//// Valve Test ////
+pub const VALVE_TEST_TOPIC: &str = "valve_test";
+
+/// Ask the control app for a dry-run test pulse, which is only run while it
+/// isn't controlling the balloon
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ValveTest {
+    /// Name of the valve to pulse, "vent" or "dump"
+    pub valve: String,
+    /// How long to hold the valve open in s
+    pub pulse_s: f32,
+}
+
+impl MFCMessage for ValveTest {}
+
+//// Valve Duty ////
+pub const VALVE_DUTY_TOPIC: &str = "valve_duty";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ValveDuty {
+    /// PWM the controller asked for over the last control cycle
+    pub vent_commanded: f32,
+    /// Fraction of the last control cycle the valve was actually open
+    pub vent_delivered: f32,
+    pub dump_commanded: f32,
+    pub dump_delivered: f32,
+}
+
+impl MFCMessage for ValveDuty {}
+
+impl Default for ValveDuty {
+    fn default() -> Self {
+        ValveDuty {
+            vent_commanded: 0.0,
+            vent_delivered: 0.0,
+            dump_commanded: 0.0,
+            dump_delivered: 0.0,
         }
     }
 }
 
 //// Altitude Control Status ////
+pub const ALT_CTRL_STATUS_TOPIC: &str = "altctrl_status";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AltCtrlStatus {
     pub cutdown: bool,
+    /// Name of the control app's current `ControlMode`
+    pub mode: String,
+    /// Raw `ControlStatus` register bits
+    pub status: u32,
+    /// Vent time (s fully open) left in this hour's budget
+    #[serde(default)]
+    pub vent_budget_s: f32,
+    /// Ballast (kg) left in this hour's budget
+    #[serde(default)]
+    pub ballast_budget_kg: f32,
+    /// Scheduled gains the controller is running with
+    #[serde(default)]
+    pub kp: f32,
+    #[serde(default)]
+    pub ki: f32,
+    #[serde(default)]
+    pub kd: f32,
+    /// Ascent rate being held in m/s, if holding one
+    #[serde(default)]
+    pub target_rate_m_s: Option<f32>,
+    /// Sun elevation above the balloon's horizon in degrees, once position is known
+    #[serde(default)]
+    pub sun_elevation_deg: Option<f32>,
+    /// Flight time the ballast and lift gas are expected to last in s, from the
+    /// latest endurance forecast
+    #[serde(default)]
+    pub expected_duration_s: Option<f32>,
 }
 
 impl MFCMessage for AltCtrlStatus {}
 
 impl Default for AltCtrlStatus {
     fn default() -> Self {
-        AltCtrlStatus { cutdown: false }
+        AltCtrlStatus {
+            cutdown: false,
+            mode: String::from("Init"),
+            status: 0,
+            vent_budget_s: 0.0,
+            ballast_budget_kg: 0.0,
+            kp: 0.0,
+            ki: 0.0,
+            kd: 0.0,
+            target_rate_m_s: None,
+            sun_elevation_deg: None,
+            expected_duration_s: None,
+        }
     }
 }
 
-//// Altitude Control Arm ////
+//// Manager Status ////
+pub const MANAGER_STATUS_TOPIC: &str = "manager_status";
+
 #[derive(Debug, Serialize, Deserialize)]
+pub struct ManagerStatus {
+    /// True when the cutdown safety lock is armed
+    pub cutdown_armed: bool,
+    /// True when the manager has decided to cut down
+    pub cutdown_commanded: bool,
+    /// What the downlink should be sending
+    #[serde(default)]
+    pub downlink_profile: DownlinkProfile,
+}
+
+impl MFCMessage for ManagerStatus {}
+
+impl Default for ManagerStatus {
+    fn default() -> Self {
+        ManagerStatus {
+            cutdown_armed: false,
+            cutdown_commanded: false,
+            downlink_profile: DownlinkProfile::Nominal,
+        }
+    }
+}
+
+/// Which telemetry the downlink prioritizes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownlinkProfile {
+    /// The usual mix of housekeeping and control telemetry
+    #[default]
+    Nominal,
+    /// Position and landing predictions as often as possible, once the
+    /// balloon is coming down on its own
+    DescentTracking,
+}
+
+//// Heartbeat ////
+pub const HEARTBEAT_TOPIC: &str = "heartbeat";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Heartbeat {
+    /// Name of the app sending the heartbeat
+    pub app: String,
+    /// Unix time (ms) when the heartbeat was sent
+    pub timestamp_ms: u64,
+}
+
+impl Heartbeat {
+    /// A heartbeat from the given app stamped with the current time
+    pub fn new(app: &str) -> Self {
+        Heartbeat {
+            app: String::from(app),
+            timestamp_ms: unix_time_ms(),
+        }
+    }
+}
+
+impl MFCMessage for Heartbeat {}
+
+/// Milliseconds since the unix epoch, or 0 if the clock is before it
+pub fn unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|t| t.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+//// Altitude Control Arm ////
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct AltCtrlCmd {
     /// True: request actuator control to be armed, false to disarm
     pub arm_actuator: bool,
@@ -85,17 +466,47 @@ pub struct AltCtrlCmd {
 
 impl MFCMessage for AltCtrlCmd {}
 
-impl Default for AltCtrlCmd {
+//// Altitude Control Setpoint ////
+pub const ALT_CTRL_SETPOINT_TOPIC: &str = "altctrl_setpoint";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AltCtrlSetpoint {
+    /// True: hold `target_rate_m_s`, false: hold `target_altitude_m`
+    pub hold_rate: bool,
+    /// Altitude to hold in m
+    pub target_altitude_m: f32,
+    /// Ascent rate to hold in m/s, negative to descend
+    pub target_rate_m_s: f32,
+    /// True: come down and land, ignoring the other fields
+    #[serde(default)]
+    pub descend: bool,
+    /// True: float loosely within the economy band, ignoring the fields above
+    #[serde(default)]
+    pub economy: bool,
+    /// True: clear a burst or leak and go back to `Ready`, ignoring the fields above
+    #[serde(default)]
+    pub rearm: bool,
+}
+
+impl MFCMessage for AltCtrlSetpoint {}
+
+impl Default for AltCtrlSetpoint {
     fn default() -> Self {
-        AltCtrlCmd {
-            arm_actuator: false,
-            cutdown: false,
+        AltCtrlSetpoint {
+            hold_rate: false,
+            target_altitude_m: 0.0,
+            target_rate_m_s: 0.0,
+            descend: false,
+            economy: false,
+            rearm: false,
         }
     }
 }
 
 //// Ground Command ////
-#[derive(Debug, Serialize, Deserialize)]
+pub const GROUND_CMD_TOPIC: &str = "ground";
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
 pub struct GroundCmd {
     pub arm_alt_ctrl: bool,
     pub arm_cutdown: bool,
@@ -104,12 +515,95 @@ pub struct GroundCmd {
 
 impl MFCMessage for GroundCmd {}
 
-impl Default for GroundCmd {
-    fn default() -> Self {
-        GroundCmd {
-            arm_alt_ctrl: false,
-            arm_cutdown: false,
-            cutdown: false,
+//// Health Report ////
+pub const HEALTH_TOPIC: &str = "health";
+
+/// Severity of a single health metric, ordered from least to most severe
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Severity {
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+/// Value reported by a health metric
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MetricValue {
+    Number(f64),
+    Text(String),
+}
+
+/// Which side of its limits a metric is flagged on
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// High values are bad, e.g. CPU load
+    #[default]
+    Above,
+    /// Low values are bad, e.g. free disk space
+    Below,
+}
+
+/// Limits at which a metric is reported as a warning or an error
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Threshold {
+    pub warn: f64,
+    pub error: f64,
+    #[serde(default)]
+    pub direction: Direction,
+}
+
+impl Threshold {
+    /// Flag values that rise to the limits
+    pub fn above(warn: f64, error: f64) -> Self {
+        Threshold {
+            warn,
+            error,
+            direction: Direction::Above,
+        }
+    }
+
+    /// Flag values that fall to the limits
+    pub fn below(warn: f64, error: f64) -> Self {
+        Threshold {
+            warn,
+            error,
+            direction: Direction::Below,
         }
     }
 }
+
+/// A single named measurement of system health
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthMetric {
+    pub name: String,
+    pub value: MetricValue,
+    pub unit: Option<String>,
+    pub severity: Severity,
+    pub threshold: Option<Threshold>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HealthReport {
+    /// Host the report was generated on
+    pub host: String,
+    /// Unix time (s) when the report was generated
+    pub timestamp: u64,
+    pub metrics: Vec<HealthMetric>,
+}
+
+impl HealthReport {
+    /// Most severe level of any metric in the report
+    pub fn worst_severity(&self) -> Severity {
+        self.metrics
+            .iter()
+            .map(|metric| metric.severity)
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+impl MFCMessage for HealthReport {}
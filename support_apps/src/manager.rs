@@ -1 +1,2 @@
+pub mod cutdown;
 pub mod ipc_receiver;
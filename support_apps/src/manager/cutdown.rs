@@ -0,0 +1,57 @@
+use std::fs;
+use std::path::PathBuf;
+
+/// A way of reaching the cutdown hardware
+pub trait CutdownChannel {
+    /// Short label of the backend for logs and reports
+    fn name(&self) -> &str;
+    /// True if the cutdown circuit is intact end to end
+    fn continuity(&self) -> Result<bool, CutdownError>;
+}
+
+#[derive(Debug)]
+pub enum CutdownError {
+    Io(std::io::Error),
+    BadValue(String),
+}
+
+/// Stand-in for the cutdown hardware when testing on the ground
+pub struct SimulatedCutdown {
+    pub continuous: bool,
+}
+
+impl CutdownChannel for SimulatedCutdown {
+    fn name(&self) -> &str {
+        "simulated"
+    }
+
+    fn continuity(&self) -> Result<bool, CutdownError> {
+        Ok(self.continuous)
+    }
+}
+
+/// Cutdown continuity sense line exposed as a sysfs GPIO value file
+pub struct SysfsCutdown {
+    sense_path: PathBuf,
+}
+
+impl SysfsCutdown {
+    pub fn new(sense_path: PathBuf) -> SysfsCutdown {
+        SysfsCutdown { sense_path }
+    }
+}
+
+impl CutdownChannel for SysfsCutdown {
+    fn name(&self) -> &str {
+        "sysfs"
+    }
+
+    fn continuity(&self) -> Result<bool, CutdownError> {
+        let value = fs::read_to_string(&self.sense_path).map_err(CutdownError::Io)?;
+        match value.trim() {
+            "1" => Ok(true),
+            "0" => Ok(false),
+            other => Err(CutdownError::BadValue(String::from(other))),
+        }
+    }
+}
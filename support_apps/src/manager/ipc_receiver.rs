@@ -1,13 +1,20 @@
-use crate::common::mfc_msgs::{AltCtrlCmd, AltCtrlStatus, GroundCmd, MessageCache};
+use nng::options::protocol::pubsub::Subscribe;
+use nng::options::Options;
+
+use crate::common::ipc;
+use crate::common::mfc_msgs::{self, AltCtrlCmd, AltCtrlStatus, FlightEvent, GroundCmd, MessageCache};
 use crate::manager::ipc_receiver::IPCReceiverError::Generic;
 
 /// A structure to store the last received deserialized message for each inbound topic
 /// In the future there will be some methods that assist with setup and receiving.
 #[derive(Debug)]
 pub struct ManagerIPCReceiver {
+    socket: nng::Socket,
     alt_ctrl_status: MessageCache<AltCtrlStatus>,
     alt_ctrl_arm: MessageCache<AltCtrlCmd>,
     ground_cmd: MessageCache<GroundCmd>,
+    /// Every event received in the last update, not just the latest
+    flight_events: Vec<FlightEvent>,
 }
 
 #[derive(Debug)]
@@ -17,10 +24,26 @@ pub enum IPCReceiverError {
 
 impl ManagerIPCReceiver {
     pub fn new() -> ManagerIPCReceiver {
+        let socket = nng::Socket::new(nng::Protocol::Sub0).unwrap();
+        for topic in [
+            mfc_msgs::ALT_CTRL_STATUS_TOPIC,
+            mfc_msgs::GROUND_CMD_TOPIC,
+            mfc_msgs::FLIGHT_EVENT_TOPIC,
+        ] {
+            socket
+                .set_opt::<Subscribe>(format!("{}:", topic).into_bytes())
+                .unwrap();
+        }
+        // ground commands come in through tlm_rx, the rest from the control
+        // app; keep retrying in the background until they're up
+        socket.dial_async(ipc::NNG_TX_ADDR).unwrap();
+        socket.dial_async(ipc::NNG_PWM_ADDR).unwrap();
         ManagerIPCReceiver {
+            socket,
             alt_ctrl_status: MessageCache::<AltCtrlStatus>::default(),
             alt_ctrl_arm: MessageCache::<AltCtrlCmd>::default(),
             ground_cmd: MessageCache::<GroundCmd>::default(),
+            flight_events: Vec::new(),
         }
     }
 
@@ -36,7 +59,41 @@ impl ManagerIPCReceiver {
         &self.ground_cmd
     }
 
-    pub fn update(&self) -> Result<(), IPCReceiverError> {
-        Err(Generic)
+    pub fn get_flight_events(&self) -> &[FlightEvent] {
+        &self.flight_events
+    }
+
+    /// Take every message that arrived since the last update, without blocking
+    pub fn update(&mut self) -> Result<(), IPCReceiverError> {
+        self.flight_events.clear();
+        loop {
+            let msg = match self.socket.try_recv() {
+                Ok(v) => v,
+                Err(nng::Error::TryAgain) => return Ok(()),
+                Err(_) => return Err(Generic),
+            };
+            let (topic, body) = match ipc::split_nng_msg(msg.as_slice()) {
+                Some(v) => v,
+                None => continue,
+            };
+            match topic {
+                mfc_msgs::ALT_CTRL_STATUS_TOPIC => {
+                    if let Some(status) = ipc::decode(body) {
+                        self.alt_ctrl_status.update(status);
+                    }
+                }
+                mfc_msgs::GROUND_CMD_TOPIC => {
+                    if let Some(cmd) = ipc::decode(body) {
+                        self.ground_cmd.update(cmd);
+                    }
+                }
+                mfc_msgs::FLIGHT_EVENT_TOPIC => {
+                    if let Some(event) = ipc::decode(body) {
+                        self.flight_events.push(event);
+                    }
+                }
+                _ => (),
+            }
+        }
     }
 }
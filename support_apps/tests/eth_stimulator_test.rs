@@ -23,6 +23,12 @@ fn test_eth_rx_relay() {
     let tlm = AltitudeBoardTlm {
         altitude: 80000.0,
         ballast_mass: 0.0,
+        pressure: 1.05,
+        temperature: 198.6,
+        gps_altitude: 80012.0,
+        gps_fix: true,
+        latitude: 41.3,
+        longitude: -105.6,
     };
 
     let mut buf = Vec::new();
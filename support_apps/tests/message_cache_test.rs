@@ -0,0 +1,21 @@
+use mfc::common::mfc_msgs::{AltitudeBoardTlm, MessageCache};
+
+#[test]
+fn test_timestamp_once_updated() {
+    let mut cache = MessageCache::<AltitudeBoardTlm>::default();
+    // nothing has arrived yet
+    assert!(cache.get_timestamp().is_none());
+
+    let tlm = AltitudeBoardTlm {
+        altitude: 24000.0,
+        ..AltitudeBoardTlm::default()
+    };
+    cache.update(tlm);
+    let first = cache.get_timestamp().unwrap();
+    assert_eq!(cache.msg.altitude, 24000.0);
+
+    // every message gets its own timestamp
+    std::thread::sleep(std::time::Duration::from_millis(1));
+    cache.update(AltitudeBoardTlm::default());
+    assert!(cache.get_timestamp().unwrap() > first);
+}